@@ -1,246 +1,468 @@
+mod batch_tracker;
+mod batches;
+#[cfg(feature = "bedrock")]
+mod bedrock;
+mod body;
+mod buffer_pool;
+mod cancel;
+mod client;
+mod concurrency;
+mod connection_pool;
+mod count_tokens;
+mod deadline;
+mod dry_run;
+#[cfg(any(test, feature = "test-support"))]
+mod fake;
+mod files;
+mod instrument;
+mod middleware;
+mod models;
+mod profiles;
+#[cfg(feature = "language-model")]
+mod provider;
+mod proxy_config;
+mod rate_limit;
+mod raw;
+mod retry;
+mod sampling;
+mod shrink_retry;
+mod speculative;
+mod sse;
+mod structured_output;
+mod verify;
+mod vertex;
+
 use anyhow::{anyhow, Result};
-use futures::{io::BufReader, stream::BoxStream, AsyncBufReadExt, AsyncReadExt, StreamExt};
-use http::{AsyncBody, HttpClient, Method, Request as HttpRequest};
+use body::RequestBody;
+use deadline::{within, Deadline};
+use futures::stream::BoxStream;
+use http::{AsyncBody, HttpClient, Method, Request as HttpRequest, Response as HttpResponse};
+use instrument::{record_status, RequestSpan};
 use isahc::config::Configurable;
-use serde::{Deserialize, Serialize};
-use std::{convert::TryFrom, time::Duration};
-use strum::EnumIter;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{borrow::Cow, sync::Arc, time::Duration};
+
+pub use anthropic_types::*;
+pub use batch_tracker::*;
+pub use batches::*;
+#[cfg(feature = "bedrock")]
+pub use bedrock::*;
+pub use body::{ContentEncoding, RequestCompression, ACCEPT_ENCODING};
+pub use buffer_pool::*;
+pub use cancel::*;
+pub use client::*;
+pub use concurrency::*;
+pub use connection_pool::*;
+pub use count_tokens::*;
+pub use deadline::DeadlineExceeded;
+pub use dry_run::*;
+#[cfg(any(test, feature = "test-support"))]
+pub use fake::*;
+pub use files::*;
+pub use middleware::Middleware;
+pub use models::*;
+pub use profiles::*;
+#[cfg(feature = "language-model")]
+pub use provider::*;
+pub use proxy_config::*;
+pub use rate_limit::*;
+pub use raw::*;
+pub use retry::*;
+pub use sampling::*;
+pub use shrink_retry::*;
+pub use speculative::*;
+pub use sse::*;
+pub use structured_output::*;
+pub use verify::*;
+pub use vertex::*;
 
 pub const ANTHROPIC_API_URL: &'static str = "https://api.anthropic.com";
 
-#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
-#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, EnumIter)]
-pub enum Model {
-    #[default]
-    #[serde(alias = "claude-3-5-sonnet", rename = "claude-3-5-sonnet-20240620")]
-    Claude3_5Sonnet,
-    #[serde(alias = "claude-3-opus", rename = "claude-3-opus-20240229")]
-    Claude3Opus,
-    #[serde(alias = "claude-3-sonnet", rename = "claude-3-sonnet-20240229")]
-    Claude3Sonnet,
-    #[serde(alias = "claude-3-haiku", rename = "claude-3-haiku-20240307")]
-    Claude3Haiku,
-    #[serde(rename = "custom")]
-    Custom {
-        name: String,
-        #[serde(default)]
-        max_tokens: Option<usize>,
-    },
+/// Transport settings applied to every request sent to the API.
+#[derive(Clone, Debug, Default)]
+pub struct ClientOptions {
+    pub low_speed_timeout: Option<Duration>,
+    pub request_compression: Option<RequestCompression>,
+    /// The size of the buffer used to read streamed responses. Defaults to
+    /// [`DEFAULT_READ_BUFFER_SIZE`].
+    pub read_buffer_size: Option<usize>,
+    /// The beta features enabled for every request, in addition to each
+    /// request's own [`Request::betas`]. Defaults to [`DEFAULT_BETAS`].
+    pub betas: Option<Vec<String>>,
+    /// The API version sent in the `Anthropic-Version` header, unless a
+    /// request sets [`Request::api_version`]. Defaults to
+    /// [`DEFAULT_API_VERSION`].
+    pub api_version: Option<String>,
+    /// How the API key passed to each function authenticates the request.
+    pub auth_scheme: AuthScheme,
+    /// Whether the spans emitted with the `tracing` feature include the
+    /// system prompt and messages of each request, which are left out by
+    /// default.
+    pub trace_contents: bool,
 }
 
-impl Model {
-    pub fn from_id(id: &str) -> Result<Self> {
-        if id.starts_with("claude-3-5-sonnet") {
-            Ok(Self::Claude3_5Sonnet)
-        } else if id.starts_with("claude-3-opus") {
-            Ok(Self::Claude3Opus)
-        } else if id.starts_with("claude-3-sonnet") {
-            Ok(Self::Claude3Sonnet)
-        } else if id.starts_with("claude-3-haiku") {
-            Ok(Self::Claude3Haiku)
-        } else {
-            Ok(Self::Custom {
-                name: id.to_string(),
-                max_tokens: None,
-            })
-        }
-    }
-
-    pub fn id(&self) -> &str {
-        match self {
-            Model::Claude3_5Sonnet => "claude-3-5-sonnet-20240620",
-            Model::Claude3Opus => "claude-3-opus-20240229",
-            Model::Claude3Sonnet => "claude-3-sonnet-20240229",
-            Model::Claude3Haiku => "claude-3-opus-20240307",
-            Model::Custom { name, .. } => name,
-        }
-    }
+/// How requests are authenticated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AuthScheme {
+    /// The key is sent in the `X-Api-Key` header.
+    #[default]
+    ApiKey,
+    /// The key is an access token sent in an `Authorization: Bearer` header,
+    /// as expected by some gateways and OAuth clients.
+    Bearer,
+}
 
-    pub fn display_name(&self) -> &str {
-        match self {
-            Self::Claude3_5Sonnet => "Claude 3.5 Sonnet",
-            Self::Claude3Opus => "Claude 3 Opus",
-            Self::Claude3Sonnet => "Claude 3 Sonnet",
-            Self::Claude3Haiku => "Claude 3 Haiku",
-            Self::Custom { name, .. } => name,
-        }
-    }
+pub const DEFAULT_API_VERSION: &str = "2023-06-01";
 
-    pub fn max_token_count(&self) -> usize {
-        match self {
-            Self::Claude3_5Sonnet
-            | Self::Claude3Opus
-            | Self::Claude3Sonnet
-            | Self::Claude3Haiku => 200_000,
-            Self::Custom { max_tokens, .. } => max_tokens.unwrap_or(200_000),
-        }
+/// Returns `options` with the API version overridden by `request`'s, if it
+/// sets one.
+fn request_options<'a>(request: &Request, options: &'a ClientOptions) -> Cow<'a, ClientOptions> {
+    match &request.api_version {
+        Some(api_version) => Cow::Owned(ClientOptions {
+            api_version: Some(api_version.clone()),
+            ..options.clone()
+        }),
+        None => Cow::Borrowed(options),
     }
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize, Debug, Eq, PartialEq)]
-#[serde(rename_all = "lowercase")]
-pub enum Role {
-    User,
-    Assistant,
-}
+/// The beta features enabled for every request unless
+/// [`ClientOptions::betas`] is set.
+pub const DEFAULT_BETAS: &[&str] = &["tools-2024-04-04"];
 
-impl TryFrom<String> for Role {
-    type Error = anyhow::Error;
-
-    fn try_from(value: String) -> Result<Self> {
-        match value.as_str() {
-            "user" => Ok(Self::User),
-            "assistant" => Ok(Self::Assistant),
-            _ => Err(anyhow!("invalid role '{value}'")),
+/// Returns the value of the `Anthropic-Beta` header enabling the betas of
+/// `options` and `betas`, or `None` if there are none.
+fn beta_header(betas: &[String], options: &ClientOptions) -> Option<String> {
+    let mut enabled: Vec<&str> = match &options.betas {
+        Some(default_betas) => default_betas.iter().map(String::as_str).collect(),
+        None => DEFAULT_BETAS.to_vec(),
+    };
+    for beta in betas {
+        if !enabled.contains(&beta.as_str()) {
+            enabled.push(beta);
         }
     }
+    (!enabled.is_empty()).then(|| enabled.join(","))
 }
 
-impl From<Role> for String {
-    fn from(val: Role) -> Self {
-        match val {
-            Role::User => "user".to_owned(),
-            Role::Assistant => "assistant".to_owned(),
-        }
-    }
+pub async fn stream_completion(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: Request,
+    low_speed_timeout: Option<Duration>,
+) -> Result<BoxStream<'static, Result<ResponseEvent>>> {
+    let options = ClientOptions {
+        low_speed_timeout,
+        ..Default::default()
+    };
+    let (reader, _, span) = connect_stream(client, api_url, api_key, request, &options).await?;
+    Ok(span.stream(reader.into_stream()))
 }
 
-#[derive(Debug, Serialize)]
-pub struct Request {
-    #[serde(serialize_with = "serialize_request_model")]
-    pub model: Model,
-    pub messages: Vec<RequestMessage>,
-    pub stream: bool,
-    pub system: String,
-    pub max_tokens: u32,
+/// Like [`stream_completion`], but returns the underlying [`EventReader`] so
+/// that events can be read as [`ResponseEventRef`]s borrowing from its buffer.
+///
+/// The request's [`Request::timeout`] bounds reading the events as well: once
+/// it passes, the reader fails with a [`DeadlineExceeded`] error.
+pub async fn stream_completion_reader(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: Request,
+    options: &ClientOptions,
+) -> Result<EventReader<AsyncBody>> {
+    let (reader, _) =
+        stream_completion_with_rate_limit(client, api_url, api_key, request, options).await?;
+    Ok(reader)
 }
 
-fn serialize_request_model<S>(model: &Model, serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: serde::Serializer,
-{
-    serializer.serialize_str(&model.id())
+/// Like [`stream_completion_reader`], but also returns the account's rate
+/// limits as reported with the response, so that callers can pace their
+/// requests.
+pub async fn stream_completion_with_rate_limit(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: Request,
+    options: &ClientOptions,
+) -> Result<(EventReader<AsyncBody>, RateLimitInfo)> {
+    let (reader, rate_limit, _) =
+        connect_stream(client, api_url, api_key, request, options).await?;
+    Ok((reader, rate_limit))
 }
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
-pub struct RequestMessage {
-    pub role: Role,
-    pub content: String,
+/// Sends a streaming request, returning the span tracing it along with the
+/// response.
+pub(crate) async fn connect_stream(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: Request,
+    options: &ClientOptions,
+) -> Result<(EventReader<AsyncBody>, RateLimitInfo, RequestSpan)> {
+    let deadline = Deadline::after(request.timeout);
+    let request = Arc::new(request);
+    let body = body::encode_request_body(request.clone(), options.request_compression)?;
+    connect_stream_with_body(client, api_url, api_key, &request, body, deadline, options).await
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(tag = "type", rename_all = "snake_case")]
-pub enum ResponseEvent {
-    MessageStart {
-        message: ResponseMessage,
-    },
-    ContentBlockStart {
-        index: u32,
-        content_block: ContentBlock,
-    },
-    Ping {},
-    ContentBlockDelta {
-        index: u32,
-        delta: TextDelta,
-    },
-    ContentBlockStop {
-        index: u32,
-    },
-    MessageDelta {
-        delta: ResponseMessage,
-        usage: Usage,
-    },
-    MessageStop {},
+/// Like [`connect_stream`], but sends `body` as the encoding of `request`,
+/// failing once `deadline` passes. Attempts of the same request share its
+/// deadline.
+pub(crate) async fn connect_stream_with_body(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: &Request,
+    body: RequestBody,
+    deadline: Option<Deadline>,
+    options: &ClientOptions,
+) -> Result<(EventReader<AsyncBody>, RateLimitInfo, RequestSpan)> {
+    let span = RequestSpan::new(request, options);
+    let (reader, rate_limit) = span
+        .send(within(
+            deadline,
+            send_stream_request(client, api_url, api_key, request, body, options),
+        ))
+        .await?;
+    Ok((reader.with_deadline(deadline), rate_limit, span))
 }
 
-#[derive(Deserialize, Debug)]
-pub struct ResponseMessage {
-    #[serde(rename = "type")]
-    pub message_type: Option<String>,
-    pub id: Option<String>,
-    pub role: Option<String>,
-    pub content: Option<Vec<String>>,
-    pub model: Option<String>,
-    pub stop_reason: Option<String>,
-    pub stop_sequence: Option<String>,
-    pub usage: Option<Usage>,
+async fn send_stream_request(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: &Request,
+    body: RequestBody,
+    options: &ClientOptions,
+) -> Result<(EventReader<AsyncBody>, RateLimitInfo)> {
+    let uri = format!("{api_url}/v1/messages");
+    let mut request_builder = api_request_builder(
+        Method::POST,
+        &uri,
+        api_key,
+        &request.betas,
+        &request_options(request, options),
+    );
+    if let Some(content_encoding) = body.content_encoding {
+        request_builder = request_builder.header("Content-Encoding", content_encoding.as_str());
+    }
+    let request = request_builder.body(body.body)?;
+    let mut response = client.send(request).await?;
+    record_status(response.status().as_u16());
+    if response.status().is_success() {
+        let rate_limit = RateLimitInfo::from_headers(response.headers());
+        let request_id = request_id(&response);
+        let reader = EventReader::with_buffer_size(
+            response.into_body(),
+            BufferPool::global(),
+            options.read_buffer_size.unwrap_or(DEFAULT_READ_BUFFER_SIZE),
+        )
+        .with_request_id(request_id);
+        Ok((reader, rate_limit))
+    } else {
+        let body = body::read_body(&mut response).await?;
+        let body_str = std::str::from_utf8(&body)?;
+
+        match serde_json::from_str::<ResponseEvent>(body_str) {
+            Ok(event) if !matches!(event, ResponseEvent::Error { .. }) => Err(anyhow!(
+                "Unexpected success response while expecting an error: {}",
+                body_str,
+            )),
+            _ => Err(api_error(&response, body_str).into()),
+        }
+    }
 }
 
-#[derive(Deserialize, Debug)]
-pub struct Usage {
-    pub input_tokens: Option<u32>,
-    pub output_tokens: Option<u32>,
+/// Sends `request` without streaming and returns the complete response, for
+/// callers that don't need incremental output.
+pub async fn complete(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: Request,
+    options: &ClientOptions,
+) -> Result<Message> {
+    let (message, _) = complete_with_rate_limit(client, api_url, api_key, request, options).await?;
+    Ok(message)
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(tag = "type", rename_all = "snake_case")]
-pub enum ContentBlock {
-    Text { text: String },
+/// Like [`complete`], but also returns the account's rate limits as reported
+/// with the response.
+pub async fn complete_with_rate_limit(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    mut request: Request,
+    options: &ClientOptions,
+) -> Result<(Message, RateLimitInfo)> {
+    request.stream = false;
+    let deadline = Deadline::after(request.timeout);
+    let request = Arc::new(request);
+    let body = body::encode_request_body(request.clone(), options.request_compression)?;
+    complete_with_body(client, api_url, api_key, &request, body, deadline, options).await
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(tag = "type", rename_all = "snake_case")]
-pub enum TextDelta {
-    TextDelta { text: String },
+/// Like [`complete_with_rate_limit`], but sends `body` as the encoding of
+/// `request`, which mustn't ask for a stream, failing once `deadline` passes.
+pub(crate) async fn complete_with_body(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: &Request,
+    body: RequestBody,
+    deadline: Option<Deadline>,
+    options: &ClientOptions,
+) -> Result<(Message, RateLimitInfo)> {
+    let span = RequestSpan::new(request, options);
+    let (message, rate_limit) = span
+        .send(within(
+            deadline,
+            send_complete_request(client, api_url, api_key, request, body, options),
+        ))
+        .await?;
+    span.finish(&message.usage);
+    Ok((message, rate_limit))
 }
 
-pub async fn stream_completion(
+async fn send_complete_request(
     client: &dyn HttpClient,
     api_url: &str,
     api_key: &str,
-    request: Request,
-    low_speed_timeout: Option<Duration>,
-) -> Result<BoxStream<'static, Result<ResponseEvent>>> {
+    request: &Request,
+    body: RequestBody,
+    options: &ClientOptions,
+) -> Result<(Message, RateLimitInfo)> {
     let uri = format!("{api_url}/v1/messages");
-    let mut request_builder = HttpRequest::builder()
-        .method(Method::POST)
-        .uri(uri)
-        .header("Anthropic-Version", "2023-06-01")
-        .header("Anthropic-Beta", "tools-2024-04-04")
-        .header("X-Api-Key", api_key)
-        .header("Content-Type", "application/json");
-    if let Some(low_speed_timeout) = low_speed_timeout {
-        request_builder = request_builder.low_speed_timeout(100, low_speed_timeout);
+    let mut request_builder = api_request_builder(
+        Method::POST,
+        &uri,
+        api_key,
+        &request.betas,
+        &request_options(request, options),
+    )
+    .header("Accept-Encoding", ACCEPT_ENCODING);
+    if let Some(content_encoding) = body.content_encoding {
+        request_builder = request_builder.header("Content-Encoding", content_encoding.as_str());
     }
-    let request = request_builder.body(AsyncBody::from(serde_json::to_string(&request)?))?;
+    let request = request_builder.body(body.body)?;
     let mut response = client.send(request).await?;
+    record_status(response.status().as_u16());
+    let body = body::read_body(&mut response).await?;
     if response.status().is_success() {
-        let reader = BufReader::new(response.into_body());
-        Ok(reader
-            .lines()
-            .filter_map(|line| async move {
-                match line {
-                    Ok(line) => {
-                        let line = line.strip_prefix("data: ")?;
-                        match serde_json::from_str(line) {
-                            Ok(response) => Some(Ok(response)),
-                            Err(error) => Some(Err(anyhow!(error))),
-                        }
-                    }
-                    Err(error) => Some(Err(anyhow!(error))),
-                }
-            })
-            .boxed())
+        let rate_limit = RateLimitInfo::from_headers(response.headers());
+        let mut message: Message = serde_json::from_slice(&body)?;
+        message.request_id = request_id(&response);
+        Ok((message, rate_limit))
     } else {
-        let mut body = Vec::new();
-        response.body_mut().read_to_end(&mut body).await?;
+        let body_str = String::from_utf8_lossy(&body);
+        Err(api_error(&response, &body_str).into())
+    }
+}
 
-        let body_str = std::str::from_utf8(&body)?;
+/// Sends a request to one of the API's JSON endpoints and deserializes the
+/// response, turning unsuccessful statuses into an [`ApiError`].
+async fn send_json_request<T: DeserializeOwned>(
+    client: &dyn HttpClient,
+    request: HttpRequest<AsyncBody>,
+) -> Result<T> {
+    let mut response = client.send(request).await?;
+    let body = body::read_body(&mut response).await?;
+    if response.status().is_success() {
+        Ok(serde_json::from_slice(&body)?)
+    } else {
+        let body = String::from_utf8_lossy(&body);
+        Err(api_error(&response, &body).into())
+    }
+}
 
-        match serde_json::from_str::<ResponseEvent>(body_str) {
-            Ok(_) => Err(anyhow!(
-                "Unexpected success response while expecting an error: {}",
-                body_str,
-            )),
-            Err(_) => Err(anyhow!(
-                "Failed to connect to API: {} {}",
-                response.status(),
-                body_str,
-            )),
+/// The number of items requested per page from the API's list endpoints.
+/// Listing is paginated, but all pages are fetched.
+const PAGE_SIZE: usize = 100;
+
+#[derive(Deserialize)]
+struct Page<T> {
+    data: Vec<T>,
+    has_more: bool,
+    last_id: Option<String>,
+}
+
+/// Returns the items of every page of the list endpoint at `uri`, in order.
+async fn fetch_all_pages<T: DeserializeOwned>(
+    client: &dyn HttpClient,
+    uri: &str,
+    api_key: &str,
+    betas: &[String],
+    options: &ClientOptions,
+) -> Result<Vec<T>> {
+    let mut items = Vec::new();
+    let mut after_id = None;
+    loop {
+        let mut page_uri = format!("{uri}?limit={PAGE_SIZE}");
+        if let Some(after_id) = &after_id {
+            page_uri.push_str("&after_id=");
+            page_uri.push_str(after_id);
+        }
+        let request = api_request_builder(Method::GET, &page_uri, api_key, betas, options)
+            .header("Accept-Encoding", ACCEPT_ENCODING)
+            .body(AsyncBody::empty())?;
+        let page: Page<T> = send_json_request(client, request).await?;
+        items.extend(page.data);
+        match page.last_id {
+            Some(last_id) if page.has_more => after_id = Some(last_id),
+            _ => return Ok(items),
         }
     }
 }
 
+/// Returns the `request-id` header of `response`, which identifies the
+/// request in the API's logs.
+fn request_id(response: &HttpResponse<AsyncBody>) -> Option<String> {
+    let request_id = response.headers().get("request-id")?;
+    Some(request_id.to_str().ok()?.to_string())
+}
+
+/// Parses the error in the body of an unsuccessful `response`.
+fn api_error(response: &HttpResponse<AsyncBody>, body: &str) -> ApiError {
+    ApiError::from_response(response.status().as_u16(), body)
+        .with_request_id(request_id(response))
+        .with_retry_after(rate_limit::retry_after(response.headers()))
+}
+
+/// Starts building a request to the API, with the headers and settings shared
+/// by all endpoints.
+fn api_request_builder(
+    method: Method,
+    uri: &str,
+    api_key: &str,
+    betas: &[String],
+    options: &ClientOptions,
+) -> isahc::http::request::Builder {
+    let mut request_builder = HttpRequest::builder()
+        .method(method)
+        .uri(uri)
+        .header(
+            "Anthropic-Version",
+            options
+                .api_version
+                .as_deref()
+                .unwrap_or(DEFAULT_API_VERSION),
+        )
+        .header("Content-Type", "application/json");
+    request_builder = match options.auth_scheme {
+        AuthScheme::ApiKey => request_builder.header("X-Api-Key", api_key),
+        AuthScheme::Bearer => request_builder.header("Authorization", format!("Bearer {api_key}")),
+    };
+    if let Some(beta_header) = beta_header(betas, options) {
+        request_builder = request_builder.header("Anthropic-Beta", beta_header);
+    }
+    if let Some(low_speed_timeout) = options.low_speed_timeout {
+        request_builder = request_builder.low_speed_timeout(100, low_speed_timeout);
+    }
+    request_builder
+}
+
 // #[cfg(test)]
 // mod tests {
 //     use super::*;
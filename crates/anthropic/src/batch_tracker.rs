@@ -0,0 +1,209 @@
+use anyhow::{anyhow, Context as _, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackedBatchStatus {
+    /// The batch was submitted and its results aren't available yet.
+    #[default]
+    Submitted,
+    /// The batch finished processing and its results can be collected.
+    Ended,
+    /// The results of the batch were collected.
+    Collected,
+}
+
+/// A message batch submitted to the API, along with what's needed to make
+/// sense of its results.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TrackedBatch {
+    pub batch_id: String,
+    /// Maps the `custom_id` of each request in the batch to the identifier
+    /// the caller uses for it, such as the path of the file it's about.
+    pub custom_ids: BTreeMap<String, String>,
+    /// When the batch was submitted, in seconds since the Unix epoch.
+    pub submitted_at: u64,
+    #[serde(default)]
+    pub status: TrackedBatchStatus,
+}
+
+/// Where a [`BatchTracker`] persists its batches.
+pub trait BatchStore: Send + Sync {
+    fn load(&self) -> Result<Vec<TrackedBatch>>;
+    fn save(&self, batches: &[TrackedBatch]) -> Result<()>;
+}
+
+impl<T: BatchStore + ?Sized> BatchStore for Arc<T> {
+    fn load(&self) -> Result<Vec<TrackedBatch>> {
+        self.as_ref().load()
+    }
+
+    fn save(&self, batches: &[TrackedBatch]) -> Result<()> {
+        self.as_ref().save(batches)
+    }
+}
+
+/// Keeps batches in memory, e.g. for tests.
+#[derive(Debug, Default)]
+pub struct MemoryBatchStore(Mutex<Vec<TrackedBatch>>);
+
+impl BatchStore for MemoryBatchStore {
+    fn load(&self) -> Result<Vec<TrackedBatch>> {
+        Ok(self.0.lock().map_err(|_| anyhow!("poisoned lock"))?.clone())
+    }
+
+    fn save(&self, batches: &[TrackedBatch]) -> Result<()> {
+        *self.0.lock().map_err(|_| anyhow!("poisoned lock"))? = batches.to_vec();
+        Ok(())
+    }
+}
+
+/// Keeps batches in a JSON file, which is replaced atomically on save.
+#[derive(Clone, Debug)]
+pub struct FileBatchStore {
+    path: PathBuf,
+}
+
+impl FileBatchStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl BatchStore for FileBatchStore {
+    fn load(&self) -> Result<Vec<TrackedBatch>> {
+        match std::fs::read(&self.path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .with_context(|| format!("failed to parse {:?}", self.path)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(error) => Err(error).with_context(|| format!("failed to read {:?}", self.path)),
+        }
+    }
+
+    fn save(&self, batches: &[TrackedBatch]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp_path = self.path.with_extension("tmp");
+        std::fs::write(&temp_path, serde_json::to_vec_pretty(batches)?)
+            .with_context(|| format!("failed to write {temp_path:?}"))?;
+        std::fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("failed to write {:?}", self.path))
+    }
+}
+
+/// Records submitted batches in a [`BatchStore`], so that long-running bulk
+/// jobs survive restarts and can be polled and collected later.
+pub struct BatchTracker {
+    store: Box<dyn BatchStore>,
+    batches: Vec<TrackedBatch>,
+}
+
+impl BatchTracker {
+    /// Opens a tracker with the batches previously saved in `store`.
+    pub fn open(store: impl BatchStore + 'static) -> Result<Self> {
+        let batches = store.load()?;
+        Ok(Self {
+            store: Box::new(store),
+            batches,
+        })
+    }
+
+    pub fn batches(&self) -> &[TrackedBatch] {
+        &self.batches
+    }
+
+    /// Returns the batches whose results haven't been collected yet.
+    pub fn pending(&self) -> impl Iterator<Item = &TrackedBatch> {
+        self.batches
+            .iter()
+            .filter(|batch| batch.status != TrackedBatchStatus::Collected)
+    }
+
+    pub fn get(&self, batch_id: &str) -> Option<&TrackedBatch> {
+        self.batches.iter().find(|batch| batch.batch_id == batch_id)
+    }
+
+    /// Records a newly submitted batch.
+    pub fn track(
+        &mut self,
+        batch_id: impl Into<String>,
+        custom_ids: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<()> {
+        let batch_id = batch_id.into();
+        self.batches.retain(|batch| batch.batch_id != batch_id);
+        self.batches.push(TrackedBatch {
+            batch_id,
+            custom_ids: custom_ids.into_iter().collect(),
+            submitted_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs()),
+            status: TrackedBatchStatus::Submitted,
+        });
+        self.store.save(&self.batches)
+    }
+
+    pub fn set_status(&mut self, batch_id: &str, status: TrackedBatchStatus) -> Result<()> {
+        let batch = self
+            .batches
+            .iter_mut()
+            .find(|batch| batch.batch_id == batch_id)
+            .ok_or_else(|| anyhow!("batch {batch_id} isn't tracked"))?;
+        batch.status = status;
+        self.store.save(&self.batches)
+    }
+
+    /// Stops tracking a batch, e.g. once its results were collected and
+    /// processed.
+    pub fn forget(&mut self, batch_id: &str) -> Result<Option<TrackedBatch>> {
+        let Some(ix) = self
+            .batches
+            .iter()
+            .position(|batch| batch.batch_id == batch_id)
+        else {
+            return Ok(None);
+        };
+        let batch = self.batches.remove(ix);
+        self.store.save(&self.batches)?;
+        Ok(Some(batch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_tracker() {
+        let store = Arc::new(MemoryBatchStore::default());
+        let mut tracker = BatchTracker::open(store.clone()).unwrap();
+        tracker
+            .track("batch_1", [("req-0".to_string(), "src/a.rs".to_string())])
+            .unwrap();
+        tracker.track("batch_2", []).unwrap();
+        tracker
+            .set_status("batch_1", TrackedBatchStatus::Collected)
+            .unwrap();
+        assert!(tracker
+            .set_status("batch_3", TrackedBatchStatus::Ended)
+            .is_err());
+
+        let tracker = BatchTracker::open(store).unwrap();
+        assert_eq!(tracker.batches().len(), 2);
+        assert_eq!(
+            tracker.get("batch_1").unwrap().custom_ids["req-0"],
+            "src/a.rs"
+        );
+        let pending = tracker
+            .pending()
+            .map(|batch| batch.batch_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(pending, ["batch_2"]);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::{
+    api_error, api_request_builder, body, send_json_request, ApiErrorKind, BatchTracker,
+    ClientOptions, Message, Request, TrackedBatchStatus, ACCEPT_ENCODING,
+};
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Utc};
+use http::{AsyncBody, HttpClient, Method};
+use serde::{Deserialize, Serialize};
+
+/// The beta that enables the message batches endpoints.
+pub const MESSAGE_BATCHES_BETA: &str = "message-batches-2024-09-24";
+
+/// One of the requests of a message batch.
+#[derive(Clone, Debug, Serialize)]
+pub struct BatchRequest {
+    /// Identifies the request's result, which may be returned in any order.
+    pub custom_id: String,
+    pub params: Request,
+}
+
+impl BatchRequest {
+    pub fn new(custom_id: impl Into<String>, mut params: Request) -> Self {
+        params.stream = false;
+        Self {
+            custom_id: custom_id.into(),
+            params,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchProcessingStatus {
+    InProgress,
+    Canceling,
+    Ended,
+}
+
+/// The number of requests of a batch in each state.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct BatchRequestCounts {
+    pub processing: u32,
+    pub succeeded: u32,
+    pub errored: u32,
+    pub canceled: u32,
+    pub expired: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct MessageBatch {
+    pub id: String,
+    pub processing_status: BatchProcessingStatus,
+    pub request_counts: BatchRequestCounts,
+    pub created_at: DateTime<Utc>,
+    /// When the batch will be canceled if it hasn't finished processing.
+    pub expires_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Where the results can be downloaded from, once the batch has ended.
+    pub results_url: Option<String>,
+}
+
+impl MessageBatch {
+    pub fn has_ended(&self) -> bool {
+        self.processing_status == BatchProcessingStatus::Ended
+    }
+}
+
+/// The result of one of the requests of a batch.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BatchResult {
+    pub custom_id: String,
+    pub result: BatchOutcome,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BatchOutcome {
+    Succeeded {
+        message: Message,
+    },
+    Errored {
+        error: BatchErrorResponse,
+    },
+    /// The batch was canceled before the request was processed.
+    Canceled,
+    /// The batch expired before the request was processed.
+    Expired,
+}
+
+/// The error of a request that failed, in the same envelope as the body of
+/// an error response.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BatchErrorResponse {
+    pub error: BatchError,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct BatchError {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub message: String,
+}
+
+impl BatchError {
+    pub fn kind(&self) -> ApiErrorKind {
+        ApiErrorKind::from(self.kind.as_str())
+    }
+}
+
+#[derive(Serialize)]
+struct CreateBatchBody {
+    requests: Vec<BatchRequest>,
+}
+
+/// Submits `requests` to be processed asynchronously, at a lower price than
+/// sending them individually. Poll the batch with [`get_batch`] until it has
+/// ended, then fetch its results with [`batch_results`].
+pub async fn create_batch(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    requests: Vec<BatchRequest>,
+    options: &ClientOptions,
+) -> Result<MessageBatch> {
+    let uri = format!("{api_url}/v1/messages/batches");
+    let mut betas = vec![MESSAGE_BATCHES_BETA.to_string()];
+    for request in &requests {
+        for beta in &request.params.betas {
+            if !betas.contains(beta) {
+                betas.push(beta.clone());
+            }
+        }
+    }
+    let mut request_builder = api_request_builder(Method::POST, &uri, api_key, &betas, options)
+        .header("Accept-Encoding", ACCEPT_ENCODING);
+
+    let size_hint = requests
+        .iter()
+        .map(|request| {
+            request.params.system.encoded_len()
+                + request
+                    .params
+                    .messages
+                    .iter()
+                    .map(|message| message.content.encoded_len())
+                    .sum::<usize>()
+        })
+        .sum();
+    let body = body::encode_body(
+        CreateBatchBody { requests },
+        size_hint,
+        options.request_compression,
+    )?;
+    if let Some(content_encoding) = body.content_encoding {
+        request_builder = request_builder.header("Content-Encoding", content_encoding.as_str());
+    }
+    let request = request_builder.body(body.body)?;
+    send_json_request(client, request).await
+}
+
+/// Like [`create_batch`], but also records the batch in `tracker`, along with
+/// the identifier the caller uses for each request.
+pub async fn submit_tracked_batch(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    requests: impl IntoIterator<Item = (String, Request)>,
+    tracker: &mut BatchTracker,
+    options: &ClientOptions,
+) -> Result<MessageBatch> {
+    let mut custom_ids = Vec::new();
+    let requests = requests
+        .into_iter()
+        .enumerate()
+        .map(|(ix, (id, request))| {
+            let custom_id = format!("request-{ix}");
+            custom_ids.push((custom_id.clone(), id));
+            BatchRequest::new(custom_id, request)
+        })
+        .collect();
+    let batch = create_batch(client, api_url, api_key, requests, options).await?;
+    tracker.track(batch.id.clone(), custom_ids)?;
+    Ok(batch)
+}
+
+/// Returns the current state of a batch.
+pub async fn get_batch(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    batch_id: &str,
+    options: &ClientOptions,
+) -> Result<MessageBatch> {
+    let uri = format!("{api_url}/v1/messages/batches/{batch_id}");
+    let betas = [MESSAGE_BATCHES_BETA.to_string()];
+    let request = api_request_builder(Method::GET, &uri, api_key, &betas, options)
+        .header("Accept-Encoding", ACCEPT_ENCODING)
+        .body(AsyncBody::empty())?;
+    send_json_request(client, request).await
+}
+
+/// Returns the results of a batch that has ended, in no particular order.
+pub async fn batch_results(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    batch_id: &str,
+    options: &ClientOptions,
+) -> Result<Vec<BatchResult>> {
+    let uri = format!("{api_url}/v1/messages/batches/{batch_id}/results");
+    let betas = [MESSAGE_BATCHES_BETA.to_string()];
+    let request = api_request_builder(Method::GET, &uri, api_key, &betas, options)
+        .header("Accept-Encoding", ACCEPT_ENCODING)
+        .body(AsyncBody::empty())?;
+    let mut response = client.send(request).await?;
+    let body = body::read_body(&mut response).await?;
+    let body = String::from_utf8_lossy(&body);
+    if !response.status().is_success() {
+        return Err(api_error(&response, &body).into());
+    }
+    parse_batch_results(&body)
+}
+
+/// Parses results in the JSON Lines format they're downloaded in.
+fn parse_batch_results(jsonl: &str) -> Result<Vec<BatchResult>> {
+    jsonl
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(ix, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("failed to parse line {} of the batch results", ix + 1))
+        })
+        .collect()
+}
+
+impl BatchTracker {
+    /// Records that a tracked batch has ended, once polling reports it.
+    pub fn update(&mut self, batch: &MessageBatch) -> Result<()> {
+        let is_submitted = self.get(&batch.id).map_or(false, |tracked| {
+            tracked.status == TrackedBatchStatus::Submitted
+        });
+        if is_submitted && batch.has_ended() {
+            self.set_status(&batch.id, TrackedBatchStatus::Ended)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_batch_results() {
+        let results = parse_batch_results(concat!(
+            r#"{"custom_id":"request-1","result":{"type":"errored","error":{"type":"error","error":{"type":"invalid_request_error","message":"max_tokens is too large"}}}}"#,
+            "\n",
+            r#"{"custom_id":"request-0","result":{"type":"succeeded","message":{"id":"msg_01","type":"message","role":"assistant","model":"claude-3-haiku-20240307","content":[{"type":"text","text":"Hi"}],"stop_reason":"end_turn","stop_sequence":null,"usage":{"input_tokens":10,"output_tokens":2}}}}"#,
+            "\n",
+            r#"{"custom_id":"request-2","result":{"type":"expired"}}"#,
+            "\n",
+        ))
+        .unwrap();
+        assert_eq!(results.len(), 3);
+        match &results[0].result {
+            BatchOutcome::Errored { error } => {
+                assert_eq!(error.error.kind(), ApiErrorKind::InvalidRequest)
+            }
+            outcome => panic!("unexpected outcome {outcome:?}"),
+        }
+        match &results[1].result {
+            BatchOutcome::Succeeded { message } => assert_eq!(message.text(), "Hi"),
+            outcome => panic!("unexpected outcome {outcome:?}"),
+        }
+        assert!(matches!(results[2].result, BatchOutcome::Expired));
+
+        assert!(parse_batch_results("{}\n").is_err());
+    }
+}
@@ -0,0 +1,558 @@
+use crate::{
+    body, send_json_request, ApiError, ClientOptions, Message, Model, Request, ResponseEvent,
+};
+use anyhow::{anyhow, bail, Context as _, Result};
+use chrono::Utc;
+use futures::{
+    stream::{self, BoxStream},
+    AsyncReadExt, StreamExt,
+};
+use http::{AsyncBody, HttpClient, Method, Request as HttpRequest};
+use isahc::config::Configurable;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// The API version sent in the body of requests to Bedrock, which doesn't
+/// accept the `Anthropic-Version` header.
+pub const BEDROCK_ANTHROPIC_VERSION: &str = "bedrock-2023-05-31";
+
+const SERVICE: &str = "bedrock";
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+/// The length of the total length, headers length and prelude checksum that
+/// start every event stream message.
+const PRELUDE_LEN: usize = 12;
+const MESSAGE_CRC_LEN: usize = 4;
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Set when using temporary credentials, e.g. from an assumed role.
+    pub session_token: Option<String>,
+}
+
+impl std::fmt::Debug for AwsCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Where and as whom requests are sent when using Claude through AWS Bedrock
+/// rather than the Anthropic API.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BedrockConfig {
+    /// The region the model is enabled in, e.g. `us-east-1`.
+    pub region: String,
+    pub credentials: AwsCredentials,
+}
+
+impl BedrockConfig {
+    fn host(&self) -> String {
+        format!("bedrock-runtime.{}.amazonaws.com", self.region)
+    }
+}
+
+/// Returns the id of `model` on Bedrock. Custom model names, which may also
+/// be inference profile ids or ARNs, are passed through.
+pub fn bedrock_model_id(model: &Model) -> &str {
+    match model {
+        Model::Claude3_5Sonnet => "anthropic.claude-3-5-sonnet-20240620-v1:0",
+        Model::Claude3Opus => "anthropic.claude-3-opus-20240229-v1:0",
+        Model::Claude3Sonnet => "anthropic.claude-3-sonnet-20240229-v1:0",
+        Model::Claude3Haiku => "anthropic.claude-3-haiku-20240307-v1:0",
+        Model::Claude3_5Haiku => "anthropic.claude-3-5-haiku-20241022-v1:0",
+        Model::Claude3_7Sonnet => "anthropic.claude-3-7-sonnet-20250219-v1:0",
+        Model::ClaudeSonnet4 => "anthropic.claude-sonnet-4-20250514-v1:0",
+        Model::ClaudeOpus4 => "anthropic.claude-opus-4-20250514-v1:0",
+        Model::ClaudeOpus4_1 => "anthropic.claude-opus-4-1-20250805-v1:0",
+        Model::Custom { name, .. } => name,
+    }
+}
+
+/// Encodes `request` for Bedrock, which takes the model from the URL, and
+/// the API version and betas from the body.
+fn bedrock_body(request: &Request) -> Result<Vec<u8>> {
+    let serde_json::Value::Object(mut body) = serde_json::to_value(request)? else {
+        bail!("request didn't serialize to an object");
+    };
+    body.remove("model");
+    body.remove("stream");
+    body.insert("anthropic_version".into(), BEDROCK_ANTHROPIC_VERSION.into());
+    if !request.betas.is_empty() {
+        body.insert("anthropic_beta".into(), request.betas.clone().into());
+    }
+    Ok(serde_json::to_vec(&body)?)
+}
+
+/// Builds a request to `action` of the request's model, signed with
+/// Signature Version 4.
+fn signed_request(
+    config: &BedrockConfig,
+    request: &Request,
+    action: &str,
+    accept: &str,
+    options: &ClientOptions,
+) -> Result<HttpRequest<AsyncBody>> {
+    let host = config.host();
+    let model_id = uri_encode(bedrock_model_id(&request.model));
+    let path = format!("/model/{model_id}/{action}");
+    // Except for S3, the path is encoded once more for signing.
+    let canonical_uri = format!("/model/{}/{action}", uri_encode(&model_id));
+    let body = bedrock_body(request)?;
+
+    let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut headers = BTreeMap::new();
+    headers.insert("accept", accept.to_string());
+    headers.insert("content-type", "application/json".to_string());
+    headers.insert("host", host.clone());
+    headers.insert("x-amz-date", amz_date.clone());
+    if let Some(session_token) = &config.credentials.session_token {
+        headers.insert("x-amz-security-token", session_token.clone());
+    }
+    let authorization = authorization(
+        &config.credentials,
+        &config.region,
+        &canonical_uri,
+        &headers,
+        &body,
+        &amz_date,
+    );
+
+    let mut request_builder = HttpRequest::builder()
+        .method(Method::POST)
+        .uri(format!("https://{host}{path}"))
+        .header("Authorization", authorization);
+    for (name, value) in headers {
+        if name != "host" {
+            request_builder = request_builder.header(name, value);
+        }
+    }
+    if let Some(low_speed_timeout) = options.low_speed_timeout {
+        request_builder = request_builder.low_speed_timeout(100, low_speed_timeout);
+    }
+    Ok(request_builder.body(AsyncBody::from(body))?)
+}
+
+/// Returns the `Authorization` header of a Signature Version 4 signed
+/// `POST` request. `headers` are the signed headers, with lowercase names.
+fn authorization(
+    credentials: &AwsCredentials,
+    region: &str,
+    canonical_uri: &str,
+    headers: &BTreeMap<&str, String>,
+    body: &[u8],
+    amz_date: &str,
+) -> String {
+    let date = &amz_date[..8];
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+        .collect();
+    let signed_headers = headers.keys().copied().collect::<Vec<_>>().join(";");
+    let canonical_request = format!(
+        "POST\n{canonical_uri}\n\n{canonical_headers}\n{signed_headers}\n{}",
+        hex::encode(Sha256::digest(body))
+    );
+    let scope = format!("{date}/{region}/{SERVICE}/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = hmac_sha256(
+        format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+        date.as_bytes(),
+    );
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, SERVICE.as_bytes());
+    let key = hmac_sha256(&key, b"aws4_request");
+    let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, \
+        Signature={signature}",
+        credentials.access_key_id
+    )
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_LEN: usize = 64;
+    let mut block = [0; BLOCK_LEN];
+    if key.len() > BLOCK_LEN {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(block.map(|byte| byte ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|byte| byte ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+/// Percent-encodes everything but unreserved characters, as required for
+/// Signature Version 4.
+fn uri_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Like [`crate::stream_completion`], but sends the request to Bedrock.
+pub async fn stream_completion_bedrock(
+    client: &dyn HttpClient,
+    config: &BedrockConfig,
+    request: Request,
+    options: &ClientOptions,
+) -> Result<BoxStream<'static, Result<ResponseEvent>>> {
+    let http_request = signed_request(
+        config,
+        &request,
+        "invoke-with-response-stream",
+        "application/vnd.amazon.eventstream",
+        options,
+    )?;
+    let mut response = client.send(http_request).await?;
+    if response.status().is_success() {
+        Ok(event_stream(response.into_body()))
+    } else {
+        let body = body::read_body(&mut response).await?;
+        let body = String::from_utf8_lossy(&body);
+        Err(ApiError::from_response(response.status().as_u16(), &body).into())
+    }
+}
+
+/// Like [`crate::complete`], but sends the request to Bedrock.
+pub async fn complete_bedrock(
+    client: &dyn HttpClient,
+    config: &BedrockConfig,
+    request: Request,
+    options: &ClientOptions,
+) -> Result<Message> {
+    let http_request = signed_request(config, &request, "invoke", "application/json", options)?;
+    send_json_request(client, http_request).await
+}
+
+/// A message of the `application/vnd.amazon.eventstream` encoding. Only the
+/// headers with string values are kept.
+#[derive(Debug, PartialEq)]
+struct EventStreamMessage {
+    headers: BTreeMap<String, String>,
+    payload: Vec<u8>,
+}
+
+impl EventStreamMessage {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+#[derive(Default)]
+struct EventStreamDecoder {
+    buffer: Vec<u8>,
+}
+
+impl EventStreamDecoder {
+    fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Returns the next complete message, if one has been received.
+    fn next_message(&mut self) -> Result<Option<EventStreamMessage>> {
+        if self.buffer.len() < PRELUDE_LEN {
+            return Ok(None);
+        }
+        let total_len = read_u32(&self.buffer[0..4]) as usize;
+        let headers_len = read_u32(&self.buffer[4..8]) as usize;
+        if crc32(&self.buffer[..8]) != read_u32(&self.buffer[8..12]) {
+            bail!("event stream message has an invalid prelude checksum");
+        }
+        if total_len < PRELUDE_LEN + headers_len + MESSAGE_CRC_LEN {
+            bail!("event stream message is too short");
+        }
+        if self.buffer.len() < total_len {
+            return Ok(None);
+        }
+
+        let message: Vec<u8> = self.buffer.drain(..total_len).collect();
+        let crc_start = total_len - MESSAGE_CRC_LEN;
+        if crc32(&message[..crc_start]) != read_u32(&message[crc_start..]) {
+            bail!("event stream message has an invalid checksum");
+        }
+        let headers = parse_headers(&message[PRELUDE_LEN..PRELUDE_LEN + headers_len])?;
+        Ok(Some(EventStreamMessage {
+            headers,
+            payload: message[PRELUDE_LEN + headers_len..crc_start].to_vec(),
+        }))
+    }
+}
+
+fn parse_headers(mut bytes: &[u8]) -> Result<BTreeMap<String, String>> {
+    fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+        if bytes.len() < len {
+            bail!("event stream message has truncated headers");
+        }
+        let (taken, rest) = bytes.split_at(len);
+        *bytes = rest;
+        Ok(taken)
+    }
+
+    let mut headers = BTreeMap::new();
+    while !bytes.is_empty() {
+        let name_len = take(&mut bytes, 1)?[0] as usize;
+        let name = String::from_utf8_lossy(take(&mut bytes, name_len)?).into_owned();
+        let value_type = take(&mut bytes, 1)?[0];
+        match value_type {
+            // true and false
+            0 | 1 => {}
+            2 => drop(take(&mut bytes, 1)?),
+            3 => drop(take(&mut bytes, 2)?),
+            4 => drop(take(&mut bytes, 4)?),
+            // long and timestamp
+            5 | 8 => drop(take(&mut bytes, 8)?),
+            // byte array and string
+            6 | 7 => {
+                let len = take(&mut bytes, 2)?;
+                let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+                let value = take(&mut bytes, len)?;
+                if value_type == 7 {
+                    headers.insert(name, String::from_utf8_lossy(value).into_owned());
+                }
+            }
+            9 => drop(take(&mut bytes, 16)?),
+            _ => bail!("event stream header '{name}' has unknown type {value_type}"),
+        }
+    }
+    Ok(headers)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// The CRC-32 (IEEE) checksum used by the event stream encoding.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[derive(Deserialize)]
+struct Chunk {
+    /// A base64 encoded event of the Messages API.
+    bytes: String,
+}
+
+#[derive(Deserialize)]
+struct Exception {
+    message: String,
+}
+
+/// Converts an event stream message to the [`ResponseEvent`] it carries, if
+/// any.
+fn to_response_event(message: &EventStreamMessage) -> Result<Option<ResponseEvent>> {
+    match message.header(":message-type") {
+        Some("event") if message.header(":event-type") == Some("chunk") => {
+            let chunk: Chunk = serde_json::from_slice(&message.payload)?;
+            let event = base64::decode(&chunk.bytes).context("invalid chunk from Bedrock")?;
+            Ok(Some(serde_json::from_slice(&event)?))
+        }
+        Some("event") => Ok(None),
+        Some("exception") => {
+            let exception_type = message.header(":exception-type").unwrap_or_default();
+            let text = match serde_json::from_slice::<Exception>(&message.payload) {
+                Ok(exception) => exception.message,
+                Err(_) => String::from_utf8_lossy(&message.payload).into_owned(),
+            };
+            let status = match exception_type {
+                "throttlingException" => 429,
+                "validationException" => 400,
+                "serviceUnavailableException" => 503,
+                _ => 500,
+            };
+            Err(ApiError::from_response(status, &format!("{exception_type}: {text}")).into())
+        }
+        message_type => Err(anyhow!(
+            "unexpected event stream message type {message_type:?}"
+        )),
+    }
+}
+
+struct EventStreamState {
+    body: AsyncBody,
+    decoder: EventStreamDecoder,
+    read_buffer: Vec<u8>,
+    done: bool,
+}
+
+impl EventStreamState {
+    async fn next_event(&mut self) -> Option<Result<ResponseEvent>> {
+        while !self.done {
+            match self.decoder.next_message() {
+                Ok(Some(message)) => match to_response_event(&message) {
+                    Ok(Some(event)) => return Some(Ok(event)),
+                    Ok(None) => continue,
+                    Err(error) => return Some(Err(self.fail(error))),
+                },
+                Ok(None) => {}
+                Err(error) => return Some(Err(self.fail(error))),
+            }
+            match self.body.read(&mut self.read_buffer).await {
+                Ok(0) if self.decoder.buffer.is_empty() => self.done = true,
+                Ok(0) => {
+                    let error = anyhow!("Bedrock response ended in the middle of a message");
+                    return Some(Err(self.fail(error)));
+                }
+                Ok(len) => self.decoder.push(&self.read_buffer[..len]),
+                Err(error) => return Some(Err(self.fail(error.into()))),
+            }
+        }
+        None
+    }
+
+    fn fail(&mut self, error: anyhow::Error) -> anyhow::Error {
+        self.done = true;
+        error
+    }
+}
+
+fn event_stream(body: AsyncBody) -> BoxStream<'static, Result<ResponseEvent>> {
+    let state = EventStreamState {
+        body,
+        decoder: EventStreamDecoder::default(),
+        read_buffer: vec![0; READ_CHUNK_SIZE],
+        done: false,
+    };
+    stream::unfold(state, |mut state| async move {
+        let event = state.next_event().await?;
+        Some((event, state))
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_message(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+        let mut encoded_headers = Vec::new();
+        for (name, value) in headers {
+            encoded_headers.push(name.len() as u8);
+            encoded_headers.extend_from_slice(name.as_bytes());
+            encoded_headers.push(7);
+            encoded_headers.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            encoded_headers.extend_from_slice(value.as_bytes());
+        }
+        let total_len = PRELUDE_LEN + encoded_headers.len() + payload.len() + MESSAGE_CRC_LEN;
+        let mut message = Vec::new();
+        message.extend_from_slice(&(total_len as u32).to_be_bytes());
+        message.extend_from_slice(&(encoded_headers.len() as u32).to_be_bytes());
+        message.extend_from_slice(&crc32(&message).to_be_bytes());
+        message.extend_from_slice(&encoded_headers);
+        message.extend_from_slice(payload);
+        message.extend_from_slice(&crc32(&message).to_be_bytes());
+        message
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_decode_chunks() {
+        let event = br#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#;
+        let payload = format!(r#"{{"bytes":"{}"}}"#, base64::encode(event));
+        let message = encode_message(
+            &[
+                (":event-type", "chunk"),
+                (":content-type", "application/json"),
+                (":message-type", "event"),
+            ],
+            payload.as_bytes(),
+        );
+
+        let mut decoder = EventStreamDecoder::default();
+        let (first, second) = message.split_at(20);
+        decoder.push(first);
+        assert_eq!(decoder.next_message().unwrap(), None);
+        decoder.push(second);
+        let message = decoder.next_message().unwrap().unwrap();
+        let event = to_response_event(&message).unwrap().unwrap();
+        assert_eq!(event.text(), Some("Hi"));
+        assert!(decoder.buffer.is_empty());
+    }
+
+    #[test]
+    fn test_decode_exception() {
+        let message = encode_message(
+            &[
+                (":exception-type", "throttlingException"),
+                (":message-type", "exception"),
+            ],
+            br#"{"message":"Too many requests"}"#,
+        );
+        let mut decoder = EventStreamDecoder::default();
+        decoder.push(&message);
+        let message = decoder.next_message().unwrap().unwrap();
+        let error = to_response_event(&message).unwrap_err();
+        assert_eq!(error.downcast_ref::<ApiError>().unwrap().status, 429);
+    }
+
+    #[test]
+    fn test_authorization() {
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".into(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+            session_token: None,
+        };
+        let mut headers = BTreeMap::new();
+        headers.insert("content-type", "application/json".to_string());
+        headers.insert(
+            "host",
+            "bedrock-runtime.us-east-1.amazonaws.com".to_string(),
+        );
+        headers.insert("x-amz-date", "20240101T000000Z".to_string());
+        let canonical_uri = format!(
+            "/model/{}/invoke",
+            uri_encode(&uri_encode("anthropic.claude-3-haiku-20240307-v1:0"))
+        );
+        assert_eq!(
+            canonical_uri,
+            "/model/anthropic.claude-3-haiku-20240307-v1%253A0/invoke"
+        );
+        assert_eq!(
+            authorization(
+                &credentials,
+                "us-east-1",
+                &canonical_uri,
+                &headers,
+                b"{}",
+                "20240101T000000Z",
+            ),
+            "AWS4-HMAC-SHA256 \
+            Credential=AKIDEXAMPLE/20240101/us-east-1/bedrock/aws4_request, \
+            SignedHeaders=content-type;host;x-amz-date, \
+            Signature=f359389ac8fa831b6492ea8904d66d614ab3c8e7ef8e4985cb5fd6f5375e6fcd"
+        );
+    }
+}
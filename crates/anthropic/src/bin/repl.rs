@@ -0,0 +1,162 @@
+//! An interactive chat with the Anthropic API, for trying out the crate
+//! without running Zed.
+//!
+//! Usage: ANTHROPIC_API_KEY=... cargo run -p anthropic --features cli
+
+use anthropic::{
+    stream_completion_reader, ClientOptions, Model, Request, RequestMessage, ResponseEvent, Usage,
+    UsageBreakdown, ANTHROPIC_API_URL,
+};
+use anyhow::{Context as _, Result};
+use futures::StreamExt;
+use http::HttpClient;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+Commands:
+  /model [id]     show or switch the model
+  /system [text]  show or set the system prompt
+  /usage          show the tokens used so far
+  /reset          start a new conversation
+  /quit           exit";
+
+fn main() -> Result<()> {
+    let api_key = std::env::var("ANTHROPIC_API_KEY").context("ANTHROPIC_API_KEY must be set")?;
+    let api_url = std::env::var("ANTHROPIC_API_URL").unwrap_or(ANTHROPIC_API_URL.to_string());
+    let client = http::client(None);
+
+    let mut repl = Repl {
+        model: Model::default(),
+        system: String::new(),
+        messages: Vec::new(),
+        usage: UsageBreakdown::new(),
+    };
+    println!(
+        "Chatting with {}. Type /help for commands.",
+        repl.model.display_name()
+    );
+
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(command) = line.strip_prefix('/') {
+            if !repl.run_command(command)? {
+                break;
+            }
+            continue;
+        }
+
+        repl.messages.push(RequestMessage::user(line));
+        let result = smol::block_on(repl.send(client.as_ref(), &api_url, &api_key));
+        match result {
+            Ok((text, usage)) => {
+                println!(
+                    "\n[{} input, {} output tokens]",
+                    usage.total_input_tokens(),
+                    usage.output_tokens.unwrap_or(0)
+                );
+                repl.messages.push(RequestMessage::assistant(text));
+                repl.usage.record(&repl.model, &usage);
+            }
+            Err(error) => {
+                eprintln!("\nerror: {error:#}");
+                repl.messages.pop();
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Repl {
+    model: Model,
+    system: String,
+    messages: Vec<RequestMessage>,
+    usage: UsageBreakdown,
+}
+
+impl Repl {
+    /// Returns `false` if the REPL should exit.
+    fn run_command(&mut self, command: &str) -> Result<bool> {
+        let (name, argument) = command
+            .split_once(' ')
+            .map_or((command, ""), |(name, argument)| (name, argument.trim()));
+        match name {
+            "model" if argument.is_empty() => println!("{}", self.model.id()),
+            "model" => {
+                self.model = Model::from_id(argument)?;
+                println!("Switched to {}", self.model.display_name());
+            }
+            "system" if argument.is_empty() => println!("{}", self.system),
+            "system" => self.system = argument.to_string(),
+            "usage" => {
+                for (model_id, usage) in self.usage.iter() {
+                    print_usage(model_id, usage);
+                }
+                print_usage("total", &self.usage.total());
+            }
+            "reset" => self.messages.clear(),
+            "quit" | "exit" => return Ok(false),
+            "help" => println!("{HELP}"),
+            _ => eprintln!("unknown command /{name}\n{HELP}"),
+        }
+        Ok(true)
+    }
+
+    async fn send(
+        &self,
+        client: &dyn HttpClient,
+        api_url: &str,
+        api_key: &str,
+    ) -> Result<(String, Usage)> {
+        let request = Request::builder(self.model.clone())
+            .system(self.system.clone())
+            .messages(self.messages.iter().cloned())
+            .build()?;
+        let reader =
+            stream_completion_reader(client, api_url, api_key, request, &ClientOptions::default())
+                .await?;
+        let mut events = reader.into_stream();
+
+        let mut text = String::new();
+        let mut usage = Usage::default();
+        while let Some(event) = events.next().await {
+            let event = event?;
+            if let Some(delta) = event.text() {
+                print!("{delta}");
+                io::stdout().flush()?;
+                text.push_str(delta);
+            }
+            match event {
+                ResponseEvent::MessageStart { message } => {
+                    usage = message.usage.unwrap_or_default();
+                }
+                ResponseEvent::MessageDelta {
+                    usage: delta_usage, ..
+                } => {
+                    usage.output_tokens = delta_usage.output_tokens.or(usage.output_tokens);
+                }
+                ResponseEvent::Error { error } => return Err(error.into()),
+                _ => {}
+            }
+        }
+        Ok((text, usage))
+    }
+}
+
+fn print_usage(label: &str, usage: &Usage) {
+    println!(
+        "{label}: {} input ({} cached), {} output",
+        usage.total_input_tokens(),
+        usage.cache_read_input_tokens.unwrap_or(0),
+        usage.output_tokens.unwrap_or(0)
+    );
+}
@@ -0,0 +1,308 @@
+use crate::Request;
+use anyhow::{anyhow, Result};
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::GzEncoder,
+};
+use futures::{channel::mpsc, executor::block_on, io::Cursor, AsyncReadExt, SinkExt, TryStreamExt};
+use http::{AsyncBody, Response};
+use serde::Serialize;
+use std::{
+    io::{self, Read, Write},
+    mem,
+    sync::Arc,
+};
+
+/// The `Accept-Encoding` sent to endpoints whose responses are read in full
+/// rather than streamed. Such responses are decompressed transparently.
+pub const ACCEPT_ENCODING: &str = "gzip, deflate, zstd";
+
+/// Requests carrying more than this many bytes of content are serialized
+/// directly into the request body, rather than first being rendered into one
+/// large in-memory string.
+const STREAMING_BODY_THRESHOLD: usize = 1024 * 1024;
+const CHUNK_SIZE: usize = 64 * 1024;
+const CHUNK_CHANNEL_CAPACITY: usize = 4;
+
+/// A `Content-Encoding` supported for request bodies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Gzip,
+    Zstd,
+}
+
+impl ContentEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Self::Zstd => zstd::encode_all(data, 0),
+        }
+    }
+}
+
+/// Compresses request bodies of at least `min_size` bytes.
+///
+/// Long prompts compress well, so this can significantly speed up uploads on
+/// slow connections, at the cost of some CPU time per request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RequestCompression {
+    pub encoding: ContentEncoding,
+    pub min_size: usize,
+}
+
+impl RequestCompression {
+    const DEFAULT_MIN_SIZE: usize = 32 * 1024;
+
+    pub fn gzip() -> Self {
+        Self {
+            encoding: ContentEncoding::Gzip,
+            min_size: Self::DEFAULT_MIN_SIZE,
+        }
+    }
+
+    pub fn zstd() -> Self {
+        Self {
+            encoding: ContentEncoding::Zstd,
+            min_size: Self::DEFAULT_MIN_SIZE,
+        }
+    }
+}
+
+/// A request body ready to be sent once.
+pub(crate) struct RequestBody {
+    pub body: AsyncBody,
+    pub content_encoding: Option<ContentEncoding>,
+}
+
+/// A request body encoded up front, which can be sent any number of times,
+/// e.g. when a request is retried, without serializing it again.
+#[derive(Clone)]
+pub(crate) struct EncodedBody {
+    bytes: Arc<[u8]>,
+    content_encoding: Option<ContentEncoding>,
+}
+
+impl EncodedBody {
+    /// Encodes `value` as JSON, compressing it if requested and the body is
+    /// large enough.
+    pub fn new<T: Serialize + ?Sized>(
+        value: &T,
+        compression: Option<RequestCompression>,
+    ) -> Result<Self> {
+        let json = serde_json::to_vec(value)?;
+        match compression.filter(|compression| json.len() >= compression.min_size) {
+            Some(compression) => Ok(Self {
+                bytes: compression.encoding.compress(&json)?.into(),
+                content_encoding: Some(compression.encoding),
+            }),
+            None => Ok(Self {
+                bytes: json.into(),
+                content_encoding: None,
+            }),
+        }
+    }
+
+    pub fn to_request_body(&self) -> RequestBody {
+        RequestBody {
+            body: AsyncBody::from_reader_sized(
+                Cursor::new(self.bytes.clone()),
+                self.bytes.len() as u64,
+            ),
+            content_encoding: self.content_encoding,
+        }
+    }
+}
+
+pub(crate) fn encode_request_body(
+    request: Arc<Request>,
+    compression: Option<RequestCompression>,
+) -> Result<RequestBody> {
+    let content_len = request.system.encoded_len()
+        + request
+            .messages
+            .iter()
+            .map(|message| message.content.encoded_len())
+            .sum::<usize>();
+    encode_shared_body(request, content_len, compression)
+}
+
+/// Encodes `value` as a JSON request body, compressing it if requested and
+/// the body is large enough.
+///
+/// Small values are serialized up front. Large ones are serialized on smol's
+/// blocking thread pool in fixed-size chunks as the HTTP client consumes the
+/// body, so at most a few chunks of the encoded JSON are held in memory
+/// alongside the value itself. Use an [`EncodedBody`] instead for bodies that
+/// are sent more than once.
+pub(crate) fn encode_body<T>(
+    value: T,
+    size_hint: usize,
+    compression: Option<RequestCompression>,
+) -> Result<RequestBody>
+where
+    T: Serialize + Send + Sync + 'static,
+{
+    encode_shared_body(Arc::new(value), size_hint, compression)
+}
+
+fn encode_shared_body<T>(
+    value: Arc<T>,
+    size_hint: usize,
+    compression: Option<RequestCompression>,
+) -> Result<RequestBody>
+where
+    T: Serialize + Send + Sync + 'static,
+{
+    if size_hint < STREAMING_BODY_THRESHOLD {
+        return Ok(EncodedBody::new(value.as_ref(), compression)?.to_request_body());
+    }
+
+    let content_encoding = compression
+        .filter(|compression| size_hint >= compression.min_size)
+        .map(|compression| compression.encoding);
+    Ok(RequestBody {
+        body: stream_body(value, content_encoding),
+        content_encoding,
+    })
+}
+
+fn stream_body<T>(value: Arc<T>, content_encoding: Option<ContentEncoding>) -> AsyncBody
+where
+    T: Serialize + Send + Sync + 'static,
+{
+    let (tx, rx) = mpsc::channel(CHUNK_CHANNEL_CAPACITY);
+    // Writing blocks while the channel is full, so the encoder runs on the
+    // pool meant for blocking work, whose threads are reused across requests.
+    smol::unblock(move || {
+        let mut error_tx = tx.clone();
+        let writer = ChunkWriter {
+            chunk: Vec::with_capacity(CHUNK_SIZE),
+            tx,
+        };
+        if let Err(error) = write_json(value.as_ref(), writer, content_encoding) {
+            block_on(error_tx.send(Err(error))).ok();
+        }
+    })
+    .detach();
+    AsyncBody::from_reader(rx.into_async_read())
+}
+
+fn write_json<T: Serialize>(
+    value: &T,
+    mut writer: ChunkWriter,
+    encoding: Option<ContentEncoding>,
+) -> io::Result<()> {
+    match encoding {
+        None => {
+            serde_json::to_writer(&mut writer, value)?;
+            writer.flush()
+        }
+        Some(ContentEncoding::Gzip) => {
+            let mut encoder = GzEncoder::new(writer, flate2::Compression::default());
+            serde_json::to_writer(&mut encoder, value)?;
+            encoder.finish()?.flush()
+        }
+        Some(ContentEncoding::Zstd) => {
+            let mut encoder = zstd::stream::write::Encoder::new(writer, 0)?;
+            serde_json::to_writer(&mut encoder, value)?;
+            encoder.finish()?.flush()
+        }
+    }
+}
+
+/// Reads a complete response body, transparently decompressing it according
+/// to its `Content-Encoding`.
+pub(crate) async fn read_body(response: &mut Response<AsyncBody>) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    response.body_mut().read_to_end(&mut body).await?;
+
+    let Some(content_encoding) = response.headers().get("Content-Encoding") else {
+        return Ok(body);
+    };
+    let mut decoded = Vec::new();
+    match content_encoding.to_str()?.trim() {
+        "" | "identity" => return Ok(body),
+        "gzip" | "x-gzip" => {
+            GzDecoder::new(body.as_slice()).read_to_end(&mut decoded)?;
+        }
+        "deflate" => {
+            ZlibDecoder::new(body.as_slice()).read_to_end(&mut decoded)?;
+        }
+        "zstd" => decoded = zstd::decode_all(body.as_slice())?,
+        other => return Err(anyhow!("unsupported response content encoding '{other}'")),
+    }
+    Ok(decoded)
+}
+
+struct ChunkWriter {
+    chunk: Vec<u8>,
+    tx: mpsc::Sender<io::Result<Vec<u8>>>,
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.chunk.extend_from_slice(buf);
+        if self.chunk.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+
+        let chunk = mem::replace(&mut self.chunk, Vec::with_capacity(CHUNK_SIZE));
+        block_on(self.tx.send(Ok(chunk)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "request body was dropped"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+
+    struct Counted<'a>(&'a AtomicUsize);
+
+    impl Serialize for Counted<'_> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.fetch_add(1, SeqCst);
+            serializer.serialize_str("hello")
+        }
+    }
+
+    #[test]
+    fn test_encoded_body() {
+        let serializations = AtomicUsize::new(0);
+        let compression = RequestCompression {
+            encoding: ContentEncoding::Gzip,
+            min_size: 1,
+        };
+        let body = EncodedBody::new(&Counted(&serializations), Some(compression)).unwrap();
+        for _ in 0..3 {
+            let mut request_body = body.to_request_body();
+            assert_eq!(request_body.content_encoding, Some(ContentEncoding::Gzip));
+            let mut compressed = Vec::new();
+            block_on(request_body.body.read_to_end(&mut compressed)).unwrap();
+            let mut json = String::new();
+            GzDecoder::new(compressed.as_slice())
+                .read_to_string(&mut json)
+                .unwrap();
+            assert_eq!(json, "\"hello\"");
+        }
+        assert_eq!(serializations.load(SeqCst), 1);
+    }
+}
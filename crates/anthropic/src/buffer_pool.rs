@@ -0,0 +1,79 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/// How many idle buffers a pool keeps around.
+const MAX_POOLED_BUFFERS: usize = 32;
+
+/// Buffers that grew beyond this size (e.g. while reading a very large tool
+/// input) are freed instead of being returned to the pool.
+const MAX_POOLED_CAPACITY: usize = 1024 * 1024;
+
+/// A pool of byte buffers shared by concurrently running streams, so that
+/// running many completions at once (e.g. inline suggestions in several
+/// panes) doesn't repeatedly allocate and free read buffers.
+#[derive(Clone, Default)]
+pub struct BufferPool {
+    buffers: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl BufferPool {
+    /// Returns the pool used by default for all streams.
+    pub fn global() -> &'static Self {
+        static GLOBAL: OnceLock<BufferPool> = OnceLock::new();
+        GLOBAL.get_or_init(Self::default)
+    }
+
+    /// Takes an empty buffer from the pool, allocating one if none is idle.
+    pub fn take(&self) -> PooledBuffer {
+        let buffer = self
+            .buffers
+            .lock()
+            .ok()
+            .and_then(|mut buffers| buffers.pop())
+            .unwrap_or_default();
+        PooledBuffer {
+            buffer,
+            pool: self.clone(),
+        }
+    }
+
+    fn put(&self, mut buffer: Vec<u8>) {
+        if buffer.capacity() == 0 || buffer.capacity() > MAX_POOLED_CAPACITY {
+            return;
+        }
+        buffer.clear();
+        if let Ok(mut buffers) = self.buffers.lock() {
+            if buffers.len() < MAX_POOLED_BUFFERS {
+                buffers.push(buffer);
+            }
+        }
+    }
+}
+
+/// A buffer borrowed from a [`BufferPool`], returned to it when dropped.
+pub struct PooledBuffer {
+    buffer: Vec<u8>,
+    pool: BufferPool,
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        self.pool.put(std::mem::take(&mut self.buffer));
+    }
+}
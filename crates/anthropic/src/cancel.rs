@@ -0,0 +1,175 @@
+use crate::{connect_stream, ClientOptions, Request, ResponseEvent};
+use anyhow::Result;
+use futures::{
+    stream::{BoxStream, Stream, StreamExt},
+    task::AtomicWaker,
+};
+use http::HttpClient;
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    task::{Context, Poll},
+};
+
+/// A stream that can be cancelled from another task with its
+/// [`CancelHandle`].
+///
+/// Cancelling drops the wrapped stream right away, rather than the next time
+/// it's polled, which for a completion drops the response body and makes the
+/// HTTP client abort the transfer, so the model stops generating (and
+/// billing for) tokens. Dropping the stream has the same effect, even if
+/// handles to it are still around.
+pub struct CancellableStream<S> {
+    shared: Arc<Shared<S>>,
+}
+
+struct Shared<S> {
+    stream: Mutex<Option<S>>,
+    cancelled: AtomicBool,
+    waker: AtomicWaker,
+}
+
+trait Cancel: Send + Sync {
+    fn cancel(&self);
+    fn is_cancelled(&self) -> bool;
+}
+
+impl<S: Send> Cancel for Shared<S> {
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        // The stream is dropped outside of the lock, in case dropping it
+        // takes a while.
+        let stream = self
+            .stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        drop(stream);
+        self.waker.wake();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Cancels a [`CancellableStream`]. Cloned handles cancel the same stream.
+#[derive(Clone)]
+pub struct CancelHandle {
+    target: Arc<dyn Cancel>,
+}
+
+impl CancelHandle {
+    /// Cancels the stream, which ends after yielding any item it's currently
+    /// producing.
+    pub fn cancel(&self) {
+        self.target.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.target.is_cancelled()
+    }
+}
+
+impl std::fmt::Debug for CancelHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancelHandle")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl<S: Stream + Unpin + Send + 'static> CancellableStream<S> {
+    pub fn new(stream: S) -> (Self, CancelHandle) {
+        let shared = Arc::new(Shared {
+            stream: Mutex::new(Some(stream)),
+            cancelled: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        });
+        let handle = CancelHandle {
+            target: shared.clone(),
+        };
+        (Self { shared }, handle)
+    }
+}
+
+impl<S: Stream + Unpin> Stream for CancellableStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        self.shared.waker.register(cx.waker());
+        let mut stream = self
+            .shared
+            .stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(inner) = stream.as_mut() else {
+            return Poll::Ready(None);
+        };
+        let poll = inner.poll_next_unpin(cx);
+        if let Poll::Ready(None) = poll {
+            *stream = None;
+        }
+        poll
+    }
+}
+
+impl<S> Drop for CancellableStream<S> {
+    fn drop(&mut self) {
+        self.shared.cancelled.store(true, Ordering::SeqCst);
+        let stream = self
+            .shared
+            .stream
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        drop(stream);
+    }
+}
+
+/// Like [`crate::stream_completion`], but also returns a handle that aborts
+/// the request when cancelled.
+pub async fn stream_completion_cancellable(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: Request,
+    options: &ClientOptions,
+) -> Result<(BoxStream<'static, Result<ResponseEvent>>, CancelHandle)> {
+    let (reader, _, span) = connect_stream(client, api_url, api_key, request, options).await?;
+    let (stream, handle) = CancellableStream::new(span.stream(reader.into_stream()));
+    Ok((stream.boxed(), handle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, stream};
+
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_cancel_drops_stream() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let inner = stream::unfold(
+            DropFlag(dropped.clone()),
+            |flag| async move { Some((0, flag)) },
+        );
+        let (mut stream, handle) = CancellableStream::new(inner.boxed());
+        assert_eq!(block_on(stream.next()), Some(0));
+
+        handle.cancel();
+        assert!(dropped.load(Ordering::SeqCst));
+        assert!(handle.is_cancelled());
+        assert_eq!(block_on(stream.next()), None);
+    }
+}
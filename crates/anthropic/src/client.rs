@@ -0,0 +1,313 @@
+use crate::{
+    api_error, body, complete, complete_vertex, connect_stream, count_tokens,
+    middleware::MiddlewareHttpClient, raw::raw_request, stream_completion_vertex, with_retry,
+    ApiError, AuthScheme, ClientOptions, Message, Middleware, RawResponse, Request, ResponseEvent,
+    RetryPolicy, VertexConfig, ANTHROPIC_API_URL,
+};
+#[cfg(feature = "bedrock")]
+use crate::{complete_bedrock, stream_completion_bedrock, BedrockConfig};
+use anyhow::{anyhow, Result};
+use futures::{future::BoxFuture, stream::BoxStream, StreamExt};
+use http::{HttpClient, Method};
+use std::{borrow::Cow, sync::Arc};
+
+/// Fetches the access token for a request, see [`Auth::BearerCallback`].
+pub type TokenCallback = Arc<dyn Fn() -> BoxFuture<'static, Result<String>> + Send + Sync>;
+
+/// How an [`AnthropicClient`] authenticates its requests.
+#[derive(Clone)]
+pub enum Auth {
+    ApiKey(String),
+    /// An access token sent in an `Authorization: Bearer` header.
+    Bearer(String),
+    /// An access token fetched before each request, so that the callback can
+    /// refresh it when it expires.
+    BearerCallback(TokenCallback),
+}
+
+impl std::fmt::Debug for Auth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ApiKey(_) => f.write_str("ApiKey(..)"),
+            Self::Bearer(_) => f.write_str("Bearer(..)"),
+            Self::BearerCallback(_) => f.write_str("BearerCallback(..)"),
+        }
+    }
+}
+
+/// Where an [`AnthropicClient`] sends its requests, and how it authenticates
+/// them.
+#[derive(Clone)]
+pub enum Transport {
+    /// The Anthropic API, or a gateway compatible with it.
+    Anthropic { api_url: String, auth: Auth },
+    /// Claude on Google Cloud's Vertex AI, which only supports sending
+    /// messages.
+    Vertex(VertexConfig),
+    /// Claude on AWS Bedrock, which only supports sending messages.
+    #[cfg(feature = "bedrock")]
+    Bedrock(BedrockConfig),
+}
+
+impl std::fmt::Debug for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Anthropic { api_url, auth } => f
+                .debug_struct("Anthropic")
+                .field("api_url", api_url)
+                .field("auth", auth)
+                .finish(),
+            Self::Vertex(config) => f
+                .debug_struct("Vertex")
+                .field("project_id", &config.project_id)
+                .field("region", &config.region)
+                .finish_non_exhaustive(),
+            #[cfg(feature = "bedrock")]
+            Self::Bedrock(config) => f.debug_tuple("Bedrock").field(config).finish(),
+        }
+    }
+}
+
+/// A client of the API, owning the HTTP client and the settings that the
+/// free functions of this crate take as arguments. Clones share them.
+#[derive(Clone)]
+pub struct AnthropicClient {
+    http_client: Arc<dyn HttpClient>,
+    config: Arc<ClientConfig>,
+}
+
+#[derive(Clone)]
+struct ClientConfig {
+    transport: Transport,
+    options: ClientOptions,
+    middleware: Vec<Arc<dyn Middleware>>,
+}
+
+impl AnthropicClient {
+    /// Creates a client of the API at [`ANTHROPIC_API_URL`].
+    pub fn new(http_client: Arc<dyn HttpClient>, api_key: impl Into<String>) -> Self {
+        Self::with_auth(http_client, Auth::ApiKey(api_key.into()))
+    }
+
+    pub fn with_auth(http_client: Arc<dyn HttpClient>, auth: Auth) -> Self {
+        Self::with_transport(
+            http_client,
+            Transport::Anthropic {
+                api_url: ANTHROPIC_API_URL.to_string(),
+                auth,
+            },
+        )
+    }
+
+    pub fn with_transport(http_client: Arc<dyn HttpClient>, transport: Transport) -> Self {
+        Self {
+            http_client,
+            config: Arc::new(ClientConfig {
+                transport,
+                options: ClientOptions::default(),
+                middleware: Vec::new(),
+            }),
+        }
+    }
+
+    /// Sends requests to the Anthropic API at `api_url`. Has no effect on
+    /// clients of other transports.
+    pub fn with_api_url(mut self, api_url: impl Into<String>) -> Self {
+        if let Transport::Anthropic {
+            api_url: current, ..
+        } = &mut Arc::make_mut(&mut self.config).transport
+        {
+            *current = api_url.into();
+        }
+        self
+    }
+
+    pub fn with_options(mut self, options: ClientOptions) -> Self {
+        Arc::make_mut(&mut self.config).options = options;
+        self
+    }
+
+    /// Adds `middleware` to run around every request, after the middleware
+    /// added before it.
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        Arc::make_mut(&mut self.config)
+            .middleware
+            .push(Arc::new(middleware));
+        self
+    }
+
+    pub fn http_client(&self) -> &Arc<dyn HttpClient> {
+        &self.http_client
+    }
+
+    pub fn transport(&self) -> &Transport {
+        &self.config.transport
+    }
+
+    /// The URL of the Anthropic API the client sends its requests to, or
+    /// `None` if it uses another transport.
+    pub fn api_url(&self) -> Option<&str> {
+        match &self.config.transport {
+            Transport::Anthropic { api_url, .. } => Some(api_url),
+            _ => None,
+        }
+    }
+
+    pub fn options(&self) -> &ClientOptions {
+        &self.config.options
+    }
+
+    /// Returns the URL of the Anthropic API, the key to pass to the API
+    /// functions, and the options with the matching [`AuthScheme`]. Fails
+    /// unless the client uses [`Transport::Anthropic`].
+    async fn credentials(&self) -> Result<(&str, Cow<'_, str>, Cow<'_, ClientOptions>)> {
+        let config = &*self.config;
+        let Transport::Anthropic { api_url, auth } = &config.transport else {
+            return Err(anyhow!(
+                "only messages can be sent through {:?}",
+                config.transport
+            ));
+        };
+        let (key, auth_scheme) = match auth {
+            Auth::ApiKey(api_key) => (Cow::Borrowed(api_key.as_str()), AuthScheme::ApiKey),
+            Auth::Bearer(token) => (Cow::Borrowed(token.as_str()), AuthScheme::Bearer),
+            Auth::BearerCallback(callback) => (Cow::Owned(callback().await?), AuthScheme::Bearer),
+        };
+        let options = if config.options.auth_scheme == auth_scheme {
+            Cow::Borrowed(&config.options)
+        } else {
+            Cow::Owned(ClientOptions {
+                auth_scheme,
+                ..config.options.clone()
+            })
+        };
+        Ok((api_url, key, options))
+    }
+
+    /// Runs the request hooks of the client's middleware on `request`, and
+    /// returns the HTTP client to send it with.
+    fn prepare(&self, request: &mut Request) -> MiddlewareHttpClient<'_> {
+        for middleware in &self.config.middleware {
+            middleware.on_request(request);
+        }
+        self.middleware_client()
+    }
+
+    fn middleware_client(&self) -> MiddlewareHttpClient<'_> {
+        MiddlewareHttpClient {
+            client: self.http_client.as_ref(),
+            middleware: &self.config.middleware,
+        }
+    }
+
+    /// See [`crate::complete`].
+    pub async fn complete(&self, mut request: Request) -> Result<Message> {
+        let client = self.prepare(&mut request);
+        let options = &self.config.options;
+        match &self.config.transport {
+            Transport::Anthropic { .. } => {
+                let (api_url, key, options) = self.credentials().await?;
+                complete(&client, api_url, &key, request, &options).await
+            }
+            Transport::Vertex(config) => complete_vertex(&client, config, request, options).await,
+            #[cfg(feature = "bedrock")]
+            Transport::Bedrock(config) => complete_bedrock(&client, config, request, options).await,
+        }
+    }
+
+    /// See [`crate::stream_completion`].
+    pub async fn stream(
+        &self,
+        mut request: Request,
+    ) -> Result<BoxStream<'static, Result<ResponseEvent>>> {
+        let client = self.prepare(&mut request);
+        let options = &self.config.options;
+        let events = match &self.config.transport {
+            Transport::Anthropic { .. } => {
+                let (api_url, key, options) = self.credentials().await?;
+                let (reader, _, span) =
+                    connect_stream(&client, api_url, &key, request, &options).await?;
+                span.stream(reader.into_stream())
+            }
+            Transport::Vertex(config) => {
+                stream_completion_vertex(&client, config, request, options).await?
+            }
+            #[cfg(feature = "bedrock")]
+            Transport::Bedrock(config) => {
+                stream_completion_bedrock(&client, config, request, options).await?
+            }
+        };
+        if self.config.middleware.is_empty() {
+            return Ok(events);
+        }
+        let middleware = self.config.middleware.clone();
+        Ok(events
+            .inspect(move |event| {
+                if let Ok(event) = event {
+                    for middleware in &middleware {
+                        middleware.on_event(event);
+                    }
+                }
+            })
+            .boxed())
+    }
+
+    /// See [`crate::count_tokens`].
+    pub async fn count_tokens(&self, mut request: Request) -> Result<u32> {
+        let (api_url, key, options) = self.credentials().await?;
+        count_tokens(
+            &self.prepare(&mut request),
+            api_url,
+            &key,
+            request,
+            &options,
+        )
+        .await
+    }
+
+    /// See [`crate::send_raw`]. The request goes through the client's
+    /// middleware with `betas` enabled on top of the client's, and is sent
+    /// again with `policy` when the API can't be reached or responds with a
+    /// retryable status, such as a rate limit. Retryable responses are
+    /// returned as errors once `policy` is exhausted.
+    pub async fn send_raw(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+        betas: &[String],
+        policy: &RetryPolicy,
+    ) -> Result<RawResponse> {
+        let (api_url, key, options) = self.credentials().await?;
+        let client = self.middleware_client();
+        let (client, key, options, method, body) = (&client, &key, &options, &method, &body);
+        with_retry(policy, || async move {
+            let request = raw_request(
+                api_url,
+                key,
+                method.clone(),
+                path,
+                body.clone(),
+                betas,
+                options,
+            )?;
+            let mut response = client.send(request).await?;
+            let status = response.status();
+            if !status.is_success() && ApiError::from_response(status.as_u16(), "").is_retryable() {
+                let body = body::read_body(&mut response).await?;
+                return Err(api_error(&response, &String::from_utf8_lossy(&body)).into());
+            }
+            Ok(RawResponse { response })
+        })
+        .await
+    }
+}
+
+impl std::fmt::Debug for AnthropicClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnthropicClient")
+            .field("transport", &self.config.transport)
+            .field("options", &self.config.options)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{
+    stream_completion_with_rate_limit, ApiError, ApiErrorKind, ClientOptions, RateLimitInfo,
+    Request, ResponseEvent,
+};
+use anyhow::Result;
+use chrono::Utc;
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use http::HttpClient;
+use smol::{lock::Semaphore, Timer};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Limits applied to a set of completions run by [`stream_many`].
+#[derive(Clone, Copy, Debug)]
+pub struct ConcurrencyLimits {
+    /// The maximum number of completions streaming at the same time.
+    pub max_concurrent: usize,
+    /// Spaces out the start of consecutive requests so that no more than this
+    /// many are sent per minute, to stay within the account's rate limits.
+    pub requests_per_minute: Option<u32>,
+    /// How long to stop sending requests after one fails with a rate limit
+    /// error that doesn't say when to retry.
+    pub rate_limit_pause: Duration,
+}
+
+impl ConcurrencyLimits {
+    const DEFAULT_RATE_LIMIT_PAUSE: Duration = Duration::from_secs(10);
+
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            max_concurrent,
+            requests_per_minute: None,
+            rate_limit_pause: Self::DEFAULT_RATE_LIMIT_PAUSE,
+        }
+    }
+}
+
+/// Runs a set of independent completions with bounded concurrency.
+///
+/// Returns one event stream per request, in the same order. Each request is
+/// only sent once its stream is first polled and a slot is available, and the
+/// slot is held until that stream completes or is dropped, so the streams are
+/// typically consumed together via [`futures::stream::select_all`] or similar.
+///
+/// When a request fails with a rate limit error, or a response reports that
+/// no requests remain, no further requests are sent until the time given by
+/// its `Retry-After` header or rate limit reset, or
+/// [`ConcurrencyLimits::rate_limit_pause`] if there is none. Requests that
+/// are already streaming aren't affected.
+pub fn stream_many(
+    client: Arc<dyn HttpClient>,
+    api_url: &str,
+    api_key: &str,
+    requests: Vec<Request>,
+    limits: ConcurrencyLimits,
+    options: ClientOptions,
+) -> Vec<BoxStream<'static, Result<ResponseEvent>>> {
+    let semaphore = Arc::new(Semaphore::new(limits.max_concurrent.max(1)));
+    let start_interval = limits
+        .requests_per_minute
+        .filter(|requests_per_minute| *requests_per_minute > 0)
+        .map(|requests_per_minute| Duration::from_secs(60) / requests_per_minute);
+    let next_start = Arc::new(Mutex::new(Instant::now()));
+    let paused_until = Arc::new(Mutex::new(Instant::now()));
+
+    requests
+        .into_iter()
+        .map(|request| {
+            let client = client.clone();
+            let api_url = api_url.to_string();
+            let api_key = api_key.to_string();
+            let options = options.clone();
+            let semaphore = semaphore.clone();
+            let next_start = next_start.clone();
+            let paused_until = paused_until.clone();
+            let rate_limit_pause = limits.rate_limit_pause;
+            stream::once(async move {
+                let permit = semaphore.acquire_arc().await;
+                if let Some(start_interval) = start_interval {
+                    wait_for_start(&next_start, start_interval).await;
+                }
+                wait_until_resumed(&paused_until).await;
+
+                match stream_completion_with_rate_limit(
+                    client.as_ref(),
+                    &api_url,
+                    &api_key,
+                    request,
+                    &options,
+                )
+                .await
+                {
+                    Ok((reader, rate_limit)) => {
+                        if let Some(pause) = exhausted_pause(&rate_limit) {
+                            pause_until(&paused_until, Instant::now() + pause);
+                        }
+                        reader
+                            .into_stream()
+                            .map(move |event| {
+                                let _permit = &permit;
+                                event
+                            })
+                            .boxed()
+                    }
+                    Err(error) => {
+                        if let Some(error) = error
+                            .downcast_ref::<ApiError>()
+                            .filter(|error| error.kind == ApiErrorKind::RateLimit)
+                        {
+                            let pause = error.retry_after.unwrap_or(rate_limit_pause);
+                            pause_until(&paused_until, Instant::now() + pause);
+                        }
+                        stream::once(async move { Err(error) }).boxed()
+                    }
+                }
+            })
+            .flatten()
+            .boxed()
+        })
+        .collect()
+}
+
+async fn wait_for_start(next_start: &Mutex<Instant>, interval: Duration) {
+    let start = {
+        let Ok(mut next_start) = next_start.lock() else {
+            return;
+        };
+        let start = (*next_start).max(Instant::now());
+        *next_start = start + interval;
+        start
+    };
+    Timer::at(start).await;
+}
+
+/// Waits until no pause started with [`pause_until`] is in effect, including
+/// ones started while waiting.
+async fn wait_until_resumed(paused_until: &Mutex<Instant>) {
+    loop {
+        let Ok(resume) = paused_until.lock().map(|paused_until| *paused_until) else {
+            return;
+        };
+        if resume <= Instant::now() {
+            return;
+        }
+        Timer::at(resume).await;
+    }
+}
+
+fn pause_until(paused_until: &Mutex<Instant>, resume: Instant) {
+    if let Ok(mut paused_until) = paused_until.lock() {
+        *paused_until = (*paused_until).max(resume);
+    }
+}
+
+/// Returns how long to wait for the request limit to reset if `rate_limit`
+/// reports that no requests remain.
+fn exhausted_pause(rate_limit: &RateLimitInfo) -> Option<Duration> {
+    if rate_limit.requests.remaining != Some(0) {
+        return None;
+    }
+    let reset = rate_limit.requests.reset?;
+    reset.signed_duration_since(Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FakeAnthropic, FakeResponse, Model, RequestBuilder, FAKE_API_URL};
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_rate_limit_pause() {
+        let fake = FakeAnthropic::new();
+        fake.respond(FakeResponse::error(
+            429,
+            ApiErrorKind::RateLimit,
+            "Rate limited",
+        ));
+        fake.respond(FakeResponse::text("Hello"));
+        let request = || {
+            RequestBuilder::new(Model::Claude3Haiku)
+                .user("Hi")
+                .build()
+                .unwrap()
+        };
+        let limits = ConcurrencyLimits {
+            rate_limit_pause: Duration::from_millis(50),
+            ..ConcurrencyLimits::new(1)
+        };
+
+        let start = Instant::now();
+        let mut streams = stream_many(
+            fake.clone(),
+            FAKE_API_URL,
+            "fake-api-key",
+            vec![request(), request()],
+            limits,
+            ClientOptions::default(),
+        );
+        let error = block_on(streams[0].next()).unwrap().unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ApiError>().unwrap().kind,
+            ApiErrorKind::RateLimit
+        );
+
+        let events = block_on(streams.remove(1).collect::<Vec<_>>());
+        assert!(events.iter().all(|event| event.is_ok()));
+        assert!(start.elapsed() >= limits.rate_limit_pause);
+        assert_eq!(fake.requests().len(), 2);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{ProxyConfig, ANTHROPIC_API_URL};
+use anyhow::Result;
+use http::{HttpClient, Uri};
+use isahc::{
+    auth::{Authentication, Credentials},
+    config::Configurable,
+};
+use std::{sync::Arc, time::Duration};
+
+/// Connection reuse settings for an HTTP client dedicated to the API.
+///
+/// Keeping a few warm connections around avoids repeating the TCP and TLS
+/// handshakes between bursts of requests.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionPoolOptions {
+    /// The maximum number of connections open at once, across all hosts.
+    pub max_connections: Option<usize>,
+    /// The maximum number of connections open at once to a single host.
+    pub max_connections_per_host: Option<usize>,
+    /// How many idle connections are kept open for reuse.
+    pub idle_connections: Option<usize>,
+    /// How long an idle connection is kept open before being closed.
+    pub idle_connection_lifetime: Option<Duration>,
+    /// The interval of TCP keep-alive probes sent on idle connections.
+    pub keep_alive_interval: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    /// Whether to route requests through the proxy configured by the
+    /// environment, as read by [`http::read_proxy_from_env_for`].
+    pub use_proxy_env: bool,
+    /// A proxy to route requests through, which takes precedence over the
+    /// environment.
+    pub proxy: Option<ProxyConfig>,
+}
+
+impl ConnectionPoolOptions {
+    pub fn build_http_client(&self) -> Result<Arc<dyn HttpClient>> {
+        self.build_http_client_for(ANTHROPIC_API_URL)
+    }
+
+    /// Builds a client for sending requests to `api_url`, which determines
+    /// whether they go through the proxy, if any.
+    pub fn build_http_client_for(&self, api_url: &str) -> Result<Arc<dyn HttpClient>> {
+        let mut builder = isahc::HttpClient::builder();
+        if let Some(proxy) = &self.proxy {
+            let api_url: Uri = api_url.parse()?;
+            builder = builder.proxy(proxy.proxy_for(&api_url)?);
+            if let Some(username) = &proxy.username {
+                builder = builder
+                    .proxy_authentication(Authentication::basic())
+                    .proxy_credentials(Credentials::new(
+                        username.as_str(),
+                        proxy.password.as_deref().unwrap_or_default(),
+                    ));
+            }
+        } else if self.use_proxy_env {
+            let api_url: Uri = api_url.parse()?;
+            builder = builder.proxy(http::read_proxy_from_env_for(&api_url));
+        } else {
+            // Without an explicit proxy, libcurl would still pick one up from
+            // the environment.
+            builder = builder.proxy(None);
+        }
+        if let Some(max_connections) = self.max_connections {
+            builder = builder.max_connections(max_connections);
+        }
+        if let Some(max_connections_per_host) = self.max_connections_per_host {
+            builder = builder.max_connections_per_host(max_connections_per_host);
+        }
+        if let Some(idle_connections) = self.idle_connections {
+            builder = builder.connection_cache_size(idle_connections);
+        }
+        if let Some(idle_connection_lifetime) = self.idle_connection_lifetime {
+            builder = builder.connection_cache_ttl(idle_connection_lifetime);
+        }
+        if let Some(keep_alive_interval) = self.keep_alive_interval {
+            builder = builder.tcp_keepalive(keep_alive_interval);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        Ok(Arc::new(builder.build()?))
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{
+    api_request_builder, body, request_options, send_json_request, ClientOptions, Request,
+    RequestMessage, SystemPrompt, Thinking, Tool, ToolChoice, ACCEPT_ENCODING,
+};
+use anyhow::Result;
+use http::{HttpClient, Method};
+use serde::{Deserialize, Serialize};
+
+/// The beta that enables the token counting endpoint.
+pub const TOKEN_COUNTING_BETA: &str = "token-counting-2024-11-01";
+
+/// The parts of a [`Request`] that count towards its input tokens.
+#[derive(Serialize)]
+struct CountTokensBody {
+    model: String,
+    messages: Vec<RequestMessage>,
+    #[serde(skip_serializing_if = "SystemPrompt::is_empty")]
+    system: SystemPrompt,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Tool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<ToolChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking: Option<Thinking>,
+}
+
+#[derive(Deserialize)]
+struct CountTokensResponse {
+    input_tokens: u32,
+}
+
+/// Returns the number of input tokens `request` would take up, including its
+/// system prompt and tools, without generating a completion.
+pub async fn count_tokens(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: Request,
+    options: &ClientOptions,
+) -> Result<u32> {
+    let uri = format!("{api_url}/v1/messages/count_tokens");
+    let options = request_options(&request, options);
+    let mut betas = request.betas;
+    if !betas.iter().any(|beta| beta == TOKEN_COUNTING_BETA) {
+        betas.push(TOKEN_COUNTING_BETA.to_string());
+    }
+    let mut request_builder = api_request_builder(Method::POST, &uri, api_key, &betas, &options)
+        .header("Accept-Encoding", ACCEPT_ENCODING);
+
+    let size_hint = request.system.encoded_len()
+        + request
+            .messages
+            .iter()
+            .map(|message| message.content.encoded_len())
+            .sum::<usize>();
+    let body = body::encode_body(
+        CountTokensBody {
+            model: request.model.id().to_string(),
+            messages: request.messages,
+            system: request.system,
+            tools: request.tools,
+            tool_choice: request.tool_choice,
+            thinking: request.thinking,
+        },
+        size_hint,
+        options.request_compression,
+    )?;
+    if let Some(content_encoding) = body.content_encoding {
+        request_builder = request_builder.header("Content-Encoding", content_encoding.as_str());
+    }
+    let request = request_builder.body(body.body)?;
+    let response: CountTokensResponse = send_json_request(client, request).await?;
+    Ok(response.input_tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FakeAnthropic, FakeResponse, Model, FAKE_API_URL};
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_count_tokens() {
+        let fake = FakeAnthropic::new();
+        fake.respond(FakeResponse::InputTokens(42));
+        let request = Request::new(Model::Claude3Haiku, ["Hello"]);
+
+        let input_tokens = block_on(count_tokens(
+            fake.as_ref(),
+            FAKE_API_URL,
+            "key",
+            request,
+            &ClientOptions::default(),
+        ))
+        .unwrap();
+        assert_eq!(input_tokens, 42);
+        assert_eq!(fake.paths(), ["/v1/messages/count_tokens"]);
+        let body = &fake.requests()[0];
+        assert_eq!(body["model"], Model::Claude3Haiku.id());
+        assert_eq!(body["messages"][0]["role"], "user");
+        assert!(body.get("max_tokens").is_none());
+        assert!(body.get("stream").is_none());
+    }
+}
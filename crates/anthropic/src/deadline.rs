@@ -0,0 +1,63 @@
+use anyhow::Result;
+use smol::{future::FutureExt as _, Timer};
+use std::{
+    fmt,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+/// The error of a request that didn't complete within its
+/// [`crate::Request::timeout`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeadlineExceeded {
+    pub timeout: Duration,
+}
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request didn't complete within {:?}", self.timeout)
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Deadline {
+    at: Instant,
+    timeout: Duration,
+}
+
+impl Deadline {
+    /// Returns the deadline of a request with the given timeout, starting
+    /// now.
+    pub(crate) fn after(timeout: Option<Duration>) -> Option<Self> {
+        timeout.map(|timeout| Self {
+            at: Instant::now() + timeout,
+            timeout,
+        })
+    }
+
+    fn error(&self) -> anyhow::Error {
+        DeadlineExceeded {
+            timeout: self.timeout,
+        }
+        .into()
+    }
+}
+
+/// Fails with [`DeadlineExceeded`] if `future` doesn't complete before
+/// `deadline`, dropping it.
+pub(crate) async fn within<T>(
+    deadline: Option<Deadline>,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(deadline) = deadline else {
+        return future.await;
+    };
+    future
+        .or(async {
+            Timer::at(deadline.at).await;
+            Err(deadline.error())
+        })
+        .await
+}
@@ -0,0 +1,82 @@
+use crate::{api_request_builder, request_options, ClientOptions, Request};
+use anyhow::Result;
+use http::Method;
+
+/// What [`dry_run`] would send for a request.
+#[derive(Clone, Debug)]
+pub struct DryRun {
+    pub method: String,
+    pub url: String,
+    /// The request's headers, with the API key redacted.
+    pub headers: Vec<(String, String)>,
+    /// The JSON body, before any compression.
+    pub body: String,
+    pub estimated_input_tokens: usize,
+    /// The cost, in US dollars, of the request if the model generated
+    /// `max_tokens` tokens, or `None` if the model's pricing isn't known.
+    pub max_cost: Option<f64>,
+}
+
+const REDACTED_HEADERS: &[&str] = &["x-api-key", "authorization"];
+
+/// Returns exactly what sending `request` would transmit to the API, along
+/// with its estimated size and cost, without sending anything.
+pub fn dry_run(
+    api_url: &str,
+    api_key: &str,
+    request: &Request,
+    options: &ClientOptions,
+) -> Result<DryRun> {
+    let url = format!("{api_url}/v1/messages");
+    let body = serde_json::to_string(request)?;
+    let mut request_builder = api_request_builder(
+        Method::POST,
+        &url,
+        api_key,
+        &request.betas,
+        &request_options(request, options),
+    );
+    if let Some(compression) = options
+        .request_compression
+        .filter(|compression| body.len() >= compression.min_size)
+    {
+        request_builder = request_builder.header("Content-Encoding", compression.encoding.as_str());
+    }
+    let http_request = request_builder.body(())?;
+    let headers = http_request
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                "<redacted>".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect();
+
+    let estimated_input_tokens = estimate_input_tokens(request);
+    let max_cost = request
+        .model
+        .pricing()
+        .map(|pricing| pricing.cost(estimated_input_tokens, request.max_tokens as usize));
+    Ok(DryRun {
+        method: Method::POST.to_string(),
+        url,
+        headers,
+        body,
+        estimated_input_tokens,
+        max_cost,
+    })
+}
+
+#[cfg(feature = "bpe-tokenizer")]
+fn estimate_input_tokens(request: &Request) -> usize {
+    crate::bpe_request_token_count(request)
+}
+
+#[cfg(not(feature = "bpe-tokenizer"))]
+fn estimate_input_tokens(request: &Request) -> usize {
+    crate::estimate_request_tokens(request)
+}
@@ -0,0 +1,368 @@
+use crate::{AnthropicClient, ApiErrorKind, Model};
+use futures::{future::BoxFuture, AsyncReadExt as _};
+use http::{AsyncBody, Error, HttpClient, Request as HttpRequest, Response as HttpResponse, Uri};
+use serde_json::{json, Value};
+use smol::Timer;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+pub const FAKE_API_URL: &str = "http://anthropic.test";
+
+/// A response scripted with [`FakeAnthropic::respond`].
+#[derive(Clone, Debug)]
+pub enum FakeResponse {
+    /// A message with the given text, streamed in chunks of a few words if
+    /// the request asks for a stream.
+    Text(String),
+    /// A message calling one of the request's tools.
+    ToolUse {
+        id: String,
+        name: String,
+        input: Value,
+    },
+    /// The given server-sent events, such as `{"type": "message_stop"}`,
+    /// for scripting streams that the other responses can't express.
+    Events(Vec<Value>),
+    /// The response to a token counting request.
+    InputTokens(u32),
+    /// A JSON response for the API's other endpoints, such as a page of
+    /// [`crate::list_models`].
+    Json(Value),
+    /// An error response.
+    Error {
+        status: u16,
+        kind: ApiErrorKind,
+        message: String,
+    },
+    /// `response`, sent once `delay` has passed, for scripting concurrent
+    /// requests that are answered in a given order.
+    Delayed {
+        delay: Duration,
+        response: Box<FakeResponse>,
+    },
+}
+
+impl FakeResponse {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    pub fn error(status: u16, kind: ApiErrorKind, message: impl Into<String>) -> Self {
+        Self::Error {
+            status,
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn delayed(self, delay: Duration) -> Self {
+        Self::Delayed {
+            delay,
+            response: Box::new(self),
+        }
+    }
+}
+
+/// An in-memory stand-in for the API, which answers the requests sent to it
+/// with the responses scripted for it in order, and records their bodies.
+///
+/// Requests sent once the script is exhausted fail with a 500 error. Bodies
+/// are expected to be sent uncompressed.
+#[derive(Default)]
+pub struct FakeAnthropic {
+    state: Arc<Mutex<FakeState>>,
+}
+
+#[derive(Default)]
+struct FakeState {
+    responses: VecDeque<FakeResponse>,
+    model_responses: HashMap<String, VecDeque<FakeResponse>>,
+    requests: Vec<Value>,
+    paths: Vec<String>,
+}
+
+impl FakeAnthropic {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Returns a client sending its requests to this fake.
+    pub fn client(self: &Arc<Self>) -> AnthropicClient {
+        AnthropicClient::new(self.clone(), "fake-api-key").with_api_url(FAKE_API_URL)
+    }
+
+    /// Queues `response` as the answer to the next unanswered request.
+    pub fn respond(&self, response: FakeResponse) {
+        self.state.lock().unwrap().responses.push_back(response);
+    }
+
+    /// Queues `response` as the answer to the next unanswered request for
+    /// `model`, ahead of the responses queued with [`Self::respond`].
+    pub fn respond_to(&self, model: &Model, response: FakeResponse) {
+        self.state
+            .lock()
+            .unwrap()
+            .model_responses
+            .entry(model.id().to_string())
+            .or_default()
+            .push_back(response);
+    }
+
+    /// Returns the bodies of the requests received so far.
+    pub fn requests(&self) -> Vec<Value> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Returns the paths, including queries, of the requests received so far.
+    pub fn paths(&self) -> Vec<String> {
+        self.state.lock().unwrap().paths.clone()
+    }
+}
+
+impl FakeState {
+    /// Returns the response to a request, and how long to wait before
+    /// sending it.
+    fn answer(&mut self, path: String, body: &[u8]) -> (HttpResponse<AsyncBody>, Duration) {
+        let request: Value = serde_json::from_slice(body).unwrap_or_default();
+        self.requests.push(request.clone());
+        self.paths.push(path);
+        let model = request["model"].as_str().unwrap_or_default();
+        let response = self
+            .model_responses
+            .get_mut(model)
+            .and_then(VecDeque::pop_front)
+            .or_else(|| self.responses.pop_front());
+        let Some(mut response) = response else {
+            let response = error_response(500, &ApiErrorKind::Api, "no response scripted");
+            return (response, Duration::ZERO);
+        };
+        let mut delay = Duration::ZERO;
+        while let FakeResponse::Delayed {
+            delay: response_delay,
+            response: delayed,
+        } = response
+        {
+            delay += response_delay;
+            response = *delayed;
+        }
+        (response_to(&request, response), delay)
+    }
+}
+
+fn response_to(request: &Value, response: FakeResponse) -> HttpResponse<AsyncBody> {
+    let model = request["model"].as_str().unwrap_or_default();
+    let stream = request["stream"].as_bool().unwrap_or(false);
+    let content = match response {
+        FakeResponse::Text(text) => json!({"type": "text", "text": text}),
+        FakeResponse::ToolUse { id, name, input } => {
+            json!({"type": "tool_use", "id": id, "name": name, "input": input})
+        }
+        FakeResponse::Events(events) => return events_response(events),
+        FakeResponse::InputTokens(input_tokens) => {
+            return json_response(json!({ "input_tokens": input_tokens }))
+        }
+        FakeResponse::Json(body) => return json_response(body),
+        FakeResponse::Delayed { response, .. } => return response_to(request, *response),
+        FakeResponse::Error {
+            status,
+            kind,
+            message,
+        } => return error_response(status, &kind, &message),
+    };
+    let stop_reason = if content["type"] == "tool_use" {
+        "tool_use"
+    } else {
+        "end_turn"
+    };
+    if stream {
+        events_response(message_events(model, content, stop_reason))
+    } else {
+        json_response(json!({
+            "type": "message",
+            "id": "msg_fake",
+            "role": "assistant",
+            "model": model,
+            "content": [content],
+            "stop_reason": stop_reason,
+            "stop_sequence": null,
+            "usage": {"input_tokens": 1, "output_tokens": 1},
+        }))
+    }
+}
+
+impl HttpClient for FakeAnthropic {
+    fn send(
+        &self,
+        mut req: HttpRequest<AsyncBody>,
+    ) -> BoxFuture<'static, Result<HttpResponse<AsyncBody>, Error>> {
+        let state = self.state.clone();
+        let path = req
+            .uri()
+            .path_and_query()
+            .map_or_else(String::new, |path| path.to_string());
+        Box::pin(async move {
+            let mut body = Vec::new();
+            req.body_mut().read_to_end(&mut body).await?;
+            let (response, delay) = state.lock().unwrap().answer(path, &body);
+            if !delay.is_zero() {
+                Timer::after(delay).await;
+            }
+            Ok(response)
+        })
+    }
+
+    fn proxy(&self) -> Option<&Uri> {
+        None
+    }
+}
+
+impl std::fmt::Debug for FakeAnthropic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FakeAnthropic").finish_non_exhaustive()
+    }
+}
+
+/// Returns the events streaming a message with a single content block.
+fn message_events(model: &str, content: Value, stop_reason: &str) -> Vec<Value> {
+    let mut events = vec![json!({
+        "type": "message_start",
+        "message": {
+            "type": "message",
+            "id": "msg_fake",
+            "role": "assistant",
+            "model": model,
+            "content": [],
+            "usage": {"input_tokens": 1, "output_tokens": 0},
+        },
+    })];
+    if let Some(text) = content["text"].as_str() {
+        events.push(json!({
+            "type": "content_block_start",
+            "index": 0,
+            "content_block": {"type": "text", "text": ""},
+        }));
+        let words: Vec<&str> = text.split_inclusive(' ').collect();
+        for chunk in words.chunks(3) {
+            events.push(json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "text_delta", "text": chunk.concat()},
+            }));
+        }
+    } else {
+        let mut content_block = content.clone();
+        content_block["input"] = json!({});
+        events.push(json!({
+            "type": "content_block_start",
+            "index": 0,
+            "content_block": content_block,
+        }));
+        events.push(json!({
+            "type": "content_block_delta",
+            "index": 0,
+            "delta": {"type": "input_json_delta", "partial_json": content["input"].to_string()},
+        }));
+    }
+    events.push(json!({"type": "content_block_stop", "index": 0}));
+    events.push(json!({
+        "type": "message_delta",
+        "delta": {"stop_reason": stop_reason, "stop_sequence": null},
+        "usage": {"output_tokens": 1},
+    }));
+    events.push(json!({"type": "message_stop"}));
+    events
+}
+
+fn events_response(events: Vec<Value>) -> HttpResponse<AsyncBody> {
+    let mut body = String::new();
+    for event in events {
+        let name = event["type"].as_str().unwrap_or_default().to_string();
+        body.push_str(&format!("event: {name}\ndata: {event}\n\n"));
+    }
+    HttpResponse::builder()
+        .status(200)
+        .header("Content-Type", "text/event-stream")
+        .body(AsyncBody::from(body))
+        .unwrap()
+}
+
+fn json_response(body: Value) -> HttpResponse<AsyncBody> {
+    HttpResponse::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(AsyncBody::from(body.to_string()))
+        .unwrap()
+}
+
+fn error_response(status: u16, kind: &ApiErrorKind, message: &str) -> HttpResponse<AsyncBody> {
+    let body = json!({
+        "type": "error",
+        "error": {"type": kind.as_str(), "message": message},
+    });
+    HttpResponse::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(AsyncBody::from(body.to_string()))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ApiError, ContentBlock, Model, RequestBuilder};
+    use futures::{executor::block_on, StreamExt};
+
+    #[test]
+    fn test_fake_anthropic() {
+        let fake = FakeAnthropic::new();
+        let client = fake.client();
+        let request = || {
+            RequestBuilder::new(Model::Claude3Haiku)
+                .user("Hi")
+                .build()
+                .unwrap()
+        };
+
+        fake.respond(FakeResponse::text("Hello there, how can I help?"));
+        let events = block_on(async {
+            client
+                .stream(request())
+                .await
+                .unwrap()
+                .collect::<Vec<_>>()
+                .await
+        });
+        let text: String = events
+            .iter()
+            .filter_map(|event| event.as_ref().unwrap().text())
+            .collect();
+        assert_eq!(text, "Hello there, how can I help?");
+
+        fake.respond(FakeResponse::ToolUse {
+            id: "toolu_1".into(),
+            name: "get_weather".into(),
+            input: json!({"city": "Paris"}),
+        });
+        let message = block_on(client.complete(request())).unwrap();
+        assert!(matches!(
+            &message.content[0],
+            ContentBlock::ToolUse { name, input, .. }
+                if name == "get_weather" && input["city"] == "Paris"
+        ));
+
+        fake.respond(FakeResponse::error(
+            529,
+            ApiErrorKind::Overloaded,
+            "Overloaded",
+        ));
+        let error = block_on(client.complete(request())).unwrap_err();
+        assert_eq!(error.downcast_ref::<ApiError>().unwrap().status, 529);
+
+        let requests = fake.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0]["messages"][0]["content"], "Hi");
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{
+    api_request_builder, fetch_all_pages, send_json_request, ClientOptions, ACCEPT_ENCODING,
+    FILES_API_BETA,
+};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use http::{AsyncBody, HttpClient, Method};
+use isahc::http::{header::CONTENT_TYPE, HeaderValue};
+use serde::Deserialize;
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// A file uploaded with [`upload_file`], which content blocks can refer to
+/// by its ID instead of including its data in every request.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct FileMetadata {
+    pub id: String,
+    pub filename: String,
+    pub mime_type: String,
+    pub size_bytes: u64,
+    pub created_at: DateTime<Utc>,
+    /// Whether the file's contents can be downloaded, which is only the case
+    /// for files created by tools rather than uploaded.
+    #[serde(default)]
+    pub downloadable: bool,
+}
+
+pub async fn upload_file(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    filename: &str,
+    mime_type: &str,
+    contents: Vec<u8>,
+    options: &ClientOptions,
+) -> Result<FileMetadata> {
+    let uri = format!("{api_url}/v1/files");
+    let boundary = format!(
+        "anthropic-boundary-{:016x}",
+        RandomState::new().build_hasher().finish()
+    );
+    let body = multipart_body(&boundary, filename, mime_type, &contents);
+    let mut request = api_request_builder(Method::POST, &uri, api_key, &files_betas(), options)
+        .header("Accept-Encoding", ACCEPT_ENCODING)
+        .body(AsyncBody::from(body))?;
+    request.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_str(&format!("multipart/form-data; boundary={boundary}"))?,
+    );
+    send_json_request(client, request).await
+}
+
+/// Returns all the files uploaded with the API key's workspace, most recent
+/// first.
+pub async fn list_files(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    options: &ClientOptions,
+) -> Result<Vec<FileMetadata>> {
+    let uri = format!("{api_url}/v1/files");
+    fetch_all_pages(client, &uri, api_key, &files_betas(), options).await
+}
+
+pub async fn get_file(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    file_id: &str,
+    options: &ClientOptions,
+) -> Result<FileMetadata> {
+    let uri = format!("{api_url}/v1/files/{file_id}");
+    let request = api_request_builder(Method::GET, &uri, api_key, &files_betas(), options)
+        .header("Accept-Encoding", ACCEPT_ENCODING)
+        .body(AsyncBody::empty())?;
+    send_json_request(client, request).await
+}
+
+pub async fn delete_file(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    file_id: &str,
+    options: &ClientOptions,
+) -> Result<()> {
+    let uri = format!("{api_url}/v1/files/{file_id}");
+    let request = api_request_builder(Method::DELETE, &uri, api_key, &files_betas(), options)
+        .body(AsyncBody::empty())?;
+    let _: serde_json::Value = send_json_request(client, request).await?;
+    Ok(())
+}
+
+fn files_betas() -> [String; 1] {
+    [FILES_API_BETA.to_string()]
+}
+
+/// Encodes `contents` as the `file` field of a `multipart/form-data` body.
+fn multipart_body(boundary: &str, filename: &str, mime_type: &str, contents: &[u8]) -> Vec<u8> {
+    let filename = filename.replace('"', "%22").replace(['\r', '\n'], " ");
+    let mut body = Vec::with_capacity(contents.len() + 256);
+    body.extend_from_slice(
+        format!(
+            "--{boundary}\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"{filename}\"\r\n\
+            Content-Type: {mime_type}\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(contents);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FakeAnthropic, FakeResponse, FAKE_API_URL};
+    use futures::executor::block_on;
+    use serde_json::json;
+
+    #[test]
+    fn test_list_files() {
+        let file = |id: &str| {
+            json!({
+                "id": id,
+                "filename": format!("{id}.txt"),
+                "mime_type": "text/plain",
+                "size_bytes": 5,
+                "created_at": "2025-04-01T12:00:00Z",
+            })
+        };
+        let fake = FakeAnthropic::new();
+        fake.respond(FakeResponse::Json(json!({
+            "data": [file("file_2"), file("file_1")],
+            "has_more": true,
+            "first_id": "file_2",
+            "last_id": "file_1",
+        })));
+        fake.respond(FakeResponse::Json(json!({
+            "data": [file("file_0")],
+            "has_more": false,
+            "first_id": "file_0",
+            "last_id": "file_0",
+        })));
+
+        let files = block_on(list_files(
+            fake.as_ref(),
+            FAKE_API_URL,
+            "key",
+            &ClientOptions::default(),
+        ))
+        .unwrap();
+        assert_eq!(
+            files
+                .iter()
+                .map(|file| file.id.as_str())
+                .collect::<Vec<_>>(),
+            ["file_2", "file_1", "file_0"]
+        );
+        assert!(!files[0].downloadable);
+        assert_eq!(
+            fake.paths(),
+            ["/v1/files?limit=100", "/v1/files?limit=100&after_id=file_1"]
+        );
+    }
+
+    #[test]
+    fn test_multipart_body() {
+        let body = multipart_body("xyz", "a \"b\".txt", "text/plain", b"hello");
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "--xyz\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"a %22b%22.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n\
+            hello\r\n\
+            --xyz--\r\n"
+        );
+    }
+}
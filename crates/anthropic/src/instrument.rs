@@ -0,0 +1,165 @@
+use crate::{ClientOptions, Request, ResponseEvent, Usage};
+use anyhow::Result;
+use futures::{stream::BoxStream, Future};
+
+/// The `tracing` span of a request, recording its model, message count,
+/// status, duration and token usage when the `tracing` feature is enabled.
+///
+/// The API key is never recorded, and the contents of the request only when
+/// [`ClientOptions::trace_contents`] is set.
+pub(crate) struct RequestSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "tracing")]
+    start: std::time::Instant,
+}
+
+impl RequestSpan {
+    #[cfg(feature = "tracing")]
+    pub(crate) fn new(request: &Request, options: &ClientOptions) -> Self {
+        let span = tracing::info_span!(
+            "anthropic_request",
+            model = request.model.id(),
+            messages = request.messages.len(),
+            status = tracing::field::Empty,
+            duration_ms = tracing::field::Empty,
+            input_tokens = tracing::field::Empty,
+            output_tokens = tracing::field::Empty,
+        );
+        if options.trace_contents {
+            span.in_scope(|| {
+                tracing::debug!(
+                    system = ?request.system,
+                    messages = ?request.messages,
+                    "request contents"
+                )
+            });
+        }
+        Self {
+            span,
+            start: std::time::Instant::now(),
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn new(_request: &Request, _options: &ClientOptions) -> Self {
+        Self {}
+    }
+
+    /// Sends the request with `future` within the span, recording the
+    /// status of failed requests.
+    pub(crate) async fn send<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        #[cfg(feature = "tracing")]
+        {
+            use tracing::Instrument as _;
+            let result = future.instrument(self.span.clone()).await;
+            if let Err(error) = &result {
+                if let Some(error) = error.downcast_ref::<crate::ApiError>() {
+                    self.span.record("status", error.status);
+                }
+                self.span
+                    .in_scope(|| tracing::debug!(error = %error, "request failed"));
+            }
+            result
+        }
+        #[cfg(not(feature = "tracing"))]
+        future.await
+    }
+
+    /// Records the duration and token usage of the completed request.
+    pub(crate) fn finish(&self, _usage: &Usage) {
+        #[cfg(feature = "tracing")]
+        {
+            self.span
+                .record("duration_ms", self.start.elapsed().as_millis() as u64);
+            if let Some(input_tokens) = _usage.input_tokens {
+                self.span.record("input_tokens", input_tokens);
+            }
+            if let Some(output_tokens) = _usage.output_tokens {
+                self.span.record("output_tokens", output_tokens);
+            }
+        }
+    }
+
+    /// Traces the lifecycle of the streamed response, finishing the span when
+    /// the stream is dropped.
+    pub(crate) fn stream(
+        self,
+        events: BoxStream<'static, Result<ResponseEvent>>,
+    ) -> BoxStream<'static, Result<ResponseEvent>> {
+        #[cfg(feature = "tracing")]
+        {
+            use futures::StreamExt as _;
+            let mut guard = StreamGuard {
+                span: self,
+                usage: Usage::default(),
+                done: false,
+            };
+            events
+                .map(move |event| {
+                    guard.observe(&event);
+                    event
+                })
+                .boxed()
+        }
+        #[cfg(not(feature = "tracing"))]
+        events
+    }
+}
+
+/// Records the HTTP status of the response to the request of the current
+/// span.
+pub(crate) fn record_status(_status: u16) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("status", _status);
+}
+
+#[cfg(feature = "tracing")]
+struct StreamGuard {
+    span: RequestSpan,
+    usage: Usage,
+    done: bool,
+}
+
+#[cfg(feature = "tracing")]
+impl StreamGuard {
+    fn observe(&mut self, event: &Result<ResponseEvent>) {
+        let _enter = self.span.span.enter();
+        match event {
+            Ok(event) => {
+                if let Some(usage) = event.usage() {
+                    self.usage.update(usage);
+                }
+                match event {
+                    ResponseEvent::MessageStart { .. } => tracing::debug!("stream started"),
+                    ResponseEvent::MessageStop {} => {
+                        self.done = true;
+                        tracing::debug!("stream completed");
+                    }
+                    ResponseEvent::Error { error } => {
+                        self.done = true;
+                        self.span.span.record("status", error.status);
+                        tracing::debug!(error = %error, "stream failed");
+                    }
+                    _ => {}
+                }
+            }
+            Err(error) => {
+                self.done = true;
+                tracing::debug!(error = %error, "stream failed");
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        if !self.done {
+            self.span
+                .span
+                .in_scope(|| tracing::debug!("stream dropped before completion"));
+        }
+        self.span.finish(&self.usage);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{Request, ResponseEvent};
+use futures::future::BoxFuture;
+use http::{AsyncBody, Error, HttpClient, Request as HttpRequest, Response as HttpResponse, Uri};
+use std::sync::Arc;
+
+/// Hooks run by an [`crate::AnthropicClient`] around the requests it sends,
+/// e.g. for logging, injecting credentials or collecting telemetry.
+///
+/// Middleware added to a client runs in the order it was added.
+pub trait Middleware: Send + Sync {
+    /// Called with each request before it's encoded, so that its parameters
+    /// can be changed.
+    fn on_request(&self, _request: &mut Request) {}
+
+    /// Called with each HTTP request before it's sent, so that its headers
+    /// and body can be changed.
+    fn on_http_request(&self, _request: &mut HttpRequest<AsyncBody>) {}
+
+    /// Called with each HTTP response before its body is read, including
+    /// unsuccessful ones.
+    fn on_response(&self, _response: &HttpResponse<AsyncBody>) {}
+
+    /// Called with each event of a streamed response.
+    fn on_event(&self, _event: &ResponseEvent) {}
+}
+
+/// An [`HttpClient`] that runs the HTTP hooks of `middleware` around the
+/// requests sent through `client`.
+pub(crate) struct MiddlewareHttpClient<'a> {
+    pub client: &'a dyn HttpClient,
+    pub middleware: &'a [Arc<dyn Middleware>],
+}
+
+impl HttpClient for MiddlewareHttpClient<'_> {
+    fn send(
+        &self,
+        mut req: HttpRequest<AsyncBody>,
+    ) -> BoxFuture<'static, Result<HttpResponse<AsyncBody>, Error>> {
+        for middleware in self.middleware {
+            middleware.on_http_request(&mut req);
+        }
+        let response = self.client.send(req);
+        let middleware = self.middleware.to_vec();
+        Box::pin(async move {
+            let response = response.await?;
+            for middleware in &middleware {
+                middleware.on_response(&response);
+            }
+            Ok(response)
+        })
+    }
+
+    fn proxy(&self) -> Option<&Uri> {
+        self.client.proxy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnthropicClient, Model, RequestBuilder};
+    use futures::executor::block_on;
+    use std::sync::Mutex;
+
+    struct CountTokensServer {
+        requests: Mutex<Vec<HttpRequest<AsyncBody>>>,
+    }
+
+    impl HttpClient for CountTokensServer {
+        fn send(
+            &self,
+            req: HttpRequest<AsyncBody>,
+        ) -> BoxFuture<'static, Result<HttpResponse<AsyncBody>, Error>> {
+            self.requests.lock().unwrap().push(req);
+            let response = HttpResponse::builder()
+                .status(200)
+                .body(AsyncBody::from(r#"{"input_tokens":7}"#))
+                .unwrap();
+            Box::pin(async move { Ok(response) })
+        }
+
+        fn proxy(&self) -> Option<&Uri> {
+            None
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        statuses: Mutex<Vec<u16>>,
+    }
+
+    impl Middleware for Arc<Recorder> {
+        fn on_http_request(&self, request: &mut HttpRequest<AsyncBody>) {
+            request
+                .headers_mut()
+                .insert("X-Trace-Id", "abc".parse().unwrap());
+        }
+
+        fn on_response(&self, response: &HttpResponse<AsyncBody>) {
+            self.statuses
+                .lock()
+                .unwrap()
+                .push(response.status().as_u16());
+        }
+    }
+
+    #[test]
+    fn test_middleware() {
+        let server = Arc::new(CountTokensServer {
+            requests: Mutex::default(),
+        });
+        let recorder = Arc::new(Recorder::default());
+        let client = AnthropicClient::new(server.clone(), "key").with_middleware(recorder.clone());
+        let request = RequestBuilder::new(Model::Claude3Haiku)
+            .user("Hi")
+            .build()
+            .unwrap();
+
+        assert_eq!(block_on(client.count_tokens(request)).unwrap(), 7);
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(requests[0].headers()["X-Trace-Id"], "abc");
+        assert_eq!(*recorder.statuses.lock().unwrap(), [200]);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{fetch_all_pages, ClientOptions, Model};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use http::HttpClient;
+use serde::Deserialize;
+
+/// A model available to an API key, as returned by [`list_models`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    pub display_name: String,
+    /// When the model was released, or an approximation for older models.
+    pub created_at: DateTime<Utc>,
+}
+
+impl ModelInfo {
+    pub fn model(&self) -> Result<Model> {
+        Model::from_id(&self.id)
+    }
+}
+
+/// Returns the models `api_key` can use, most recently released first.
+pub async fn list_models(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    options: &ClientOptions,
+) -> Result<Vec<ModelInfo>> {
+    let uri = format!("{api_url}/v1/models");
+    fetch_all_pages(client, &uri, api_key, &[], options).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FakeAnthropic, FakeResponse, FAKE_API_URL};
+    use futures::executor::block_on;
+    use serde_json::json;
+
+    #[test]
+    fn test_list_models() {
+        let fake = FakeAnthropic::new();
+        fake.respond(FakeResponse::Json(json!({
+            "data": [{
+                "type": "model",
+                "id": "claude-3-7-sonnet-20250219",
+                "display_name": "Claude 3.7 Sonnet",
+                "created_at": "2025-02-24T00:00:00Z",
+            }],
+            "has_more": true,
+            "first_id": "claude-3-7-sonnet-20250219",
+            "last_id": "claude-3-7-sonnet-20250219",
+        })));
+        fake.respond(FakeResponse::Json(json!({
+            "data": [{
+                "type": "model",
+                "id": "claude-3-5-haiku-20241022",
+                "display_name": "Claude 3.5 Haiku",
+                "created_at": "2024-10-22T00:00:00Z",
+            }],
+            "has_more": false,
+            "first_id": "claude-3-5-haiku-20241022",
+            "last_id": "claude-3-5-haiku-20241022",
+        })));
+
+        let models = block_on(list_models(
+            fake.as_ref(),
+            FAKE_API_URL,
+            "key",
+            &ClientOptions::default(),
+        ))
+        .unwrap();
+        assert_eq!(
+            models
+                .iter()
+                .map(|model| model.display_name.as_str())
+                .collect::<Vec<_>>(),
+            ["Claude 3.7 Sonnet", "Claude 3.5 Haiku"]
+        );
+        assert_eq!(
+            fake.paths(),
+            [
+                "/v1/models?limit=100",
+                "/v1/models?limit=100&after_id=claude-3-7-sonnet-20250219"
+            ]
+        );
+    }
+}
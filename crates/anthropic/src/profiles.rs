@@ -0,0 +1,193 @@
+use crate::{
+    AnthropicClient, Auth, ClientOptions, Conversation, RequestDefaults, Transport,
+    ANTHROPIC_API_URL,
+};
+use anyhow::{anyhow, Result};
+use http::HttpClient;
+use std::{collections::BTreeMap, sync::Arc};
+
+/// The transport, credentials and defaults used for one account or route to
+/// the API.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub transport: Transport,
+    pub defaults: RequestDefaults,
+    pub options: ClientOptions,
+}
+
+impl Profile {
+    /// Creates a profile for the Anthropic API authenticated with `api_key`.
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self::with_transport(Transport::Anthropic {
+            api_url: ANTHROPIC_API_URL.to_string(),
+            auth: Auth::ApiKey(api_key.into()),
+        })
+    }
+
+    pub fn with_transport(transport: Transport) -> Self {
+        Self {
+            transport,
+            defaults: RequestDefaults::default(),
+            options: ClientOptions::default(),
+        }
+    }
+
+    /// Starts a conversation that uses this profile's defaults.
+    pub fn new_conversation(&self, name: &str) -> Conversation {
+        let mut conversation = Conversation::new(self.defaults.clone());
+        conversation.set_profile(Some(name.to_string()));
+        conversation
+    }
+
+    /// Returns a client sending requests through this profile's transport
+    /// with its options.
+    pub fn client(&self, http_client: Arc<dyn HttpClient>) -> AnthropicClient {
+        AnthropicClient::with_transport(http_client, self.transport.clone())
+            .with_options(self.options.clone())
+    }
+}
+
+/// A set of named [`Profile`]s, e.g. "personal" and "work gateway", one of
+/// which is active unless a request or conversation names another.
+#[derive(Clone, Debug, Default)]
+pub struct ProfileRegistry {
+    profiles: BTreeMap<String, Profile>,
+    active: Option<String>,
+}
+
+impl ProfileRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces a profile. The first profile added becomes active.
+    pub fn insert(&mut self, name: impl Into<String>, profile: Profile) {
+        let name = name.into();
+        if self.active.is_none() {
+            self.active = Some(name.clone());
+        }
+        self.profiles.insert(name, profile);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Profile> {
+        if self.active.as_deref() == Some(name) {
+            self.active = None;
+        }
+        self.profiles.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    pub fn set_active(&mut self, name: &str) -> Result<()> {
+        if !self.profiles.contains_key(name) {
+            return Err(anyhow!("no profile named '{name}'"));
+        }
+        self.active = Some(name.to_string());
+        Ok(())
+    }
+
+    pub fn active(&self) -> Option<(&str, &Profile)> {
+        let name = self.active.as_deref()?;
+        Some((name, self.profiles.get(name)?))
+    }
+
+    /// Returns the profile named `name`, or the active one if `name` is
+    /// `None`.
+    pub fn resolve(&self, name: Option<&str>) -> Result<&Profile> {
+        match name {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .ok_or_else(|| anyhow!("no profile named '{name}'")),
+            None => self
+                .active()
+                .map(|(_, profile)| profile)
+                .ok_or_else(|| anyhow!("no active profile")),
+        }
+    }
+
+    /// Returns the profile `conversation` is bound to, or the active one.
+    pub fn profile_for(&self, conversation: &Conversation) -> Result<&Profile> {
+        self.resolve(conversation.profile())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FakeAnthropic, FakeResponse, Middleware, Model, FAKE_API_URL};
+    use futures::executor::block_on;
+    use http::{AsyncBody, Request as HttpRequest};
+    use std::sync::Mutex;
+
+    /// Records the API key each request is sent with.
+    #[derive(Default)]
+    struct KeyRecorder(Mutex<Vec<String>>);
+
+    impl Middleware for Arc<KeyRecorder> {
+        fn on_http_request(&self, request: &mut HttpRequest<AsyncBody>) {
+            let key = request.headers()["X-Api-Key"].to_str().unwrap().to_string();
+            self.0.lock().unwrap().push(key);
+        }
+    }
+
+    fn profile(api_key: &str, model: Model) -> Profile {
+        let mut profile = Profile::with_transport(Transport::Anthropic {
+            api_url: FAKE_API_URL.to_string(),
+            auth: Auth::ApiKey(api_key.to_string()),
+        });
+        profile.defaults.model = Some(model);
+        profile.defaults.max_tokens = Some(16);
+        profile
+    }
+
+    #[test]
+    fn test_profiles() {
+        let fake = FakeAnthropic::new();
+        let keys = Arc::new(KeyRecorder::default());
+        let mut registry = ProfileRegistry::new();
+        registry.insert("personal", profile("personal-key", Model::Claude3Haiku));
+        registry.insert("work", profile("work-key", Model::Claude3Opus));
+
+        let send = |registry: &ProfileRegistry, conversation: &Conversation| {
+            let client = registry
+                .profile_for(conversation)
+                .unwrap()
+                .client(fake.clone())
+                .with_middleware(keys.clone());
+            fake.respond(FakeResponse::text("Hi"));
+            block_on(client.complete(conversation.request().unwrap())).unwrap();
+        };
+
+        let mut unbound = Conversation::new(RequestDefaults {
+            model: Some(Model::Claude3Haiku),
+            max_tokens: Some(16),
+            ..Default::default()
+        });
+        unbound.push_user("Hello");
+        let mut work = registry.get("work").unwrap().new_conversation("work");
+        work.push_user("Hello");
+
+        // Unbound conversations follow the active profile.
+        send(&registry, &unbound);
+        registry.set_active("work").unwrap();
+        send(&registry, &unbound);
+        assert!(registry.set_active("missing").is_err());
+
+        // Bound conversations keep their profile and its defaults.
+        registry.set_active("personal").unwrap();
+        send(&registry, &work);
+
+        assert_eq!(
+            *keys.0.lock().unwrap(),
+            ["personal-key", "work-key", "work-key"]
+        );
+        assert_eq!(fake.requests()[2]["model"], Model::Claude3Opus.id());
+    }
+}
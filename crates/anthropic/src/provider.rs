@@ -0,0 +1,120 @@
+use crate::{
+    stream_completion_reader, ClientOptions, ContentBlock, Model, Request, RequestMessage,
+    ResponseEvent, TextDelta, ANTHROPIC_API_URL,
+};
+use anyhow::Result;
+use futures::{future, stream::BoxStream, StreamExt};
+use http::HttpClient;
+use language_model::{LanguageModel, LanguageModelRequest};
+use std::time::Duration;
+
+/// The settings a language model provider backed by this crate is configured
+/// with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProviderSettings {
+    /// The model used for requests that don't target a specific Anthropic
+    /// model.
+    pub model: Model,
+    pub api_url: String,
+    pub low_speed_timeout: Option<Duration>,
+}
+
+impl Default for ProviderSettings {
+    fn default() -> Self {
+        Self {
+            model: Model::default(),
+            api_url: ANTHROPIC_API_URL.to_string(),
+            low_speed_timeout: None,
+        }
+    }
+}
+
+impl ProviderSettings {
+    pub fn client_options(&self) -> ClientOptions {
+        ClientOptions {
+            low_speed_timeout: self.low_speed_timeout,
+            ..Default::default()
+        }
+    }
+}
+
+/// Converts a request made through Zed's language model abstraction into a
+/// request for the Messages API. `default_model` is used unless the request
+/// targets a specific Anthropic model.
+pub fn to_anthropic_request(mut request: LanguageModelRequest, default_model: &Model) -> Request {
+    request.preprocess_anthropic();
+
+    let model = match request.model {
+        LanguageModel::Anthropic(model) => model,
+        _ => default_model.clone(),
+    };
+
+    let mut system = String::new();
+    let mut messages = Vec::with_capacity(request.messages.len());
+    for message in request.messages {
+        match message.role {
+            language_model::Role::User => messages.push(RequestMessage::user(message.content)),
+            language_model::Role::Assistant => {
+                messages.push(RequestMessage::assistant(message.content))
+            }
+            language_model::Role::System => {
+                if !system.is_empty() {
+                    system.push_str("\n\n");
+                }
+                system.push_str(&message.content);
+            }
+        }
+    }
+
+    Request {
+        model,
+        messages,
+        stream: true,
+        system: system.into(),
+        stop_sequences: request.stop,
+        ..Default::default()
+    }
+}
+
+/// Maps a stream of response events to the stream of text chunks expected by
+/// language model providers.
+pub fn to_text_stream(
+    events: BoxStream<'static, Result<ResponseEvent>>,
+) -> BoxStream<'static, Result<String>> {
+    events
+        .filter_map(|event| {
+            future::ready(match event {
+                Ok(ResponseEvent::ContentBlockStart {
+                    content_block: ContentBlock::Text { text, .. },
+                    ..
+                }) => Some(Ok(text)),
+                Ok(ResponseEvent::ContentBlockDelta {
+                    delta: TextDelta::TextDelta { text },
+                    ..
+                }) => Some(Ok(text.into_string())),
+                Ok(ResponseEvent::Error { error }) => Some(Err(error.into())),
+                Ok(_) => None,
+                Err(error) => Some(Err(error)),
+            })
+        })
+        .boxed()
+}
+
+/// Streams the completion of a language model request, as text.
+pub async fn stream_language_model_completion(
+    client: &dyn HttpClient,
+    api_key: &str,
+    settings: &ProviderSettings,
+    request: LanguageModelRequest,
+) -> Result<BoxStream<'static, Result<String>>> {
+    let request = to_anthropic_request(request, &settings.model);
+    let reader = stream_completion_reader(
+        client,
+        &settings.api_url,
+        api_key,
+        request,
+        &settings.client_options(),
+    )
+    .await?;
+    Ok(to_text_stream(reader.into_stream()))
+}
@@ -0,0 +1,67 @@
+use anyhow::{Context as _, Result};
+use http::Uri;
+
+/// A proxy configured explicitly rather than through the environment.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct ProxyConfig {
+    /// The URL of the proxy, e.g. `http://proxy:3128` or
+    /// `socks5://proxy:1080`. URLs without a scheme are HTTP proxies.
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// The hosts that are reached directly, in the format of `NO_PROXY`.
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// Returns the proxy that requests to `url` should go through, if any.
+    pub fn proxy_for(&self, url: &Uri) -> Result<Option<Uri>> {
+        if http::bypasses_proxy(self.no_proxy.iter().map(String::as_str), url) {
+            return Ok(None);
+        }
+        parse_proxy_url(&self.url).map(Some)
+    }
+}
+
+impl std::fmt::Debug for ProxyConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProxyConfig")
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("no_proxy", &self.no_proxy)
+            .finish_non_exhaustive()
+    }
+}
+
+fn parse_proxy_url(proxy: &str) -> Result<Uri> {
+    let uri = if proxy.contains("://") {
+        proxy.parse()
+    } else {
+        format!("http://{proxy}").parse()
+    };
+    uri.with_context(|| format!("invalid proxy URL {proxy:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_proxy_config() {
+        let proxy = ProxyConfig {
+            url: "socks5://proxy:1080".into(),
+            no_proxy: vec!["Internal.Example.com".into()],
+            ..Default::default()
+        };
+        let proxy_for = |url: &str| {
+            proxy
+                .proxy_for(&url.parse().unwrap())
+                .unwrap()
+                .map(|proxy| proxy.to_string())
+        };
+        assert_eq!(
+            proxy_for("https://api.anthropic.com").as_deref(),
+            Some("socks5://proxy:1080/")
+        );
+        assert_eq!(proxy_for("https://llm.internal.example.com"), None);
+    }
+}
@@ -0,0 +1,130 @@
+use chrono::{DateTime, Utc};
+use isahc::http::HeaderMap;
+use std::time::Duration;
+
+/// The state of one of the limits reported in the `anthropic-ratelimit-*`
+/// response headers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    pub limit: Option<u64>,
+    pub remaining: Option<u64>,
+    /// When the limit will be fully replenished.
+    pub reset: Option<DateTime<Utc>>,
+}
+
+impl RateLimit {
+    fn from_headers(headers: &HeaderMap, name: &str) -> Self {
+        let header = |suffix: &str| {
+            headers
+                .get(format!("anthropic-ratelimit-{name}-{suffix}"))
+                .and_then(|value| value.to_str().ok())
+        };
+        Self {
+            limit: header("limit").and_then(|value| value.parse().ok()),
+            remaining: header("remaining").and_then(|value| value.parse().ok()),
+            reset: header("reset")
+                .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+                .map(|reset| reset.with_timezone(&Utc)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.limit.is_none() && self.remaining.is_none() && self.reset.is_none()
+    }
+}
+
+/// The rate limits of the account, as reported with a response.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    pub requests: RateLimit,
+    /// The most restrictive of the input and output token limits.
+    pub tokens: RateLimit,
+    pub input_tokens: RateLimit,
+    pub output_tokens: RateLimit,
+}
+
+impl RateLimitInfo {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        Self {
+            requests: RateLimit::from_headers(headers, "requests"),
+            tokens: RateLimit::from_headers(headers, "tokens"),
+            input_tokens: RateLimit::from_headers(headers, "input-tokens"),
+            output_tokens: RateLimit::from_headers(headers, "output-tokens"),
+        }
+    }
+
+    /// Whether the response carried no rate limit headers, e.g. because it
+    /// came from a proxy.
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+            && self.tokens.is_empty()
+            && self.input_tokens.is_empty()
+            && self.output_tokens.is_empty()
+    }
+}
+
+/// Returns how long the `Retry-After` header in `headers` asks clients to
+/// wait before retrying, given either as a number of seconds or as a date.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get("retry-after")?.to_str().ok()?.trim();
+    parse_retry_after(value, Utc::now())
+}
+
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        date.with_timezone(&Utc)
+            .signed_duration_since(now)
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rate_limit_headers() {
+        let mut headers = HeaderMap::new();
+        for (name, value) in [
+            ("anthropic-ratelimit-requests-limit", "50"),
+            ("anthropic-ratelimit-requests-remaining", "49"),
+            ("anthropic-ratelimit-requests-reset", "2024-07-01T12:00:30Z"),
+            ("anthropic-ratelimit-tokens-remaining", "not a number"),
+        ] {
+            headers.insert(name, value.parse().unwrap());
+        }
+
+        let info = RateLimitInfo::from_headers(&headers);
+        assert_eq!(info.requests.limit, Some(50));
+        assert_eq!(info.requests.remaining, Some(49));
+        assert_eq!(
+            info.requests.reset.unwrap().to_rfc3339(),
+            "2024-07-01T12:00:30+00:00"
+        );
+        assert!(info.tokens.is_empty());
+        assert!(!info.is_empty());
+        assert!(RateLimitInfo::from_headers(&HeaderMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2024-07-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(parse_retry_after("30", now), Some(Duration::from_secs(30)));
+        assert_eq!(
+            parse_retry_after("Mon, 01 Jul 2024 12:00:45 GMT", now),
+            Some(Duration::from_secs(45))
+        );
+        assert_eq!(
+            parse_retry_after("Mon, 01 Jul 2024 11:59:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{api_request_builder, body, ClientOptions, EventReader};
+use anyhow::Result;
+use futures::stream::{self, BoxStream, StreamExt};
+use http::{AsyncBody, HttpClient, Method, Request as HttpRequest, Response, StatusCode};
+use serde_json::value::RawValue;
+
+/// A response to a request sent with [`send_raw`], whatever its status.
+pub struct RawResponse {
+    pub(crate) response: Response<AsyncBody>,
+}
+
+impl RawResponse {
+    pub fn status(&self) -> StatusCode {
+        self.response.status()
+    }
+
+    pub fn headers(&self) -> &isahc::http::HeaderMap {
+        self.response.headers()
+    }
+
+    /// Reads the whole body, decompressing it if needed.
+    pub async fn bytes(mut self) -> Result<Vec<u8>> {
+        body::read_body(&mut self.response).await
+    }
+
+    pub async fn json(self) -> Result<serde_json::Value> {
+        Ok(serde_json::from_slice(&self.bytes().await?)?)
+    }
+
+    /// Reads the body as server-sent events, yielding the JSON payload of each
+    /// `data:` line.
+    pub fn into_data_stream(self) -> BoxStream<'static, Result<Box<RawValue>>> {
+        let reader = EventReader::new(self.response.into_body());
+        stream::unfold(reader, |mut reader| async move {
+            let data = reader.next_data().await?;
+            let payload = data.and_then(|data| {
+                let json = std::str::from_utf8(data)?.to_string();
+                Ok(RawValue::from_string(json)?)
+            });
+            Some((payload, reader))
+        })
+        .boxed()
+    }
+
+    pub fn into_response(self) -> Response<AsyncBody> {
+        self.response
+    }
+}
+
+/// Sends a request with an arbitrary JSON body to `path`, such as
+/// `/v1/messages`, for using endpoints or parameters that don't have a typed
+/// counterpart in this crate yet.
+///
+/// The request is authenticated and configured like every other request, but
+/// unsuccessful statuses are returned rather than turned into errors. See
+/// [`crate::AnthropicClient::send_raw`] for sending it with retries.
+pub async fn send_raw(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    method: Method,
+    path: &str,
+    body: Option<serde_json::Value>,
+    options: &ClientOptions,
+) -> Result<RawResponse> {
+    let request = raw_request(api_url, api_key, method, path, body, &[], options)?;
+    let response = client.send(request).await?;
+    Ok(RawResponse { response })
+}
+
+/// Builds the request sent by [`send_raw`], with `betas` enabled on top of
+/// the ones of `options`.
+pub(crate) fn raw_request(
+    api_url: &str,
+    api_key: &str,
+    method: Method,
+    path: &str,
+    body: Option<serde_json::Value>,
+    betas: &[String],
+    options: &ClientOptions,
+) -> Result<HttpRequest<AsyncBody>> {
+    let separator = if path.starts_with('/') { "" } else { "/" };
+    let uri = format!("{api_url}{separator}{path}");
+    let mut request_builder = api_request_builder(method, &uri, api_key, betas, options);
+    let body = match body {
+        Some(body) => {
+            let body = body::encode_body(body, 0, options.request_compression)?;
+            if let Some(content_encoding) = body.content_encoding {
+                request_builder =
+                    request_builder.header("Content-Encoding", content_encoding.as_str());
+            }
+            body.body
+        }
+        None => AsyncBody::empty(),
+    };
+    Ok(request_builder.body(body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ApiErrorKind, FakeAnthropic, FakeResponse, Middleware, RetryPolicy};
+    use futures::executor::block_on;
+    use http::{AsyncBody, Method, Request as HttpRequest};
+    use serde_json::json;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    #[derive(Default)]
+    struct BetaRecorder(Mutex<Vec<Option<String>>>);
+
+    impl Middleware for Arc<BetaRecorder> {
+        fn on_http_request(&self, request: &mut HttpRequest<AsyncBody>) {
+            let beta = request
+                .headers()
+                .get("Anthropic-Beta")
+                .and_then(|beta| beta.to_str().ok())
+                .map(str::to_string);
+            self.0.lock().unwrap().push(beta);
+        }
+    }
+
+    #[test]
+    fn test_client_send_raw() {
+        let fake = FakeAnthropic::new();
+        let recorder = Arc::new(BetaRecorder::default());
+        let client = fake.client().with_middleware(recorder.clone());
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+        };
+
+        fake.respond(FakeResponse::error(
+            529,
+            ApiErrorKind::Overloaded,
+            "Overloaded",
+        ));
+        fake.respond(FakeResponse::Json(json!({"id": "file_01"})));
+        let response = block_on(client.send_raw(
+            Method::GET,
+            "/v1/files/file_01",
+            None,
+            &["files-api-2025-04-14".to_string()],
+            &policy,
+        ))
+        .unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(block_on(response.json()).unwrap(), json!({"id": "file_01"}));
+        assert_eq!(fake.paths(), ["/v1/files/file_01", "/v1/files/file_01"]);
+        let betas = recorder.0.lock().unwrap().clone();
+        assert_eq!(betas.len(), 2);
+        assert!(betas
+            .iter()
+            .all(|beta| beta.as_deref().unwrap().contains("files-api-2025-04-14")));
+
+        fake.respond(FakeResponse::error(
+            404,
+            ApiErrorKind::NotFound,
+            "File not found",
+        ));
+        let response =
+            block_on(client.send_raw(Method::GET, "/v1/files/file_02", None, &[], &policy))
+                .unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+        assert_eq!(fake.paths().len(), 3);
+    }
+}
@@ -0,0 +1,330 @@
+use crate::{
+    body::EncodedBody, complete_with_body, connect_stream_with_body, deadline::Deadline, ApiError,
+    ClientOptions, EventReader, Message, Request,
+};
+use anyhow::Result;
+use http::{AsyncBody, HttpClient};
+use smol::Timer;
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+/// How failed requests are retried by [`with_retry`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of times a request is sent, including the first.
+    pub max_attempts: u32,
+    /// The delay before the first retry, doubled for every further one.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The recommended policy for requests failing because the API is
+    /// overloaded (see [`ApiError::is_overloaded`]), which backs off longer
+    /// than the default since overload typically lasts seconds to minutes.
+    pub fn overloaded() -> Self {
+        Self {
+            max_attempts: 6,
+            initial_backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+
+    /// Returns the delay before retrying after the given failed attempt,
+    /// counting from 1. Delays are randomized between half and all of the
+    /// exponential backoff, so that clients failing together don't retry in
+    /// lockstep.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+        let jitter = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        backoff.mul_f64(0.5 + jitter / 2.)
+    }
+}
+
+/// Whether a request that failed with `error` could succeed if sent again:
+/// rate limiting, overloaded or failing servers, and network errors.
+pub fn is_retryable(error: &anyhow::Error) -> bool {
+    if let Some(error) = error.downcast_ref::<ApiError>() {
+        error.is_retryable()
+    } else if let Some(error) = error.downcast_ref::<http::Error>() {
+        error.is_network() || error.is_timeout()
+    } else {
+        error.downcast_ref::<std::io::Error>().is_some()
+    }
+}
+
+/// Whether `error`, or the last attempt of a [`RetryError`], is an
+/// [`ApiError`] caused by the API being overloaded, so that it can be
+/// reported as such instead of as a generic failure.
+pub fn is_overloaded(error: &anyhow::Error) -> bool {
+    if let Some(error) = error.downcast_ref::<RetryError>() {
+        return is_overloaded(error.last_error());
+    }
+    error
+        .downcast_ref::<ApiError>()
+        .map_or(false, ApiError::is_overloaded)
+}
+
+/// A failed attempt recorded in a [`RetryError`].
+#[derive(Debug)]
+pub struct FailedAttempt {
+    pub error: anyhow::Error,
+    /// How long was waited before the next attempt, if there was one.
+    pub backoff: Option<Duration>,
+}
+
+/// The error returned when every attempt of a retried request failed.
+#[derive(Debug)]
+pub struct RetryError {
+    pub attempts: Vec<FailedAttempt>,
+}
+
+impl RetryError {
+    /// Returns the error of the final attempt.
+    pub fn last_error(&self) -> &anyhow::Error {
+        &self
+            .attempts
+            .last()
+            .expect("at least one attempt failed")
+            .error
+    }
+}
+
+impl fmt::Display for RetryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "request failed after {} attempts: {}",
+            self.attempts.len(),
+            self.last_error()
+        )
+    }
+}
+
+impl std::error::Error for RetryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.last_error().as_ref())
+    }
+}
+
+/// Calls `send` until it succeeds, fails with an error that isn't
+/// [`is_retryable`], or `policy.max_attempts` is reached, waiting with
+/// exponential backoff between attempts. Errors with an
+/// [`ApiError::retry_after`] are retried after the delay the server asked
+/// for instead.
+///
+/// An error that isn't retryable is returned as is. Otherwise, the error is a
+/// [`RetryError`] listing every attempt.
+pub async fn with_retry<T, F, Fut>(policy: &RetryPolicy, mut send: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempts = Vec::new();
+    loop {
+        let error = match send().await {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        if !is_retryable(&error) {
+            return Err(error);
+        }
+        let attempt = attempts.len() as u32 + 1;
+        if attempt >= policy.max_attempts {
+            attempts.push(FailedAttempt {
+                error,
+                backoff: None,
+            });
+            return Err(RetryError { attempts }.into());
+        }
+        let backoff = error
+            .downcast_ref::<ApiError>()
+            .and_then(|error| error.retry_after)
+            .unwrap_or_else(|| policy.backoff(attempt));
+        attempts.push(FailedAttempt {
+            error,
+            backoff: Some(backoff),
+        });
+        Timer::after(backoff).await;
+    }
+}
+
+/// Like [`crate::stream_completion_reader`], retrying failures to start the
+/// stream with `policy`. Errors that occur once events are being streamed
+/// aren't retried.
+///
+/// The request is encoded once, and the encoded body is sent again on every
+/// attempt. Its [`Request::timeout`] spans all attempts rather than restarting
+/// with each one.
+pub async fn stream_completion_with_retry(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: Request,
+    options: &ClientOptions,
+    policy: &RetryPolicy,
+) -> Result<EventReader<AsyncBody>> {
+    let body = EncodedBody::new(&request, options.request_compression)?;
+    let deadline = Deadline::after(request.timeout);
+    let (request, body) = (&request, &body);
+    with_retry(policy, || async move {
+        let (reader, _, _) = connect_stream_with_body(
+            client,
+            api_url,
+            api_key,
+            request,
+            body.to_request_body(),
+            deadline,
+            options,
+        )
+        .await?;
+        Ok(reader)
+    })
+    .await
+}
+
+/// Like [`crate::complete`], retrying failures with `policy`.
+///
+/// The request is encoded once, and the encoded body is sent again on every
+/// attempt. Its [`Request::timeout`] spans all attempts rather than restarting
+/// with each one.
+pub async fn complete_with_retry(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    mut request: Request,
+    options: &ClientOptions,
+    policy: &RetryPolicy,
+) -> Result<Message> {
+    request.stream = false;
+    let body = EncodedBody::new(&request, options.request_compression)?;
+    let deadline = Deadline::after(request.timeout);
+    let (request, body) = (&request, &body);
+    with_retry(policy, || async move {
+        let (message, _) = complete_with_body(
+            client,
+            api_url,
+            api_key,
+            request,
+            body.to_request_body(),
+            deadline,
+            options,
+        )
+        .await?;
+        Ok(message)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ApiErrorKind, FakeAnthropic, FakeResponse, Model, RequestBuilder, FAKE_API_URL};
+    use anyhow::anyhow;
+    use futures::executor::block_on;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_with_retry() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+        };
+        let overloaded = || ApiError::from_response(529, "Overloaded");
+
+        let calls = Cell::new(0);
+        let result = block_on(with_retry(&policy, || {
+            calls.set(calls.get() + 1);
+            let result = if calls.get() < 3 {
+                Err(overloaded().into())
+            } else {
+                Ok(calls.get())
+            };
+            async move { result }
+        }));
+        assert_eq!(result.unwrap(), 3);
+
+        let error = block_on(with_retry(&policy, || async {
+            Err::<(), _>(overloaded().into())
+        }))
+        .unwrap_err();
+        assert!(is_overloaded(&error));
+        let error = error.downcast::<RetryError>().unwrap();
+        assert_eq!(error.attempts.len(), 3);
+        assert!(error.attempts[2].backoff.is_none());
+
+        let rate_limited = || {
+            ApiError::from_response(429, "Rate limited")
+                .with_retry_after(Some(Duration::from_millis(5)))
+        };
+        let error = block_on(with_retry(&policy, || async {
+            Err::<(), _>(rate_limited().into())
+        }))
+        .unwrap_err();
+        let error = error.downcast::<RetryError>().unwrap();
+        assert_eq!(error.attempts[0].backoff, Some(Duration::from_millis(5)));
+
+        calls.set(0);
+        let error = block_on(with_retry(&policy, || {
+            calls.set(calls.get() + 1);
+            async { Err::<(), _>(anyhow!("invalid")) }
+        }))
+        .unwrap_err();
+        assert_eq!(error.to_string(), "invalid");
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_complete_with_retry() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+        };
+        let fake = FakeAnthropic::new();
+        fake.respond(FakeResponse::error(
+            529,
+            ApiErrorKind::Overloaded,
+            "Overloaded",
+        ));
+        fake.respond(FakeResponse::text("Hello"));
+        let request = RequestBuilder::new(Model::Claude3Haiku)
+            .user("Hi")
+            .build()
+            .unwrap();
+
+        let message = block_on(complete_with_retry(
+            fake.as_ref(),
+            FAKE_API_URL,
+            "fake-api-key",
+            request,
+            &ClientOptions::default(),
+            &policy,
+        ))
+        .unwrap();
+        assert_eq!(message.content.len(), 1);
+        let requests = fake.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0], requests[1]);
+        assert_eq!(requests[1]["stream"], false);
+    }
+}
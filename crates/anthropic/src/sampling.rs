@@ -0,0 +1,219 @@
+use crate::{
+    stream_completion_reader, ClientOptions, Model, Request, RequestMessage, ResponseEvent, Role,
+    StopReason, Thinking, Usage,
+};
+use anyhow::{anyhow, Context as _, Result};
+use futures::{future, StreamExt};
+use http::HttpClient;
+
+/// How far apart the temperatures of the completions sent by [`sample_n`] are
+/// spread, centered on the request's temperature.
+const TEMPERATURE_SPREAD: f32 = 0.4;
+const DEFAULT_TEMPERATURE: f32 = 1.0;
+
+/// One completion produced by [`sample_n`].
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub text: String,
+    pub temperature: f32,
+    pub stop_reason: Option<StopReason>,
+    pub usage: Usage,
+}
+
+/// Sends `k` copies of `request` in parallel and waits for all of them.
+///
+/// To get more varied completions, each copy is sent with a slightly
+/// different temperature, spread around the request's own temperature (or the
+/// API's default when it isn't set). Results are returned in the order of
+/// increasing temperature, one per completion, so that a failed completion
+/// doesn't discard the others.
+///
+/// Extended thinking doesn't support changing the temperature, so requests
+/// with [`Thinking::Enabled`] are all sent as is.
+pub async fn sample_n(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: Request,
+    k: usize,
+    options: &ClientOptions,
+) -> Vec<Result<Sample>> {
+    let base_temperature = request.temperature.unwrap_or(DEFAULT_TEMPERATURE);
+    let spread = !matches!(request.thinking, Some(Thinking::Enabled { .. }));
+    let samples = (0..k).map(|ix| {
+        let mut request = request.clone();
+        let temperature = if spread {
+            let temperature = sample_temperature(base_temperature, ix, k);
+            request.temperature = Some(temperature);
+            temperature
+        } else {
+            base_temperature
+        };
+        request.stream = true;
+        async move {
+            let reader =
+                stream_completion_reader(client, api_url, api_key, request, options).await?;
+            collect_sample(reader.into_stream(), temperature).await
+        }
+    });
+    future::join_all(samples).await
+}
+
+fn sample_temperature(base: f32, ix: usize, k: usize) -> f32 {
+    if k <= 1 {
+        return base;
+    }
+    let offset = TEMPERATURE_SPREAD * (ix as f32 / (k - 1) as f32 - 0.5);
+    (base + offset).clamp(0., 1.)
+}
+
+async fn collect_sample(
+    mut events: impl futures::Stream<Item = Result<ResponseEvent>> + Unpin,
+    temperature: f32,
+) -> Result<Sample> {
+    let mut sample = Sample {
+        text: String::new(),
+        temperature,
+        stop_reason: None,
+        usage: Usage::default(),
+    };
+    while let Some(event) = events.next().await {
+        let event = event?;
+        if let Some(text) = event.text() {
+            sample.text.push_str(text);
+        }
+        match event {
+            ResponseEvent::MessageStart { message } => {
+                if let Some(usage) = message.usage {
+                    sample.usage = usage;
+                }
+            }
+            ResponseEvent::MessageDelta { delta, usage } => {
+                if delta.stop_reason.is_some() {
+                    sample.stop_reason = delta.stop_reason;
+                }
+                if usage.output_tokens.is_some() {
+                    sample.usage.output_tokens = usage.output_tokens;
+                }
+            }
+            ResponseEvent::Error { error } => return Err(error.into()),
+            _ => {}
+        }
+    }
+    Ok(sample)
+}
+
+/// Scores each sample with `score` and returns them from best to worst.
+pub fn rank_samples(
+    samples: Vec<Sample>,
+    mut score: impl FnMut(&Sample) -> f64,
+) -> Vec<(Sample, f64)> {
+    let mut ranked = samples
+        .into_iter()
+        .map(|sample| {
+            let score = score(&sample);
+            (sample, score)
+        })
+        .collect::<Vec<_>>();
+    ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    ranked
+}
+
+/// Asks `judge_model`, typically a cheaper model than the one that produced
+/// the samples, which of `samples` best accomplishes `task`. Returns the index
+/// of the chosen sample.
+pub async fn judge_samples(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    judge_model: Model,
+    task: &str,
+    samples: &[Sample],
+    options: &ClientOptions,
+) -> Result<usize> {
+    if samples.len() <= 1 {
+        return if samples.is_empty() {
+            Err(anyhow!("no samples to judge"))
+        } else {
+            Ok(0)
+        };
+    }
+
+    let mut prompt = format!("<task>\n{task}\n</task>\n\n");
+    for (ix, sample) in samples.iter().enumerate() {
+        prompt.push_str(&format!(
+            "<candidate index=\"{ix}\">\n{}\n</candidate>\n\n",
+            sample.text
+        ));
+    }
+    prompt.push_str(
+        "Which candidate best accomplishes the task? Reply with its index only, and nothing else.",
+    );
+
+    let request = Request {
+        model: judge_model,
+        messages: vec![RequestMessage {
+            role: Role::User,
+            content: prompt.into(),
+        }],
+        stream: true,
+        max_tokens: 16,
+        temperature: Some(0.),
+        ..Default::default()
+    };
+    let reader = stream_completion_reader(client, api_url, api_key, request, options).await?;
+    let verdict = collect_sample(reader.into_stream(), 0.).await?;
+    let index = verdict
+        .text
+        .trim()
+        .parse::<usize>()
+        .with_context(|| format!("judge replied with an invalid index: {:?}", verdict.text))?;
+    if index >= samples.len() {
+        return Err(anyhow!("judge chose a nonexistent candidate {index}"));
+    }
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FakeAnthropic, FakeResponse, FAKE_API_URL, MIN_THINKING_BUDGET};
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_sample_n() {
+        let fake = FakeAnthropic::new();
+        let sample = |request: Request| {
+            fake.respond(FakeResponse::text("a"));
+            fake.respond(FakeResponse::text("b"));
+            let samples = block_on(sample_n(
+                fake.as_ref(),
+                FAKE_API_URL,
+                "key",
+                request,
+                2,
+                &ClientOptions::default(),
+            ));
+            assert!(samples.iter().all(Result::is_ok));
+        };
+
+        let request = Request::new(Model::default(), ["Hi"]);
+        sample(request.clone());
+        let mut temperatures = fake
+            .requests()
+            .iter()
+            .map(|request| request["temperature"].as_f64().unwrap() as f32)
+            .collect::<Vec<_>>();
+        temperatures.sort_by(f32::total_cmp);
+        assert_eq!(temperatures, [0.8, 1.0]);
+
+        let mut request = request;
+        request.thinking = Some(Thinking::Enabled {
+            budget_tokens: MIN_THINKING_BUDGET,
+        });
+        sample(request);
+        assert!(fake.requests()[2..]
+            .iter()
+            .all(|request| request.get("temperature").is_none()));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    body, connect_stream_with_body, deadline::Deadline, ApiError, ClientOptions, EventReader,
+    Request, RequestMessage, ShrinkPolicy,
+};
+use anyhow::Result;
+use http::{AsyncBody, HttpClient};
+use std::sync::Arc;
+
+/// Like [`crate::stream_completion_reader`], but when the API rejects
+/// `request` as too large, shrinks it with [`Request::shrink`] and retries
+/// until it's accepted or can't be shrunk any further. The request's
+/// [`Request::timeout`] spans all attempts.
+///
+/// Returns the messages that were dropped along with the response, so that
+/// they can be reported to the user.
+pub async fn stream_completion_shrinking(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: Request,
+    policy: ShrinkPolicy,
+    options: &ClientOptions,
+) -> Result<(EventReader<AsyncBody>, Vec<RequestMessage>)> {
+    let deadline = Deadline::after(request.timeout);
+    let mut request = Arc::new(request);
+    let mut dropped = Vec::new();
+    loop {
+        let body = body::encode_request_body(request.clone(), options.request_compression)?;
+        match connect_stream_with_body(client, api_url, api_key, &request, body, deadline, options)
+            .await
+        {
+            Ok((reader, _, _)) => return Ok((reader, dropped)),
+            Err(error)
+                if error
+                    .downcast_ref::<ApiError>()
+                    .map_or(false, ApiError::is_request_too_large) =>
+            {
+                // The request is only cloned if a large body is still being
+                // encoded from it.
+                let shrunk = Arc::make_mut(&mut request).shrink(&policy);
+                if shrunk.is_empty() {
+                    return Err(error);
+                }
+                dropped.extend(shrunk);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ApiErrorKind, FakeAnthropic, FakeResponse, Model, Role, FAKE_API_URL};
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_stream_completion_shrinking() {
+        let fake = FakeAnthropic::new();
+        let too_large = || {
+            FakeResponse::error(
+                413,
+                ApiErrorKind::RequestTooLarge,
+                "Request exceeds the maximum allowed number of bytes",
+            )
+        };
+        fake.respond(too_large());
+        fake.respond(too_large());
+        fake.respond(FakeResponse::text("Hello"));
+
+        let messages = ["u1", "a1", "u2", "a2", "u3", "a3", "u4"]
+            .into_iter()
+            .enumerate()
+            .map(|(ix, content)| {
+                let role = if ix % 2 == 0 {
+                    Role::User
+                } else {
+                    Role::Assistant
+                };
+                RequestMessage::new(role, content)
+            });
+        let request = Request::new(Model::Claude3Haiku, messages);
+        let (_, dropped) = block_on(stream_completion_shrinking(
+            fake.as_ref(),
+            FAKE_API_URL,
+            "key",
+            request,
+            ShrinkPolicy::default(),
+            &ClientOptions::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            dropped,
+            [
+                RequestMessage::user("u1"),
+                RequestMessage::assistant("a1"),
+                RequestMessage::user("u2"),
+                RequestMessage::assistant("a2"),
+            ]
+        );
+        let sent_messages = fake
+            .requests()
+            .iter()
+            .map(|request| request["messages"].as_array().unwrap().len())
+            .collect::<Vec<_>>();
+        assert_eq!(sent_messages, [7, 5, 3]);
+    }
+}
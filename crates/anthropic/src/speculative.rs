@@ -0,0 +1,240 @@
+use crate::{stream_completion_reader, ClientOptions, Request};
+use anyhow::Result;
+use futures::{
+    future,
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use http::HttpClient;
+use std::sync::Arc;
+
+/// What happens to a draft that finishes before the main model responds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DraftPolicy {
+    /// Always replace the draft with the main model's response.
+    #[default]
+    Discard,
+    /// Keep a draft that completed successfully before the main model
+    /// produced any text, and cancel the main request.
+    KeepFinished,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpeculativeEvent {
+    /// Text from the draft model.
+    Draft(String),
+    /// The main model started responding. Any draft text shown so far should
+    /// be replaced with this text.
+    Replace(String),
+    /// Further text from the main model.
+    Text(String),
+}
+
+enum Source {
+    Draft(Option<Result<String>>),
+    Main(Option<Result<String>>),
+}
+
+/// Streams `request` while concurrently streaming `draft_request`, typically
+/// the same prompt sent to a faster model, so that text can be shown as soon
+/// as possible. Once the main model starts responding, the draft is replaced
+/// by its output.
+///
+/// Errors from the draft request are ignored, since the main request is
+/// still expected to produce a response.
+pub fn stream_speculative(
+    client: Arc<dyn HttpClient>,
+    api_url: &str,
+    api_key: &str,
+    draft_request: Request,
+    request: Request,
+    policy: DraftPolicy,
+    options: ClientOptions,
+) -> BoxStream<'static, Result<SpeculativeEvent>> {
+    let draft = completion_text(
+        client.clone(),
+        api_url.to_string(),
+        api_key.to_string(),
+        draft_request,
+        options.clone(),
+    )
+    .map(Some)
+    .chain(stream::once(future::ready(None)))
+    .map(Source::Draft);
+    let main = completion_text(
+        client,
+        api_url.to_string(),
+        api_key.to_string(),
+        request,
+        options,
+    )
+    .map(Some)
+    .chain(stream::once(future::ready(None)))
+    .map(Source::Main);
+
+    let state = SpeculationState {
+        sources: stream::select(draft, main).boxed(),
+        switched: false,
+        draft_failed: false,
+    };
+    stream::unfold(state, move |mut state| async move {
+        let event = state.next(policy).await?;
+        Some((event, state))
+    })
+    .boxed()
+}
+
+struct SpeculationState {
+    sources: BoxStream<'static, Source>,
+    switched: bool,
+    draft_failed: bool,
+}
+
+impl SpeculationState {
+    async fn next(&mut self, policy: DraftPolicy) -> Option<Result<SpeculativeEvent>> {
+        loop {
+            match self.sources.next().await? {
+                Source::Draft(Some(Ok(text))) if !self.switched => {
+                    return Some(Ok(SpeculativeEvent::Draft(text)));
+                }
+                Source::Draft(Some(Err(_))) => self.draft_failed = true,
+                Source::Draft(None) => {
+                    if !self.switched && !self.draft_failed && policy == DraftPolicy::KeepFinished {
+                        // Dropping the sources cancels the main request.
+                        return None;
+                    }
+                }
+                Source::Draft(Some(Ok(_))) => {}
+                Source::Main(Some(Ok(text))) => {
+                    if self.switched {
+                        return Some(Ok(SpeculativeEvent::Text(text)));
+                    } else {
+                        self.switched = true;
+                        return Some(Ok(SpeculativeEvent::Replace(text)));
+                    }
+                }
+                Source::Main(Some(Err(error))) => return Some(Err(error)),
+                Source::Main(None) => {
+                    if self.switched {
+                        return None;
+                    } else {
+                        // The main model responded without any text, which
+                        // still supersedes the draft.
+                        self.switched = true;
+                        return Some(Ok(SpeculativeEvent::Replace(String::new())));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn completion_text(
+    client: Arc<dyn HttpClient>,
+    api_url: String,
+    api_key: String,
+    request: Request,
+    options: ClientOptions,
+) -> BoxStream<'static, Result<String>> {
+    stream::once(async move {
+        stream_completion_reader(client.as_ref(), &api_url, &api_key, request, &options).await
+    })
+    .flat_map(|reader| match reader {
+        Ok(reader) => reader
+            .into_stream()
+            .filter_map(|event| {
+                future::ready(match event {
+                    // Text blocks start out empty, which isn't worth an event.
+                    Ok(event) => event
+                        .text()
+                        .filter(|text| !text.is_empty())
+                        .map(|text| Ok(text.to_string())),
+                    Err(error) => Some(Err(error)),
+                })
+            })
+            .boxed(),
+        Err(error) => stream::once(future::ready(Err(error))).boxed(),
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ApiErrorKind, FakeAnthropic, FakeResponse, Model, FAKE_API_URL};
+    use futures::executor::block_on;
+    use std::time::{Duration, Instant};
+
+    const DRAFT_MODEL: Model = Model::Claude3Haiku;
+    const MAIN_MODEL: Model = Model::Claude3Opus;
+
+    fn speculate(fake: &Arc<FakeAnthropic>, policy: DraftPolicy) -> Vec<SpeculativeEvent> {
+        let request = |model| Request::new(model, ["Explain lifetimes"]);
+        let events = stream_speculative(
+            fake.clone(),
+            FAKE_API_URL,
+            "key",
+            request(DRAFT_MODEL),
+            request(MAIN_MODEL),
+            policy,
+            ClientOptions::default(),
+        );
+        block_on(events.collect::<Vec<_>>())
+            .into_iter()
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_main_replaces_draft() {
+        let fake = FakeAnthropic::new();
+        fake.respond_to(&DRAFT_MODEL, FakeResponse::text("Quick draft"));
+        fake.respond_to(
+            &MAIN_MODEL,
+            FakeResponse::text("The main answer is longer").delayed(Duration::from_millis(50)),
+        );
+        assert_eq!(
+            speculate(&fake, DraftPolicy::Discard),
+            [
+                SpeculativeEvent::Draft("Quick draft".into()),
+                SpeculativeEvent::Replace("The main answer ".into()),
+                SpeculativeEvent::Text("is longer".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keep_finished_draft() {
+        let fake = FakeAnthropic::new();
+        fake.respond_to(&DRAFT_MODEL, FakeResponse::text("Quick draft"));
+        fake.respond_to(
+            &MAIN_MODEL,
+            FakeResponse::text("Too late").delayed(Duration::from_secs(30)),
+        );
+        let start = Instant::now();
+        assert_eq!(
+            speculate(&fake, DraftPolicy::KeepFinished),
+            [SpeculativeEvent::Draft("Quick draft".into())]
+        );
+        // The main request was cancelled rather than waited for.
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(fake.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_draft_error_is_ignored() {
+        let fake = FakeAnthropic::new();
+        fake.respond_to(
+            &DRAFT_MODEL,
+            FakeResponse::error(500, ApiErrorKind::Api, "Internal server error"),
+        );
+        fake.respond_to(
+            &MAIN_MODEL,
+            FakeResponse::text("The main answer").delayed(Duration::from_millis(20)),
+        );
+        assert_eq!(
+            speculate(&fake, DraftPolicy::KeepFinished),
+            [SpeculativeEvent::Replace("The main answer".into())]
+        );
+    }
+}
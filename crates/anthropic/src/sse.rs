@@ -0,0 +1,435 @@
+use crate::{
+    deadline::{within, Deadline},
+    BufferPool, PooledBuffer, ResponseEvent, ResponseEventRef,
+};
+use anyhow::{anyhow, Result};
+use futures::{
+    stream::{self, BoxStream},
+    AsyncRead, AsyncReadExt, StreamExt,
+};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::io;
+
+/// The default size of the buffer used to read from the response body. Large
+/// enough to hold a typical burst of text deltas in a single read.
+pub const DEFAULT_READ_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Reads server-sent events from a response body.
+///
+/// Events are parsed as specified for `text/event-stream`: an event is made of
+/// the lines up to the next blank line, which may end with LF, CRLF or CR, the
+/// `data:` lines of an event are joined with newlines, and comment lines
+/// starting with a colon are ignored. Events may be split across any number
+/// of reads.
+///
+/// Lines are read into a single buffer that is reused for the lifetime of the
+/// stream, and event payloads are deserialized straight from the buffer their
+/// data is collected in, so the only allocations are the ones made for the
+/// decoded events themselves. All buffers are taken from a [`BufferPool`].
+pub struct EventReader<R> {
+    reader: R,
+    buffer: PooledBuffer,
+    position: usize,
+    filled: usize,
+    /// Whether the last line ended with a CR, in which case an LF starting
+    /// the next read completes the line ending.
+    skip_lf: bool,
+    line: PooledBuffer,
+    data: PooledBuffer,
+    /// A copy of `data` to fall back to serde_json with, since simd-json
+    /// modifies the data it parses.
+    #[cfg(feature = "simd-json")]
+    original: PooledBuffer,
+    event_type: String,
+    request_id: Option<String>,
+    deadline: Option<Deadline>,
+}
+
+impl<R: AsyncRead + Unpin> EventReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_pool(reader, BufferPool::global())
+    }
+
+    pub fn with_pool(reader: R, pool: &BufferPool) -> Self {
+        Self::with_buffer_size(reader, pool, DEFAULT_READ_BUFFER_SIZE)
+    }
+
+    /// Creates a reader that reads up to `buffer_size` bytes from the body at a
+    /// time. Larger buffers need fewer reads for large events, such as big tool
+    /// inputs, at the cost of memory.
+    pub fn with_buffer_size(reader: R, pool: &BufferPool, buffer_size: usize) -> Self {
+        let mut buffer = pool.take();
+        buffer.resize(buffer_size.max(1), 0);
+        Self {
+            reader,
+            buffer,
+            position: 0,
+            filled: 0,
+            skip_lf: false,
+            line: pool.take(),
+            data: pool.take(),
+            #[cfg(feature = "simd-json")]
+            original: pool.take(),
+            event_type: String::new(),
+            request_id: None,
+            deadline: None,
+        }
+    }
+
+    /// Sets the `request-id` header of the response being read.
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    /// Fails reads with [`crate::DeadlineExceeded`] once `deadline` has
+    /// passed, so that the request's timeout also bounds reading its events.
+    pub(crate) fn with_deadline(mut self, deadline: Option<Deadline>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// The `request-id` header of the response being read, which identifies
+    /// the request in the API's logs.
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// The `event:` field of the event read last, which is empty if it had
+    /// none.
+    pub fn event_type(&self) -> &str {
+        &self.event_type
+    }
+
+    /// Returns the data of the next event, or `None` once the body has been
+    /// exhausted.
+    pub async fn next_data(&mut self) -> Option<Result<&[u8]>> {
+        Some(self.read_data().await?.map(|_| &self.data[..]))
+    }
+
+    pub async fn next_event(&mut self) -> Option<Result<ResponseEvent>> {
+        match self.read_data().await? {
+            Ok(()) => Some(self.parse_data()),
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    /// Returns the next event, borrowing its text from the read buffer. The
+    /// event must be dropped before reading the next one.
+    pub async fn next_event_ref(&mut self) -> Option<Result<ResponseEventRef<'_>>> {
+        match self.read_data().await? {
+            Ok(()) => Some(self.parse_data()),
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    /// Returns the next event along with its original JSON, for logging,
+    /// replaying, or accessing fields the typed event doesn't model.
+    pub async fn next_event_with_raw(&mut self) -> Option<Result<RawResponseEvent>> {
+        if let Err(error) = self.read_data().await? {
+            return Some(Err(error));
+        }
+        // The payload is copied before being parsed, as parsing may modify it.
+        let json = match std::str::from_utf8(&self.data) {
+            Ok(json) => json.to_string(),
+            Err(error) => return Some(Err(anyhow!(error))),
+        };
+        let result = self.parse_data().and_then(|event| {
+            Ok(RawResponseEvent {
+                event,
+                raw: RawValue::from_string(json)?,
+            })
+        });
+        Some(result)
+    }
+
+    /// Parses the data of the event read last.
+    fn parse_data<'a, T: Deserialize<'a>>(&'a mut self) -> Result<T> {
+        #[cfg(feature = "simd-json")]
+        {
+            parse_with_fallback(&mut self.data, &mut self.original, |data| {
+                Ok(simd_json::serde::from_slice(data)?)
+            })
+        }
+        #[cfg(not(feature = "simd-json"))]
+        {
+            Ok(serde_json::from_slice(&self.data)?)
+        }
+    }
+
+    /// Reads the next event into `self.data`.
+    async fn read_data(&mut self) -> Option<Result<()>> {
+        self.data.clear();
+        self.event_type.clear();
+        let mut has_data = false;
+        let deadline = self.deadline;
+        loop {
+            self.line.clear();
+            match within(deadline, async { Ok(self.read_line().await?) }).await {
+                Ok(true) => {}
+                // A final event without a trailing blank line is still
+                // dispatched, in case a proxy cut it off.
+                Ok(false) if has_data => break,
+                Ok(false) => return None,
+                Err(error) => return Some(Err(error)),
+            }
+
+            let line = trim_line_ending(&self.line);
+            if line.is_empty() {
+                if has_data {
+                    break;
+                }
+                self.event_type.clear();
+                continue;
+            }
+            let (field, value) = match line.iter().position(|byte| *byte == b':') {
+                Some(colon_ix) => {
+                    let value = &line[colon_ix + 1..];
+                    (&line[..colon_ix], value.strip_prefix(b" ").unwrap_or(value))
+                }
+                None => (line, &[][..]),
+            };
+            match field {
+                b"data" => {
+                    if has_data {
+                        self.data.push(b'\n');
+                    }
+                    self.data.extend_from_slice(value);
+                    has_data = true;
+                }
+                b"event" => {
+                    self.event_type.clear();
+                    self.event_type.push_str(&String::from_utf8_lossy(value));
+                }
+                // Comments, which have an empty field name, and the `id` and
+                // `retry` fields, which are only used by browsers to reconnect.
+                _ => {}
+            }
+        }
+        Some(Ok(()))
+    }
+
+    /// Appends the next line, including its terminator, to `self.line`.
+    /// Returns `false` once the body is exhausted.
+    async fn read_line(&mut self) -> io::Result<bool> {
+        loop {
+            if self.skip_lf && self.position < self.filled {
+                if self.buffer[self.position] == b'\n' {
+                    self.position += 1;
+                }
+                self.skip_lf = false;
+            }
+
+            let available = &self.buffer[self.position..self.filled];
+            if let Some(terminator_ix) = available
+                .iter()
+                .position(|byte| *byte == b'\n' || *byte == b'\r')
+            {
+                self.skip_lf = available[terminator_ix] == b'\r';
+                self.line.extend_from_slice(&available[..=terminator_ix]);
+                self.position += terminator_ix + 1;
+                return Ok(true);
+            }
+            self.line.extend_from_slice(available);
+
+            self.position = 0;
+            self.filled = self.reader.read(&mut self.buffer).await?;
+            if self.filled == 0 {
+                return Ok(!self.line.is_empty());
+            }
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin + Send + 'static> EventReader<R> {
+    pub fn into_stream(self) -> BoxStream<'static, Result<ResponseEvent>> {
+        stream::unfold(self, |mut reader| async move {
+            let event = reader.next_event().await?;
+            Some((event, reader))
+        })
+        .boxed()
+    }
+
+    pub fn into_stream_with_raw(self) -> BoxStream<'static, Result<RawResponseEvent>> {
+        stream::unfold(self, |mut reader| async move {
+            let event = reader.next_event_with_raw().await?;
+            Some((event, reader))
+        })
+        .boxed()
+    }
+}
+
+/// A [`ResponseEvent`] paired with the JSON it was decoded from.
+#[derive(Debug)]
+pub struct RawResponseEvent {
+    pub event: ResponseEvent,
+    pub raw: Box<RawValue>,
+}
+
+/// Decodes an event payload with `parse`, such as simd-json, which is
+/// noticeably cheaper than serde_json when hundreds of small events arrive per
+/// second, falling back to serde_json for payloads it rejects.
+///
+/// simd-json unescapes the payload in place, leaving its contents unspecified
+/// when it fails, so the payload is copied into `original` first.
+#[cfg(any(test, feature = "simd-json"))]
+fn parse_with_fallback<'a, T: Deserialize<'a>>(
+    data: &'a mut [u8],
+    original: &'a mut Vec<u8>,
+    parse: impl FnOnce(&'a mut [u8]) -> Result<T>,
+) -> Result<T> {
+    original.clear();
+    original.extend_from_slice(data);
+    let original: &'a [u8] = original;
+    parse(data).or_else(|_| Ok(serde_json::from_slice(original)?))
+}
+
+fn trim_line_ending(mut line: &[u8]) -> &[u8] {
+    while let [rest @ .., b'\n' | b'\r'] = line {
+        line = rest;
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+        time::Duration,
+    };
+
+    /// Returns at most `chunk_size` bytes per read.
+    struct ChunkedReader {
+        data: Vec<u8>,
+        position: usize,
+        chunk_size: usize,
+    }
+
+    impl AsyncRead for ChunkedReader {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let len = self
+                .chunk_size
+                .min(buf.len())
+                .min(self.data.len() - self.position);
+            let position = self.position;
+            buf[..len].copy_from_slice(&self.data[position..position + len]);
+            self.position += len;
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    /// Never returns any data, like a server that stopped sending events.
+    struct StalledReader;
+
+    impl AsyncRead for StalledReader {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            _: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let body = concat!(
+            ": keep-alive\r\n\r\n",
+            "event: message_start\r\n",
+            "data: {\"a\":\r\n",
+            "data:1}\r\n\r\n",
+            "id: 2\revent: ping\rdata: {}\r\r",
+            "data: [1,\n",
+            "data: 2]",
+        );
+        for chunk_size in [1, 3, 1024] {
+            let reader = ChunkedReader {
+                data: body.as_bytes().to_vec(),
+                position: 0,
+                chunk_size,
+            };
+            let mut reader = EventReader::with_buffer_size(reader, BufferPool::global(), 4);
+            let mut events = Vec::new();
+            while let Some(data) = block_on(reader.next_data()) {
+                let data = String::from_utf8(data.unwrap().to_vec()).unwrap();
+                events.push((reader.event_type().to_string(), data));
+            }
+            assert_eq!(
+                events,
+                [
+                    ("message_start".to_string(), "{\"a\":\n1}".to_string()),
+                    ("ping".to_string(), "{}".to_string()),
+                    (String::new(), "[1,\n2]".to_string()),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_error_event() {
+        let body = concat!(
+            "event: ping\n",
+            "data: {\"type\": \"ping\"}\n\n",
+            "event: error\n",
+            "data: {\"type\": \"error\", \"error\": {\"type\": \"overloaded_error\", \"message\": \"Overloaded\"}}\n\n",
+        );
+        let mut reader = EventReader::new(body.as_bytes());
+        assert!(matches!(
+            block_on(reader.next_event()),
+            Some(Ok(ResponseEvent::Ping {}))
+        ));
+        let Some(Ok(ResponseEvent::Error { error })) = block_on(reader.next_event()) else {
+            panic!("expected an error event");
+        };
+        assert_eq!(error.kind, crate::ApiErrorKind::Overloaded);
+        assert_eq!(error.status, 529);
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_deadline() {
+        let timeout = Duration::from_millis(10);
+        let body = "event: ping\ndata: {\"type\": \"ping\"}\n\n".as_bytes();
+        let mut reader = EventReader::new(body.chain(StalledReader))
+            .with_deadline(Deadline::after(Some(timeout)));
+        assert!(matches!(
+            block_on(reader.next_event()),
+            Some(Ok(ResponseEvent::Ping {}))
+        ));
+        let Some(Err(error)) = block_on(reader.next_event()) else {
+            panic!("expected the deadline to be exceeded");
+        };
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&crate::DeadlineExceeded { timeout })
+        );
+    }
+
+    #[test]
+    fn test_parse_with_fallback() {
+        let mut data = br#"{"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "a\"b"}}"#.to_vec();
+        let mut original = Vec::new();
+        // Fails after clobbering the payload, like simd-json can.
+        let event: ResponseEventRef = parse_with_fallback(&mut data, &mut original, |data| {
+            data.fill(b' ');
+            Err(anyhow!("rejected"))
+        })
+        .unwrap();
+        let ResponseEventRef::ContentBlockDelta {
+            delta: crate::TextDeltaRef::TextDelta { text },
+            ..
+        } = event
+        else {
+            panic!("expected a text delta");
+        };
+        assert_eq!(text, "a\"b");
+    }
+}
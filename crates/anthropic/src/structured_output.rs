@@ -0,0 +1,32 @@
+use crate::{complete, ClientOptions, Request};
+use anyhow::{Context as _, Result};
+use http::HttpClient;
+use serde::de::DeserializeOwned;
+
+/// Sends `request` without streaming, forcing the model to respond with JSON
+/// matching `schema`, and returns that JSON.
+pub async fn complete_structured(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    mut request: Request,
+    schema: serde_json::Value,
+    options: &ClientOptions,
+) -> Result<serde_json::Value> {
+    request.set_structured_output(schema);
+    let message = complete(client, api_url, api_key, request, options).await?;
+    message.structured_output().cloned()
+}
+
+/// Like [`complete_structured`], but deserializes the JSON as `T`.
+pub async fn complete_structured_as<T: DeserializeOwned>(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    request: Request,
+    schema: serde_json::Value,
+    options: &ClientOptions,
+) -> Result<T> {
+    let output = complete_structured(client, api_url, api_key, request, schema, options).await?;
+    serde_json::from_value(output).context("structured output doesn't match the expected type")
+}
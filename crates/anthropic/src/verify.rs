@@ -0,0 +1,144 @@
+use crate::{send_raw, ClientOptions};
+use http::{HttpClient, Method, StatusCode};
+
+/// The outcome of [`verify_key`].
+#[derive(Debug)]
+pub enum KeyStatus {
+    Valid,
+    /// The API rejected the key.
+    InvalidKey,
+    /// The key was authenticated, but isn't allowed to use the API, e.g.
+    /// because its workspace was disabled.
+    PermissionDenied,
+    /// The API couldn't be reached.
+    Network(anyhow::Error),
+    /// The API responded with a status that says nothing about the key, such
+    /// as a rate limit or overload.
+    Unexpected {
+        status: StatusCode,
+        body: String,
+    },
+}
+
+impl KeyStatus {
+    pub fn is_valid(&self) -> bool {
+        matches!(self, Self::Valid)
+    }
+}
+
+/// Checks that `api_key` is accepted by the API by listing a single model,
+/// which unlike sending a message isn't billed, so that a key can be
+/// validated as soon as it's entered.
+pub async fn verify_key(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &str,
+    options: &ClientOptions,
+) -> KeyStatus {
+    let response = match send_raw(
+        client,
+        api_url,
+        api_key,
+        Method::GET,
+        "/v1/models?limit=1",
+        None,
+        options,
+    )
+    .await
+    {
+        Ok(response) => response,
+        Err(error) => return KeyStatus::Network(error),
+    };
+
+    let status = response.status();
+    if status.is_success() {
+        return KeyStatus::Valid;
+    }
+    match status.as_u16() {
+        401 => KeyStatus::InvalidKey,
+        403 => KeyStatus::PermissionDenied,
+        _ => {
+            let body = match response.bytes().await {
+                Ok(body) => String::from_utf8_lossy(&body).into_owned(),
+                Err(error) => return KeyStatus::Network(error),
+            };
+            KeyStatus::Unexpected { status, body }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ApiErrorKind, FakeAnthropic, FakeResponse, FAKE_API_URL};
+    use futures::{executor::block_on, future::BoxFuture};
+    use http::{AsyncBody, Error, Request as HttpRequest, Response as HttpResponse, Uri};
+    use serde_json::json;
+    use std::io;
+
+    struct Unreachable;
+
+    impl HttpClient for Unreachable {
+        fn send(
+            &self,
+            _: HttpRequest<AsyncBody>,
+        ) -> BoxFuture<'static, Result<HttpResponse<AsyncBody>, Error>> {
+            Box::pin(async {
+                Err(io::Error::new(io::ErrorKind::ConnectionRefused, "connection refused").into())
+            })
+        }
+
+        fn proxy(&self) -> Option<&Uri> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_verify_key() {
+        let fake = FakeAnthropic::new();
+        let verify = |client: &dyn HttpClient| {
+            block_on(verify_key(
+                client,
+                FAKE_API_URL,
+                "key",
+                &ClientOptions::default(),
+            ))
+        };
+
+        fake.respond(FakeResponse::Json(json!({
+            "data": [],
+            "has_more": false,
+            "first_id": null,
+            "last_id": null,
+        })));
+        assert!(verify(fake.as_ref()).is_valid());
+        assert_eq!(fake.paths(), ["/v1/models?limit=1"]);
+
+        fake.respond(FakeResponse::error(
+            401,
+            ApiErrorKind::Authentication,
+            "invalid x-api-key",
+        ));
+        assert!(matches!(verify(fake.as_ref()), KeyStatus::InvalidKey));
+
+        fake.respond(FakeResponse::error(
+            403,
+            ApiErrorKind::Permission,
+            "workspace is disabled",
+        ));
+        assert!(matches!(verify(fake.as_ref()), KeyStatus::PermissionDenied));
+
+        fake.respond(FakeResponse::error(
+            529,
+            ApiErrorKind::Overloaded,
+            "Overloaded",
+        ));
+        let KeyStatus::Unexpected { status, body } = verify(fake.as_ref()) else {
+            panic!("expected an overloaded API to say nothing about the key");
+        };
+        assert_eq!(status.as_u16(), 529);
+        assert!(body.contains("Overloaded"));
+
+        assert!(matches!(verify(&Unreachable), KeyStatus::Network(_)));
+    }
+}
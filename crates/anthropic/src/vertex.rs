@@ -0,0 +1,208 @@
+use crate::{
+    body, send_json_request, ApiError, BufferPool, ClientOptions, EventReader, Message, Model,
+    Request, ResponseEvent, ACCEPT_ENCODING, DEFAULT_READ_BUFFER_SIZE,
+};
+use anyhow::{anyhow, Result};
+use futures::stream::BoxStream;
+use http::{AsyncBody, HttpClient, Method, Request as HttpRequest};
+use isahc::config::Configurable;
+use serde::Serialize;
+use std::borrow::Cow;
+
+/// The API version sent in the body of requests to Vertex AI, which doesn't
+/// accept the `Anthropic-Version` header.
+pub const VERTEX_ANTHROPIC_VERSION: &str = "vertex-2023-10-16";
+
+/// Where and as whom requests are sent when using Claude through Google
+/// Cloud's Vertex AI rather than the Anthropic API.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexConfig {
+    pub project_id: String,
+    /// The region the model is deployed in, e.g. `us-east5`, or `global`.
+    pub region: String,
+    /// An OAuth 2 access token of the Google Cloud account, e.g. from
+    /// `gcloud auth print-access-token`. Tokens expire, so callers have to
+    /// refresh it.
+    pub access_token: String,
+}
+
+impl VertexConfig {
+    fn endpoint(&self, model: &Model, method: &str) -> String {
+        let host = if self.region == "global" {
+            "aiplatform.googleapis.com".to_string()
+        } else {
+            format!("{}-aiplatform.googleapis.com", self.region)
+        };
+        format!(
+            "https://{host}/v1/projects/{}/locations/{}/publishers/anthropic/models/{}:{method}",
+            self.project_id,
+            self.region,
+            vertex_model_id(model)
+        )
+    }
+}
+
+/// Returns the id of `model` on Vertex AI, which separates the version with
+/// an `@`, e.g. `claude-3-opus@20240229`. Custom model names are passed
+/// through unless they end with a `-YYYYMMDD` version.
+pub fn vertex_model_id(model: &Model) -> Cow<'_, str> {
+    match model {
+        Model::Claude3_5Sonnet => "claude-3-5-sonnet@20240620".into(),
+        Model::Claude3Opus => "claude-3-opus@20240229".into(),
+        Model::Claude3Sonnet => "claude-3-sonnet@20240229".into(),
+        Model::Claude3Haiku => "claude-3-haiku@20240307".into(),
+        Model::Claude3_5Haiku => "claude-3-5-haiku@20241022".into(),
+        Model::Claude3_7Sonnet => "claude-3-7-sonnet@20250219".into(),
+        Model::ClaudeSonnet4 => "claude-sonnet-4@20250514".into(),
+        Model::ClaudeOpus4 => "claude-opus-4@20250514".into(),
+        Model::ClaudeOpus4_1 => "claude-opus-4-1@20250805".into(),
+        Model::Custom { name, .. } => match name.rsplit_once('-') {
+            Some((base, version))
+                if !name.contains('@')
+                    && version.len() == 8
+                    && version.bytes().all(|byte| byte.is_ascii_digit()) =>
+            {
+                format!("{base}@{version}").into()
+            }
+            _ => name.as_str().into(),
+        },
+    }
+}
+
+/// A request body for Vertex AI, which takes the model from the URL and the
+/// API version from the body.
+#[derive(Serialize)]
+struct VertexBody {
+    anthropic_version: &'static str,
+    #[serde(flatten)]
+    request: serde_json::Map<String, serde_json::Value>,
+}
+
+fn vertex_body(request: &Request) -> Result<VertexBody> {
+    let serde_json::Value::Object(mut request) = serde_json::to_value(request)? else {
+        return Err(anyhow!("request didn't serialize to an object"));
+    };
+    request.remove("model");
+    Ok(VertexBody {
+        anthropic_version: VERTEX_ANTHROPIC_VERSION,
+        request,
+    })
+}
+
+fn vertex_request_builder(
+    uri: &str,
+    config: &VertexConfig,
+    betas: &[String],
+    options: &ClientOptions,
+) -> isahc::http::request::Builder {
+    let mut request_builder = HttpRequest::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("Authorization", format!("Bearer {}", config.access_token))
+        .header("Content-Type", "application/json");
+    if !betas.is_empty() {
+        request_builder = request_builder.header("Anthropic-Beta", betas.join(","));
+    }
+    if let Some(low_speed_timeout) = options.low_speed_timeout {
+        request_builder = request_builder.low_speed_timeout(100, low_speed_timeout);
+    }
+    request_builder
+}
+
+fn encode_vertex_request(
+    mut request_builder: isahc::http::request::Builder,
+    request: &Request,
+    options: &ClientOptions,
+) -> Result<HttpRequest<AsyncBody>> {
+    let size_hint = request.system.encoded_len()
+        + request
+            .messages
+            .iter()
+            .map(|message| message.content.encoded_len())
+            .sum::<usize>();
+    let body = body::encode_body(
+        vertex_body(request)?,
+        size_hint,
+        options.request_compression,
+    )?;
+    if let Some(content_encoding) = body.content_encoding {
+        request_builder = request_builder.header("Content-Encoding", content_encoding.as_str());
+    }
+    Ok(request_builder.body(body.body)?)
+}
+
+/// Like [`crate::stream_completion`], but sends the request to Vertex AI.
+pub async fn stream_completion_vertex(
+    client: &dyn HttpClient,
+    config: &VertexConfig,
+    mut request: Request,
+    options: &ClientOptions,
+) -> Result<BoxStream<'static, Result<ResponseEvent>>> {
+    request.stream = true;
+    let uri = config.endpoint(&request.model, "streamRawPredict");
+    let request_builder = vertex_request_builder(&uri, config, &request.betas, options);
+    let http_request = encode_vertex_request(request_builder, &request, options)?;
+    let mut response = client.send(http_request).await?;
+    if response.status().is_success() {
+        let reader = EventReader::with_buffer_size(
+            response.into_body(),
+            BufferPool::global(),
+            options.read_buffer_size.unwrap_or(DEFAULT_READ_BUFFER_SIZE),
+        );
+        Ok(reader.into_stream())
+    } else {
+        let body = body::read_body(&mut response).await?;
+        let body = String::from_utf8_lossy(&body);
+        Err(ApiError::from_response(response.status().as_u16(), &body).into())
+    }
+}
+
+/// Like [`crate::complete`], but sends the request to Vertex AI.
+pub async fn complete_vertex(
+    client: &dyn HttpClient,
+    config: &VertexConfig,
+    mut request: Request,
+    options: &ClientOptions,
+) -> Result<Message> {
+    request.stream = false;
+    let uri = config.endpoint(&request.model, "rawPredict");
+    let request_builder = vertex_request_builder(&uri, config, &request.betas, options)
+        .header("Accept-Encoding", ACCEPT_ENCODING);
+    let http_request = encode_vertex_request(request_builder, &request, options)?;
+    send_json_request(client, http_request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vertex_model_id() {
+        assert_eq!(
+            vertex_model_id(&Model::Claude3Haiku),
+            "claude-3-haiku@20240307"
+        );
+        let custom = |name: &str| Model::Custom {
+            name: name.into(),
+            max_tokens: None,
+            max_output_tokens: None,
+        };
+        assert_eq!(
+            vertex_model_id(&custom("claude-3-5-haiku-20241022")),
+            "claude-3-5-haiku@20241022"
+        );
+        assert_eq!(
+            vertex_model_id(&custom("claude-3-5-haiku@20241022")),
+            "claude-3-5-haiku@20241022"
+        );
+    }
+
+    #[test]
+    fn test_vertex_body() {
+        let request = Request::new(Model::Claude3Opus, ["Hi"]);
+        let body = serde_json::to_value(vertex_body(&request).unwrap()).unwrap();
+        assert_eq!(body["anthropic_version"], VERTEX_ANTHROPIC_VERSION);
+        assert_eq!(body["messages"][0]["content"], "Hi");
+        assert!(body.get("model").is_none());
+    }
+}
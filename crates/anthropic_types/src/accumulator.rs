@@ -0,0 +1,197 @@
+use crate::{
+    ContentBlock, Message, ResponseEvent, ResponseMessage, StopReason, TextDelta, ToolUseCollector,
+    Usage,
+};
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeMap;
+
+/// Builds the complete message of a streamed response from its events.
+#[derive(Debug, Default)]
+pub struct Accumulator {
+    message: Option<ResponseMessage>,
+    content: BTreeMap<u32, ContentBlock>,
+    tool_uses: ToolUseCollector,
+    stop_reason: Option<StopReason>,
+    stop_sequence: Option<String>,
+    usage: Usage,
+    complete: bool,
+}
+
+impl Accumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merges `event` into the message.
+    pub fn push_event(&mut self, event: &ResponseEvent) -> Result<()> {
+        match event {
+            ResponseEvent::MessageStart { message } => {
+                if let Some(usage) = &message.usage {
+                    self.usage.update(usage);
+                }
+                self.message = Some(message.clone());
+            }
+            ResponseEvent::ContentBlockStart {
+                index,
+                content_block,
+            } => {
+                self.content.insert(*index, content_block.clone());
+                self.tool_uses.push_event(event)?;
+            }
+            ResponseEvent::ContentBlockDelta { index, delta } => {
+                let block = self
+                    .content
+                    .get_mut(index)
+                    .ok_or_else(|| anyhow!("delta for content block {index} before its start"))?;
+                match (block, delta) {
+                    (ContentBlock::Text { text, .. }, TextDelta::TextDelta { text: delta }) => {
+                        text.push_str(delta);
+                    }
+                    (
+                        ContentBlock::Text { citations, .. },
+                        TextDelta::CitationsDelta { citation },
+                    ) => {
+                        citations.push(citation.clone());
+                    }
+                    (ContentBlock::ToolUse { .. }, TextDelta::InputJsonDelta { .. }) => {
+                        self.tool_uses.push_event(event)?;
+                    }
+                    (
+                        ContentBlock::Thinking { thinking, .. },
+                        TextDelta::ThinkingDelta { thinking: delta },
+                    ) => {
+                        thinking.push_str(delta);
+                    }
+                    (
+                        ContentBlock::Thinking { signature, .. },
+                        TextDelta::SignatureDelta { signature: delta },
+                    ) => {
+                        signature.push_str(delta);
+                    }
+                    (_, delta) => bail!("unexpected {delta:?} for content block {index}"),
+                }
+            }
+            ResponseEvent::ContentBlockStop { index } => {
+                if let Some(tool_use) = self.tool_uses.push_event(event)? {
+                    if let Some(ContentBlock::ToolUse { input, .. }) = self.content.get_mut(index) {
+                        *input = tool_use.input;
+                    }
+                }
+            }
+            ResponseEvent::MessageDelta { delta, usage } => {
+                if let Some(stop_reason) = &delta.stop_reason {
+                    self.stop_reason = Some(stop_reason.clone());
+                }
+                if let Some(stop_sequence) = &delta.stop_sequence {
+                    self.stop_sequence = Some(stop_sequence.clone());
+                }
+                self.usage.update(usage);
+            }
+            ResponseEvent::MessageStop {} => self.complete = true,
+            ResponseEvent::Ping {} => {}
+            ResponseEvent::Error { error } => return Err(error.clone().into()),
+        }
+        Ok(())
+    }
+
+    /// Returns the content blocks received so far. The input of a tool call
+    /// is only filled in once its block is complete, see
+    /// [`Accumulator::partial_tool_input`] for its progress.
+    pub fn content(&self) -> impl Iterator<Item = &ContentBlock> {
+        self.content.values()
+    }
+
+    /// Returns the input streamed so far of the tool call in the content
+    /// block at `index`, parsed as well as possible, e.g. to show it while
+    /// it's being generated.
+    pub fn partial_tool_input(&self, index: u32) -> Option<serde_json::Value> {
+        self.tool_uses.partial_value(index)
+    }
+
+    /// Returns the text received so far.
+    pub fn text(&self) -> String {
+        self.content()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn stop_reason(&self) -> Option<&StopReason> {
+        self.stop_reason.as_ref()
+    }
+
+    /// Returns the token usage reported so far. The output token count is
+    /// only final once the message is complete.
+    pub fn usage(&self) -> &Usage {
+        &self.usage
+    }
+
+    /// Returns whether the message's `message_stop` event was received.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Returns the message, which is incomplete if the stream ended early.
+    pub fn finish(self) -> Result<Message> {
+        let Some(start) = self.message else {
+            bail!("the response stream ended before the message started");
+        };
+        let mut message = Message::try_from(start)?;
+        message.content = self.content.into_values().collect();
+        message.stop_reason = self.stop_reason;
+        message.stop_sequence = self.stop_sequence;
+        message.usage = self.usage;
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accumulate_message() {
+        let events = [
+            r#"{"type":"message_start","message":{"id":"msg_01","type":"message","role":"assistant","content":[],"model":"claude-3-haiku-20240307","stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":25,"output_tokens":1}}}"#,
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"ping"}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Let me "}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"check."}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_01","name":"read_file","input":{}}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"path\": "}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"\"a.rs\"}"}}"#,
+            r#"{"type":"content_block_stop","index":1}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":42}}"#,
+            r#"{"type":"message_stop"}"#,
+        ];
+        let mut accumulator = Accumulator::new();
+        for event in events {
+            let event: ResponseEvent = serde_json::from_str(event).unwrap();
+            accumulator.push_event(&event).unwrap();
+        }
+        assert!(accumulator.is_complete());
+        assert_eq!(accumulator.text(), "Let me check.");
+
+        let message = accumulator.finish().unwrap();
+        assert_eq!(message.id, "msg_01");
+        assert_eq!(message.stop_reason, Some(StopReason::ToolUse));
+        assert_eq!(message.usage.input_tokens, Some(25));
+        assert_eq!(message.usage.output_tokens, Some(42));
+        assert!(matches!(
+            &message.content[1],
+            ContentBlock::ToolUse { input, .. } if input == &serde_json::json!({"path": "a.rs"})
+        ));
+    }
+
+    #[test]
+    fn test_delta_before_start() {
+        let event: ResponseEvent = serde_json::from_str(
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#,
+        )
+        .unwrap();
+        assert!(Accumulator::new().push_event(&event).is_err());
+    }
+}
@@ -0,0 +1,824 @@
+mod accumulator;
+mod api_error;
+mod base64_data;
+#[cfg(feature = "bpe-tokenizer")]
+mod bpe;
+mod broadcast;
+mod capabilities;
+mod citations;
+mod computer_use;
+mod content;
+mod conversation;
+mod delta_text;
+mod edit_stream;
+mod encoded_prefix;
+mod estimate;
+mod message;
+mod normalize;
+mod output_cap;
+mod partial_json;
+mod pricing;
+mod request_builder;
+mod shrink;
+mod stop_sequence;
+mod structured_output;
+mod system_prompt;
+mod text_accumulator;
+mod text_sink;
+mod text_stream;
+mod token_annotations;
+mod tools;
+mod usage;
+mod xml_tags;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, convert::TryFrom, sync::Arc, time::Duration};
+use strum::EnumIter;
+
+pub use accumulator::*;
+pub use api_error::*;
+pub use base64_data::*;
+#[cfg(feature = "bpe-tokenizer")]
+pub use bpe::*;
+pub use broadcast::*;
+pub use citations::*;
+pub use computer_use::*;
+pub use content::*;
+pub use conversation::*;
+pub use delta_text::*;
+pub use edit_stream::*;
+pub use encoded_prefix::*;
+pub use estimate::*;
+pub use message::*;
+pub use normalize::*;
+pub use output_cap::*;
+pub use partial_json::*;
+pub use pricing::*;
+pub use request_builder::*;
+pub use shrink::*;
+pub use stop_sequence::*;
+pub use structured_output::*;
+pub use system_prompt::*;
+pub use text_accumulator::*;
+pub use text_sink::*;
+pub use text_stream::*;
+pub use token_annotations::*;
+pub use tools::*;
+pub use usage::*;
+pub use xml_tags::*;
+
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, EnumIter)]
+pub enum Model {
+    #[default]
+    #[serde(alias = "claude-3-5-sonnet", rename = "claude-3-5-sonnet-20240620")]
+    Claude3_5Sonnet,
+    #[serde(alias = "claude-3-opus", rename = "claude-3-opus-20240229")]
+    Claude3Opus,
+    #[serde(alias = "claude-3-sonnet", rename = "claude-3-sonnet-20240229")]
+    Claude3Sonnet,
+    #[serde(alias = "claude-3-haiku", rename = "claude-3-haiku-20240307")]
+    Claude3Haiku,
+    #[serde(
+        alias = "claude-3-5-haiku",
+        alias = "claude-3-5-haiku-latest",
+        rename = "claude-3-5-haiku-20241022"
+    )]
+    Claude3_5Haiku,
+    #[serde(
+        alias = "claude-3-7-sonnet",
+        alias = "claude-3-7-sonnet-latest",
+        rename = "claude-3-7-sonnet-20250219"
+    )]
+    Claude3_7Sonnet,
+    #[serde(alias = "claude-sonnet-4", rename = "claude-sonnet-4-20250514")]
+    ClaudeSonnet4,
+    #[serde(alias = "claude-opus-4", rename = "claude-opus-4-20250514")]
+    ClaudeOpus4,
+    #[serde(alias = "claude-opus-4-1", rename = "claude-opus-4-1-20250805")]
+    ClaudeOpus4_1,
+    #[serde(rename = "custom")]
+    Custom {
+        name: String,
+        #[serde(default)]
+        max_tokens: Option<usize>,
+        /// The maximum number of tokens the model can generate in a
+        /// response. Defaults to [`DEFAULT_MAX_TOKENS`].
+        #[serde(default)]
+        max_output_tokens: Option<u32>,
+    },
+}
+
+impl Model {
+    pub fn from_id(id: &str) -> Result<Self> {
+        if id.starts_with("claude-3-5-sonnet") {
+            Ok(Self::Claude3_5Sonnet)
+        } else if id.starts_with("claude-3-opus") {
+            Ok(Self::Claude3Opus)
+        } else if id.starts_with("claude-3-sonnet") {
+            Ok(Self::Claude3Sonnet)
+        } else if id.starts_with("claude-3-haiku") {
+            Ok(Self::Claude3Haiku)
+        } else if id.starts_with("claude-3-5-haiku") {
+            Ok(Self::Claude3_5Haiku)
+        } else if id.starts_with("claude-3-7-sonnet") {
+            Ok(Self::Claude3_7Sonnet)
+        } else if id.starts_with("claude-sonnet-4") {
+            Ok(Self::ClaudeSonnet4)
+        } else if id.starts_with("claude-opus-4-1") {
+            Ok(Self::ClaudeOpus4_1)
+        } else if id.starts_with("claude-opus-4") {
+            Ok(Self::ClaudeOpus4)
+        } else {
+            Ok(Self::Custom {
+                name: id.to_string(),
+                max_tokens: None,
+                max_output_tokens: None,
+            })
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        match self {
+            Model::Claude3_5Sonnet => "claude-3-5-sonnet-20240620",
+            Model::Claude3Opus => "claude-3-opus-20240229",
+            Model::Claude3Sonnet => "claude-3-sonnet-20240229",
+            Model::Claude3Haiku => "claude-3-haiku-20240307",
+            Model::Claude3_5Haiku => "claude-3-5-haiku-20241022",
+            Model::Claude3_7Sonnet => "claude-3-7-sonnet-20250219",
+            Model::ClaudeSonnet4 => "claude-sonnet-4-20250514",
+            Model::ClaudeOpus4 => "claude-opus-4-20250514",
+            Model::ClaudeOpus4_1 => "claude-opus-4-1-20250805",
+            Model::Custom { name, .. } => name,
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        match self {
+            Self::Claude3_5Sonnet => "Claude 3.5 Sonnet",
+            Self::Claude3Opus => "Claude 3 Opus",
+            Self::Claude3Sonnet => "Claude 3 Sonnet",
+            Self::Claude3Haiku => "Claude 3 Haiku",
+            Self::Claude3_5Haiku => "Claude 3.5 Haiku",
+            Self::Claude3_7Sonnet => "Claude 3.7 Sonnet",
+            Self::ClaudeSonnet4 => "Claude Sonnet 4",
+            Self::ClaudeOpus4 => "Claude Opus 4",
+            Self::ClaudeOpus4_1 => "Claude Opus 4.1",
+            Self::Custom { name, .. } => name,
+        }
+    }
+
+    pub fn max_token_count(&self) -> usize {
+        match self {
+            Self::Claude3_5Sonnet
+            | Self::Claude3Opus
+            | Self::Claude3Sonnet
+            | Self::Claude3Haiku
+            | Self::Claude3_5Haiku
+            | Self::Claude3_7Sonnet
+            | Self::ClaudeSonnet4
+            | Self::ClaudeOpus4
+            | Self::ClaudeOpus4_1 => 200_000,
+            Self::Custom { max_tokens, .. } => max_tokens.unwrap_or(200_000),
+        }
+    }
+
+    /// The maximum number of tokens the model can generate in a response,
+    /// i.e. the highest `max_tokens` it accepts.
+    pub fn max_output_tokens(&self) -> u32 {
+        match self {
+            Self::Claude3Opus | Self::Claude3Sonnet | Self::Claude3Haiku => 4_096,
+            Self::Claude3_5Sonnet | Self::Claude3_5Haiku => 8_192,
+            Self::Claude3_7Sonnet | Self::ClaudeSonnet4 => 64_000,
+            Self::ClaudeOpus4 | Self::ClaudeOpus4_1 => 32_000,
+            Self::Custom {
+                max_output_tokens, ..
+            } => max_output_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        }
+    }
+
+    /// The `max_tokens` of requests to the model that don't set it: the
+    /// model's whole output limit, up to [`MAX_DEFAULT_MAX_TOKENS`].
+    pub fn default_max_tokens(&self) -> u32 {
+        self.max_output_tokens().min(MAX_DEFAULT_MAX_TOKENS)
+    }
+}
+
+/// The highest [`Model::default_max_tokens`], which keeps requests that don't
+/// ask for long responses from reserving output they won't use, and
+/// non-streaming ones within the API's time limits.
+pub const MAX_DEFAULT_MAX_TOKENS: u32 = 8_192;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+impl TryFrom<String> for Role {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        match value.as_str() {
+            "user" => Ok(Self::User),
+            "assistant" => Ok(Self::Assistant),
+            _ => Err(anyhow!("invalid role '{value}'")),
+        }
+    }
+}
+
+impl From<Role> for String {
+    fn from(val: Role) -> Self {
+        match val {
+            Role::User => "user".to_owned(),
+            Role::Assistant => "assistant".to_owned(),
+        }
+    }
+}
+
+/// A request to the Messages API. It deserializes from the body it's sent
+/// as, so that it can be saved and sent again later, although the fields
+/// sent as headers aren't included.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Request {
+    #[serde(deserialize_with = "message::deserialize_model_id")]
+    pub model: Model,
+    pub messages: Vec<RequestMessage>,
+    pub stream: bool,
+    pub system: SystemPrompt,
+    pub max_tokens: u32,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    pub stop_sequences: Vec<String>,
+    pub tools: Vec<Tool>,
+    pub tool_choice: Option<ToolChoice>,
+    pub metadata: Option<Metadata>,
+    pub thinking: Option<Thinking>,
+    /// Additional parameters merged into the request body, for trying out
+    /// API parameters that this crate doesn't support yet.
+    #[serde(flatten)]
+    pub extra: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Beta features enabled for this request, sent in the `Anthropic-Beta`
+    /// header rather than in the body.
+    #[serde(skip)]
+    pub betas: Vec<String>,
+    /// Overrides the API version of the client sending the request, which
+    /// is sent in the `Anthropic-Version` header.
+    #[serde(skip)]
+    pub api_version: Option<String>,
+    /// The time after which the request is aborted, including streaming the
+    /// response. Unlike the client's low-speed timeout, this also bounds
+    /// responses that arrive slowly but steadily.
+    #[serde(skip)]
+    pub timeout: Option<Duration>,
+    /// The encoding of the system prompt and first messages, which is sent
+    /// in their place. Set by [`Conversation::begin_send`], and cleared by
+    /// [`Request::shrink`]. Code changing `system` or any message but the
+    /// last one directly must clear it as well.
+    #[serde(skip)]
+    pub encoded_prefix: Option<Arc<EncodedPrefix>>,
+}
+
+impl Default for Request {
+    fn default() -> Self {
+        Self {
+            model: Model::default(),
+            messages: Vec::new(),
+            stream: false,
+            system: SystemPrompt::default(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            stop_sequences: Vec::new(),
+            tools: Vec::new(),
+            tool_choice: None,
+            metadata: None,
+            thinking: None,
+            extra: None,
+            betas: Vec::new(),
+            api_version: None,
+            timeout: None,
+            encoded_prefix: None,
+        }
+    }
+}
+
+impl Request {
+    /// Creates a streaming request with default settings, e.g.
+    /// `Request::new(model, ["What's a monad?"])`. Plain strings are treated
+    /// as user messages.
+    pub fn new(
+        model: Model,
+        messages: impl IntoIterator<Item = impl Into<RequestMessage>>,
+    ) -> Self {
+        Self {
+            model,
+            messages: messages.into_iter().map(Into::into).collect(),
+            stream: true,
+            ..Default::default()
+        }
+    }
+
+    /// Whether any content of the request is marked with a [`CacheControl`],
+    /// which requires the [`PROMPT_CACHING_BETA`].
+    pub fn uses_prompt_caching(&self) -> bool {
+        self.system.has_cache_control()
+            || self
+                .messages
+                .iter()
+                .any(|message| message.content.has_cache_control())
+    }
+}
+
+/// Information about the request that isn't shown to the model.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Metadata {
+    /// An opaque identifier of the user on whose behalf the request is made,
+    /// which the API uses to attribute abuse. It mustn't contain identifying
+    /// information such as a name or email address.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+}
+
+/// The smallest thinking budget the API accepts.
+pub const MIN_THINKING_BUDGET: u32 = 1024;
+
+/// Whether the model reasons before answering, returning its reasoning in
+/// [`ContentBlock::Thinking`] blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Thinking {
+    Enabled {
+        /// The most tokens the model may spend reasoning, which count towards
+        /// the request's `max_tokens`.
+        budget_tokens: u32,
+    },
+    Disabled,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct RequestMessage {
+    pub role: Role,
+    pub content: MessageContent,
+}
+
+impl RequestMessage {
+    pub fn new(role: Role, content: impl Into<MessageContent>) -> Self {
+        Self {
+            role,
+            content: content.into(),
+        }
+    }
+
+    pub fn user(content: impl Into<MessageContent>) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    pub fn assistant(content: impl Into<MessageContent>) -> Self {
+        Self::new(Role::Assistant, content)
+    }
+
+    /// Marks the end of the message as the end of a cached prefix.
+    pub fn with_cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.content.set_cache_control(cache_control);
+        self
+    }
+}
+
+impl From<&str> for RequestMessage {
+    fn from(content: &str) -> Self {
+        Self::user(content)
+    }
+}
+
+impl From<String> for RequestMessage {
+    fn from(content: String) -> Self {
+        Self::user(content)
+    }
+}
+
+impl<T: Into<MessageContent>> From<(Role, T)> for RequestMessage {
+    fn from((role, content): (Role, T)) -> Self {
+        Self::new(role, content)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseEvent {
+    MessageStart {
+        message: ResponseMessage,
+    },
+    ContentBlockStart {
+        index: u32,
+        content_block: ContentBlock,
+    },
+    Ping {},
+    ContentBlockDelta {
+        index: u32,
+        delta: TextDelta,
+    },
+    ContentBlockStop {
+        index: u32,
+    },
+    MessageDelta {
+        delta: MessageDelta,
+        usage: Usage,
+    },
+    MessageStop {},
+    /// An error that interrupted the response after it started, such as the
+    /// API becoming overloaded. No more events follow it.
+    Error {
+        error: ApiError,
+    },
+}
+
+impl ResponseEvent {
+    /// Returns the text carried by this event, if any.
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Self::ContentBlockStart {
+                content_block: ContentBlock::Text { text, .. },
+                ..
+            } => Some(text),
+            Self::ContentBlockDelta {
+                delta: TextDelta::TextDelta { text },
+                ..
+            } => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns the reasoning carried by this event, if any.
+    pub fn thinking(&self) -> Option<&str> {
+        match self {
+            Self::ContentBlockStart {
+                content_block: ContentBlock::Thinking { thinking, .. },
+                ..
+            } => Some(thinking),
+            Self::ContentBlockDelta {
+                delta: TextDelta::ThinkingDelta { thinking },
+                ..
+            } => Some(thinking),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct ResponseMessage {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub message_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<StopReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequence: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    /// Fields not known to this crate, such as ones recently added to the API.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// The changes to the message reported by a [`ResponseEvent::MessageDelta`]
+/// once generation has stopped.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub struct MessageDelta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<StopReason>,
+    /// The stop sequence that ended generation, if
+    /// [`StopReason::StopSequence`] is the stop reason.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequence: Option<String>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Usage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_tokens: Option<u32>,
+    /// Input tokens written to the prompt cache.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_creation_input_tokens: Option<u32>,
+    /// Input tokens read from the prompt cache.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_read_input_tokens: Option<u32>,
+    /// Fields not known to this crate, such as ones recently added to the API.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text {
+        text: String,
+        /// The parts of the request's documents supporting the text, when
+        /// citations are enabled for them. When streamed, they're sent as
+        /// [`TextDelta::CitationsDelta`]s.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        citations: Vec<Citation>,
+        #[serde(flatten)]
+        extra: serde_json::Map<String, serde_json::Value>,
+    },
+    /// A call to one of the request's tools. When streamed, `input` starts
+    /// out empty and is sent as [`TextDelta::InputJsonDelta`]s.
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    /// The model's reasoning, when [`Thinking`] is enabled. When streamed,
+    /// it's sent as [`TextDelta::ThinkingDelta`]s followed by a
+    /// [`TextDelta::SignatureDelta`].
+    Thinking {
+        thinking: String,
+        /// Verifies the reasoning when it's passed back in a later request.
+        #[serde(default)]
+        signature: String,
+    },
+    /// Reasoning that was flagged by safety systems, and is only sent back
+    /// encrypted.
+    RedactedThinking { data: String },
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextDelta {
+    TextDelta {
+        text: DeltaText,
+    },
+    /// A fragment of the JSON input of a [`ContentBlock::ToolUse`].
+    InputJsonDelta {
+        partial_json: String,
+    },
+    /// A citation supporting the text streamed in the block.
+    CitationsDelta {
+        citation: Citation,
+    },
+    /// A fragment of a [`ContentBlock::Thinking`].
+    ThinkingDelta {
+        thinking: String,
+    },
+    SignatureDelta {
+        signature: String,
+    },
+}
+
+/// A borrowed counterpart of [`ResponseEvent`], deserialized directly from the
+/// stream's read buffer. Text is only copied out of the buffer when it
+/// contains escape sequences.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseEventRef<'a> {
+    MessageStart {
+        message: ResponseMessage,
+    },
+    ContentBlockStart {
+        index: u32,
+        #[serde(borrow)]
+        content_block: ContentBlockRef<'a>,
+    },
+    Ping {},
+    ContentBlockDelta {
+        index: u32,
+        #[serde(borrow)]
+        delta: TextDeltaRef<'a>,
+    },
+    ContentBlockStop {
+        index: u32,
+    },
+    MessageDelta {
+        delta: MessageDelta,
+        usage: Usage,
+    },
+    MessageStop {},
+    Error {
+        error: ApiError,
+    },
+}
+
+impl ResponseEventRef<'_> {
+    pub fn into_owned(self) -> ResponseEvent {
+        match self {
+            Self::MessageStart { message } => ResponseEvent::MessageStart { message },
+            Self::ContentBlockStart {
+                index,
+                content_block,
+            } => ResponseEvent::ContentBlockStart {
+                index,
+                content_block: content_block.into_owned(),
+            },
+            Self::Ping {} => ResponseEvent::Ping {},
+            Self::ContentBlockDelta { index, delta } => ResponseEvent::ContentBlockDelta {
+                index,
+                delta: delta.into_owned(),
+            },
+            Self::ContentBlockStop { index } => ResponseEvent::ContentBlockStop { index },
+            Self::MessageDelta { delta, usage } => ResponseEvent::MessageDelta { delta, usage },
+            Self::MessageStop {} => ResponseEvent::MessageStop {},
+            Self::Error { error } => ResponseEvent::Error { error },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlockRef<'a> {
+    Text {
+        #[serde(borrow)]
+        text: Cow<'a, str>,
+        #[serde(default)]
+        citations: Vec<Citation>,
+        #[serde(flatten)]
+        extra: serde_json::Map<String, serde_json::Value>,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    Thinking {
+        #[serde(borrow)]
+        thinking: Cow<'a, str>,
+        #[serde(default)]
+        signature: String,
+    },
+    RedactedThinking {
+        data: String,
+    },
+}
+
+impl ContentBlockRef<'_> {
+    pub fn into_owned(self) -> ContentBlock {
+        match self {
+            Self::Text {
+                text,
+                citations,
+                extra,
+            } => ContentBlock::Text {
+                text: text.into_owned(),
+                citations,
+                extra,
+            },
+            Self::ToolUse { id, name, input } => ContentBlock::ToolUse { id, name, input },
+            Self::Thinking {
+                thinking,
+                signature,
+            } => ContentBlock::Thinking {
+                thinking: thinking.into_owned(),
+                signature,
+            },
+            Self::RedactedThinking { data } => ContentBlock::RedactedThinking { data },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextDeltaRef<'a> {
+    TextDelta {
+        #[serde(borrow)]
+        text: Cow<'a, str>,
+    },
+    InputJsonDelta {
+        #[serde(borrow)]
+        partial_json: Cow<'a, str>,
+    },
+    CitationsDelta {
+        citation: Citation,
+    },
+    ThinkingDelta {
+        #[serde(borrow)]
+        thinking: Cow<'a, str>,
+    },
+    SignatureDelta {
+        signature: String,
+    },
+}
+
+impl TextDeltaRef<'_> {
+    pub fn into_owned(self) -> TextDelta {
+        match self {
+            Self::TextDelta { text } => TextDelta::TextDelta { text: text.into() },
+            Self::InputJsonDelta { partial_json } => TextDelta::InputJsonDelta {
+                partial_json: partial_json.into_owned(),
+            },
+            Self::CitationsDelta { citation } => TextDelta::CitationsDelta { citation },
+            Self::ThinkingDelta { thinking } => TextDelta::ThinkingDelta {
+                thinking: thinking.into_owned(),
+            },
+            Self::SignatureDelta { signature } => TextDelta::SignatureDelta { signature },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator as _;
+
+    #[test]
+    fn test_model_ids() {
+        for model in Model::iter().filter(|model| !matches!(model, Model::Custom { .. })) {
+            assert_eq!(Model::from_id(model.id()).unwrap(), model);
+            let json = serde_json::to_string(&model).unwrap();
+            assert_eq!(json, format!("\"{}\"", model.id()));
+            assert_eq!(serde_json::from_str::<Model>(&json).unwrap(), model);
+        }
+        assert_eq!(
+            serde_json::from_str::<Model>("\"claude-3-7-sonnet-latest\"").unwrap(),
+            Model::Claude3_7Sonnet
+        );
+    }
+
+    #[test]
+    fn test_max_output_tokens() {
+        assert_eq!(Model::Claude3Haiku.default_max_tokens(), 4_096);
+        assert_eq!(Model::ClaudeSonnet4.max_output_tokens(), 64_000);
+        assert_eq!(
+            Model::ClaudeSonnet4.default_max_tokens(),
+            MAX_DEFAULT_MAX_TOKENS
+        );
+        let custom = Model::from_id("claude-next").unwrap();
+        assert_eq!(custom.max_output_tokens(), DEFAULT_MAX_TOKENS);
+        assert_eq!(
+            Model::from_id("claude-opus-4-1-20250805").unwrap(),
+            Model::ClaudeOpus4_1
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let request = Request::builder(Model::Claude3_7Sonnet)
+            .system("Be brief.")
+            .user("What's the weather in Paris?")
+            .tool(ToolDefinition::new(
+                "get_weather",
+                "",
+                serde_json::json!({"type": "object"}),
+            ))
+            .tool_choice(ToolChoice::auto().sequential())
+            .thinking(2048)
+            .max_tokens(4096)
+            .build()
+            .unwrap();
+        let json = serde_json::to_value(&request).unwrap();
+        let restored: Request = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(restored.model, Model::Claude3_7Sonnet);
+        assert_eq!(serde_json::to_value(&restored).unwrap(), json);
+
+        let events = [
+            serde_json::json!({
+                "type": "message_start",
+                "message": {
+                    "type": "message",
+                    "id": "msg_01",
+                    "role": "assistant",
+                    "content": [],
+                    "model": "claude-3-7-sonnet-20250219",
+                    "usage": {"input_tokens": 10, "output_tokens": 1},
+                },
+            }),
+            serde_json::json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": {"type": "text", "text": ""},
+            }),
+            serde_json::json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "text_delta", "text": "Sunny"},
+            }),
+            serde_json::json!({"type": "content_block_stop", "index": 0}),
+            serde_json::json!({
+                "type": "message_delta",
+                "delta": {"stop_reason": "end_turn"},
+                "usage": {"output_tokens": 2},
+            }),
+            serde_json::json!({
+                "type": "error",
+                "error": {"type": "overloaded_error", "message": "Overloaded"},
+            }),
+            serde_json::json!({"type": "message_stop"}),
+        ];
+        for json in events {
+            let event: ResponseEvent = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&event).unwrap(), json);
+        }
+    }
+}
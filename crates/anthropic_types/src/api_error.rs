@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::Duration};
+
+/// The kind of an error returned by the API, from the `type` of its error
+/// envelope.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ApiErrorKind {
+    InvalidRequest,
+    Authentication,
+    Permission,
+    NotFound,
+    RequestTooLarge,
+    RateLimit,
+    /// An unexpected error internal to the API.
+    Api,
+    /// The API is temporarily overloaded across all users, sent with the
+    /// non-standard status 529. Unlike [`Self::RateLimit`], it isn't caused
+    /// by the account's usage, so the request is best retried after a
+    /// backoff of a few seconds, e.g. with `RetryPolicy::overloaded`.
+    Overloaded,
+    /// A kind not known to this crate.
+    Other(String),
+}
+
+impl ApiErrorKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::InvalidRequest => "invalid_request_error",
+            Self::Authentication => "authentication_error",
+            Self::Permission => "permission_error",
+            Self::NotFound => "not_found_error",
+            Self::RequestTooLarge => "request_too_large",
+            Self::RateLimit => "rate_limit_error",
+            Self::Api => "api_error",
+            Self::Overloaded => "overloaded_error",
+            Self::Other(kind) => kind,
+        }
+    }
+
+    /// Returns the status the API responds with for errors of this kind.
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::InvalidRequest => 400,
+            Self::Authentication => 401,
+            Self::Permission => 403,
+            Self::NotFound => 404,
+            Self::RequestTooLarge => 413,
+            Self::RateLimit => 429,
+            Self::Overloaded => 529,
+            Self::Api | Self::Other(_) => 500,
+        }
+    }
+
+    /// Returns the kind the API uses for errors with `status`, for responses
+    /// without an error envelope.
+    fn from_status(status: u16) -> Self {
+        match status {
+            400 => Self::InvalidRequest,
+            401 => Self::Authentication,
+            403 => Self::Permission,
+            404 => Self::NotFound,
+            413 => Self::RequestTooLarge,
+            429 => Self::RateLimit,
+            529 => Self::Overloaded,
+            _ => Self::Api,
+        }
+    }
+}
+
+impl From<&str> for ApiErrorKind {
+    fn from(kind: &str) -> Self {
+        match kind {
+            "invalid_request_error" => Self::InvalidRequest,
+            "authentication_error" => Self::Authentication,
+            "permission_error" => Self::Permission,
+            "not_found_error" => Self::NotFound,
+            "request_too_large" => Self::RequestTooLarge,
+            "rate_limit_error" => Self::RateLimit,
+            "api_error" => Self::Api,
+            "overloaded_error" => Self::Overloaded,
+            _ => Self::Other(kind.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ApiErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An error response from the API.
+///
+/// It's (de)serialized as the `error` object of an error envelope, as sent
+/// in [`crate::ResponseEvent::Error`]. Such errors are sent after the
+/// response succeeded, so their status is the one the API responds with for
+/// their kind, see [`ApiErrorKind::status`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "ErrorBody", into = "ErrorBody")]
+pub struct ApiError {
+    /// The HTTP status of the response.
+    pub status: u16,
+    pub kind: ApiErrorKind,
+    pub message: String,
+    /// The `request-id` header of the response, which identifies the request
+    /// in the API's logs, e.g. when contacting support.
+    pub request_id: Option<String>,
+    /// How long the response's `Retry-After` header asks to wait before
+    /// retrying, which rate limiting errors usually include.
+    pub retry_after: Option<Duration>,
+}
+
+#[derive(Deserialize)]
+struct ErrorEnvelope {
+    error: ErrorBody,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ErrorBody {
+    #[serde(rename = "type")]
+    kind: String,
+    message: String,
+}
+
+impl From<ErrorBody> for ApiError {
+    fn from(body: ErrorBody) -> Self {
+        let kind = ApiErrorKind::from(body.kind.as_str());
+        Self {
+            status: kind.status(),
+            kind,
+            message: body.message,
+            request_id: None,
+            retry_after: None,
+        }
+    }
+}
+
+impl From<ApiError> for ErrorBody {
+    fn from(error: ApiError) -> Self {
+        Self {
+            kind: error.kind.as_str().to_string(),
+            message: error.message,
+        }
+    }
+}
+
+impl ApiError {
+    /// Parses the error envelope in the body of a response with `status`.
+    /// Bodies that aren't an envelope, such as ones returned by a proxy, are
+    /// used as the message.
+    pub fn from_response(status: u16, body: &str) -> Self {
+        match serde_json::from_str::<ErrorEnvelope>(body) {
+            Ok(envelope) => Self {
+                status,
+                kind: ApiErrorKind::from(envelope.error.kind.as_str()),
+                message: envelope.error.message,
+                request_id: None,
+                retry_after: None,
+            },
+            Err(_) => Self {
+                status,
+                kind: ApiErrorKind::from_status(status),
+                message: body.trim().to_string(),
+                request_id: None,
+                retry_after: None,
+            },
+        }
+    }
+
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    pub fn with_retry_after(mut self, retry_after: Option<Duration>) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Whether the request could succeed if sent again later.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind,
+            ApiErrorKind::RateLimit | ApiErrorKind::Api | ApiErrorKind::Overloaded
+        ) || matches!(self.status, 429 | 500 | 502 | 503 | 504 | 529)
+    }
+
+    /// Whether the API was too busy to handle the request, which is
+    /// temporary and worth telling the user about while retrying, rather
+    /// than reporting it as a connection failure.
+    pub fn is_overloaded(&self) -> bool {
+        self.status == 529 || self.kind == ApiErrorKind::Overloaded
+    }
+
+    /// Whether the request exceeded the size limit or the model's context
+    /// window.
+    pub fn is_request_too_large(&self) -> bool {
+        self.status == 413
+            || self.kind == ApiErrorKind::RequestTooLarge
+            || (self.kind == ApiErrorKind::InvalidRequest
+                && self.message.contains("prompt is too long"))
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.kind, self.status, self.message)?;
+        if let Some(request_id) = &self.request_id {
+            write!(f, " [request {request_id}]")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_api_error() {
+        let error = ApiError::from_response(
+            529,
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+        );
+        assert_eq!(error.kind, ApiErrorKind::Overloaded);
+        assert_eq!(error.message, "Overloaded");
+        assert!(error.is_retryable());
+        assert!(error.is_overloaded());
+        assert_eq!(error.to_string(), "overloaded_error (529): Overloaded");
+        assert_eq!(
+            error
+                .with_request_id(Some("req_018EeWyXxfu5pfWkrYcMdjWG".into()))
+                .to_string(),
+            "overloaded_error (529): Overloaded [request req_018EeWyXxfu5pfWkrYcMdjWG]"
+        );
+
+        let error = ApiError::from_response(
+            400,
+            r#"{"type":"error","error":{"type":"invalid_request_error","message":"prompt is too long: 210000 tokens > 200000 maximum"}}"#,
+        );
+        assert!(error.is_request_too_large());
+        assert!(!error.is_retryable());
+
+        assert!(!error.is_overloaded());
+
+        let error = ApiError::from_response(529, "<html>Service Overloaded</html>");
+        assert_eq!(error.kind, ApiErrorKind::Overloaded);
+        assert!(error.is_overloaded());
+
+        let error = ApiError::from_response(413, "<html>Request Entity Too Large</html>");
+        assert_eq!(error.kind, ApiErrorKind::RequestTooLarge);
+        assert!(error.is_request_too_large());
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::Result;
+use base64::display::Base64Display;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{borrow::Cow, fmt, sync::Arc};
+#[cfg(feature = "mmap")]
+use std::{fs::File, path::Path};
+
+/// Binary data, such as an image or a document, that the API expects to be
+/// base64-encoded.
+///
+/// The data is kept in its original form and only encoded while the request
+/// body is being written, so the encoded form is never held in memory as a
+/// whole. With the `mmap` feature, data can also be memory-mapped from a file
+/// rather than copied.
+#[derive(Clone)]
+pub struct Base64Data(Source);
+
+#[derive(Clone)]
+enum Source {
+    Bytes(Arc<[u8]>),
+    #[cfg(feature = "mmap")]
+    Mapped(Arc<Mmap>),
+    /// Data that was already base64-encoded, e.g. when deserialized.
+    Encoded(Arc<str>),
+}
+
+impl Base64Data {
+    pub fn from_bytes(bytes: impl Into<Arc<[u8]>>) -> Self {
+        Self(Source::Bytes(bytes.into()))
+    }
+
+    pub fn from_base64(encoded: impl Into<Arc<str>>) -> Self {
+        Self(Source::Encoded(encoded.into()))
+    }
+
+    /// Memory-maps the file at `path`, rather than reading it into memory.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated or modified while the returned data, or
+    /// any clone of it, is alive. Doing so is undefined behavior, and can
+    /// crash the process with `SIGBUS`. Files that may change, such as ones
+    /// open in an editor, should be read with [`std::fs::read`] instead.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn map_file(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the caller guarantees that the file isn't modified while
+        // it's mapped.
+        let mmap = unsafe { Mmap::map(&file)? };
+        Ok(Self(Source::Mapped(Arc::new(mmap))))
+    }
+
+    /// Returns the decoded data.
+    pub fn bytes(&self) -> Result<Cow<'_, [u8]>> {
+        match &self.0 {
+            Source::Bytes(bytes) => Ok(Cow::Borrowed(&bytes[..])),
+            #[cfg(feature = "mmap")]
+            Source::Mapped(mmap) => Ok(Cow::Borrowed(&mmap[..])),
+            Source::Encoded(encoded) => Ok(Cow::Owned(base64::decode(encoded.as_bytes())?)),
+        }
+    }
+
+    /// Returns the data as is, unless it was already base64-encoded.
+    fn raw_bytes(&self) -> Option<&[u8]> {
+        match &self.0 {
+            Source::Bytes(bytes) => Some(bytes),
+            #[cfg(feature = "mmap")]
+            Source::Mapped(mmap) => Some(mmap),
+            Source::Encoded(_) => None,
+        }
+    }
+
+    fn encoded(&self) -> Cow<'_, str> {
+        match &self.0 {
+            Source::Bytes(bytes) => Cow::Owned(base64::encode(bytes)),
+            #[cfg(feature = "mmap")]
+            Source::Mapped(mmap) => Cow::Owned(base64::encode(&mmap[..])),
+            Source::Encoded(encoded) => Cow::Borrowed(encoded),
+        }
+    }
+
+    /// Returns the length of the data once base64-encoded.
+    pub fn encoded_len(&self) -> usize {
+        match &self.0 {
+            Source::Bytes(bytes) => encoded_len(bytes.len()),
+            #[cfg(feature = "mmap")]
+            Source::Mapped(mmap) => encoded_len(mmap.len()),
+            Source::Encoded(encoded) => encoded.len(),
+        }
+    }
+}
+
+fn encoded_len(len: usize) -> usize {
+    len.div_ceil(3) * 4
+}
+
+impl From<Vec<u8>> for Base64Data {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+
+impl PartialEq for Base64Data {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.raw_bytes(), other.raw_bytes()) {
+            (Some(a), Some(b)) => a == b,
+            // Payloads that can't be decoded are only equal to themselves.
+            _ => self.encoded() == other.encoded(),
+        }
+    }
+}
+
+impl Eq for Base64Data {}
+
+impl fmt::Debug for Base64Data {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Base64Data")
+            .field("encoded_len", &self.encoded_len())
+            .finish()
+    }
+}
+
+impl Serialize for Base64Data {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.0 {
+            Source::Bytes(bytes) => {
+                serializer.collect_str(&Base64Display::with_config(bytes, base64::STANDARD))
+            }
+            #[cfg(feature = "mmap")]
+            Source::Mapped(mmap) => {
+                serializer.collect_str(&Base64Display::with_config(mmap, base64::STANDARD))
+            }
+            Source::Encoded(encoded) => serializer.serialize_str(encoded),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Base64Data {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        Ok(Self::from_base64(encoded))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eq() {
+        let data = Base64Data::from_bytes(b"hello".to_vec());
+        assert_eq!(data, Base64Data::from_base64("aGVsbG8="));
+        assert_ne!(data, Base64Data::from_bytes(b"world".to_vec()));
+        assert_eq!(Base64Data::from_base64("!!"), Base64Data::from_base64("!!"));
+        assert_ne!(Base64Data::from_base64("!!"), Base64Data::from_base64("??"));
+    }
+}
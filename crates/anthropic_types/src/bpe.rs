@@ -0,0 +1,35 @@
+use crate::{estimate::MESSAGE_OVERHEAD_TOKENS, Request, RequestMessage, ESTIMATED_IMAGE_TOKENS};
+use std::sync::OnceLock;
+use tiktoken_rs::CoreBPE;
+
+/// Returns the BPE used to approximate Claude's tokenizer.
+///
+/// Claude's tokenizer isn't public, but `cl100k_base`'s vocabulary is close
+/// enough to count tokens within a few percent, in microseconds and without a
+/// network round-trip.
+fn bpe() -> &'static CoreBPE {
+    static BPE: OnceLock<CoreBPE> = OnceLock::new();
+    BPE.get_or_init(|| tiktoken_rs::cl100k_base().expect("cl100k_base ranks are bundled"))
+}
+
+/// Approximates the number of tokens in `text`.
+pub fn bpe_token_count(text: &str) -> usize {
+    bpe().encode_ordinary(text).len()
+}
+
+/// Approximates the number of tokens `message` takes up in a request.
+pub fn bpe_message_token_count(message: &RequestMessage) -> usize {
+    bpe_token_count(&message.content.text())
+        + message.content.images().count() * ESTIMATED_IMAGE_TOKENS
+        + MESSAGE_OVERHEAD_TOKENS
+}
+
+/// Approximates the number of input tokens of `request`.
+pub fn bpe_request_token_count(request: &Request) -> usize {
+    bpe_token_count(&request.system.text())
+        + request
+            .messages
+            .iter()
+            .map(bpe_message_token_count)
+            .sum::<usize>()
+}
@@ -0,0 +1,218 @@
+use crate::ResponseEvent;
+use futures::{
+    channel::mpsc,
+    future,
+    stream::{self, BoxStream},
+    SinkExt, Stream, StreamExt,
+};
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+/// What happens when a [`Subscriber`]'s buffer is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Stop reading the source until the subscriber catches up, slowing down
+    /// every other subscriber along with it.
+    #[default]
+    Wait,
+    /// Disconnect the subscriber, whose stream then ends early and reports
+    /// [`Subscriber::is_lagged`].
+    Disconnect,
+}
+
+/// Splits one stream so that several consumers, such as the assistant panel,
+/// a logger and an edit applier, each receive every item without requesting
+/// the completion again.
+///
+/// Subscribers are added with [`Broadcast::subscribe`] before the source is
+/// driven with [`Broadcast::run`], typically on a background task.
+pub struct Broadcast<T> {
+    source: BoxStream<'static, T>,
+    subscribers: Vec<SubscriberHandle<T>>,
+}
+
+struct SubscriberHandle<T> {
+    sender: mpsc::Sender<T>,
+    policy: LagPolicy,
+    lagged: Arc<AtomicBool>,
+}
+
+/// One consumer of a [`Broadcast`].
+pub struct Subscriber<T> {
+    receiver: mpsc::Receiver<T>,
+    lagged: Arc<AtomicBool>,
+}
+
+impl<T: Clone + Send + 'static> Broadcast<T> {
+    pub fn new(source: BoxStream<'static, T>) -> Self {
+        Self {
+            source,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Adds a consumer that can fall up to `capacity` items behind the
+    /// source before `policy` applies.
+    pub fn subscribe(&mut self, capacity: usize, policy: LagPolicy) -> Subscriber<T> {
+        // The channel holds an extra item for each sender.
+        let (sender, receiver) = mpsc::channel(capacity.saturating_sub(1));
+        let lagged = Arc::new(AtomicBool::new(false));
+        self.subscribers.push(SubscriberHandle {
+            sender,
+            policy,
+            lagged: lagged.clone(),
+        });
+        Subscriber { receiver, lagged }
+    }
+
+    /// Forwards the source's items to every subscriber. Returns once the
+    /// source ends or every subscriber has been dropped, in which case the
+    /// source is dropped without being read further.
+    pub async fn run(self) {
+        let Self {
+            mut source,
+            mut subscribers,
+        } = self;
+        while !subscribers.is_empty() {
+            let Some(item) = source.next().await else {
+                break;
+            };
+            let mut ix = 0;
+            while ix < subscribers.len() {
+                let subscriber = &mut subscribers[ix];
+                let connected = match subscriber.policy {
+                    LagPolicy::Wait => subscriber.sender.send(item.clone()).await.is_ok(),
+                    LagPolicy::Disconnect => match subscriber.sender.try_send(item.clone()) {
+                        Ok(()) => true,
+                        Err(error) => {
+                            if error.is_full() {
+                                subscriber.lagged.store(true, Ordering::SeqCst);
+                            }
+                            false
+                        }
+                    },
+                };
+                if connected {
+                    ix += 1;
+                } else {
+                    subscribers.swap_remove(ix);
+                }
+            }
+        }
+    }
+}
+
+impl<T> Subscriber<T> {
+    /// Whether this subscriber was disconnected for falling behind, meaning
+    /// that it didn't receive every item.
+    pub fn is_lagged(&self) -> bool {
+        self.lagged.load(Ordering::SeqCst)
+    }
+}
+
+impl<T> Stream for Subscriber<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+/// An error shared between the subscribers of a [`Broadcast`].
+#[derive(Clone, Debug)]
+pub struct SharedError(Arc<anyhow::Error>);
+
+impl SharedError {
+    pub fn error(&self) -> &anyhow::Error {
+        &self.0
+    }
+}
+
+impl From<anyhow::Error> for SharedError {
+    fn from(error: anyhow::Error) -> Self {
+        Self(Arc::new(error))
+    }
+}
+
+impl fmt::Display for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl std::error::Error for SharedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// The error ending the event stream of a [`Subscriber`] that was
+/// disconnected for falling behind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lagged;
+
+impl fmt::Display for Lagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the consumer fell too far behind the stream")
+    }
+}
+
+impl std::error::Error for Lagged {}
+
+pub type EventBroadcast = Broadcast<Result<ResponseEvent, SharedError>>;
+
+impl EventBroadcast {
+    /// Broadcasts a completion's events, sharing any error between the
+    /// subscribers.
+    pub fn events(events: BoxStream<'static, anyhow::Result<ResponseEvent>>) -> Self {
+        Self::new(events.map(|event| event.map_err(SharedError::from)).boxed())
+    }
+}
+
+impl Subscriber<Result<ResponseEvent, SharedError>> {
+    /// Converts the subscriber to a regular event stream, which ends with a
+    /// [`Lagged`] error if it was disconnected.
+    pub fn into_events(self) -> BoxStream<'static, anyhow::Result<ResponseEvent>> {
+        let lagged = self.lagged.clone();
+        let lag_error = stream::once(async move {
+            lagged
+                .load(Ordering::SeqCst)
+                .then(|| Err(anyhow::Error::from(Lagged)))
+        })
+        .filter_map(future::ready);
+        self.map(|event| event.map_err(anyhow::Error::from))
+            .chain(lag_error)
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_broadcast() {
+        let mut broadcast = Broadcast::new(stream::iter(0..5).boxed());
+        let a = broadcast.subscribe(1, LagPolicy::Wait);
+        let b = broadcast.subscribe(2, LagPolicy::Wait);
+        let mut lagging = broadcast.subscribe(2, LagPolicy::Disconnect);
+        let (_, a, b) = block_on(future::join3(
+            broadcast.run(),
+            a.collect::<Vec<_>>(),
+            b.collect::<Vec<_>>(),
+        ));
+        assert_eq!(a, [0, 1, 2, 3, 4]);
+        assert_eq!(b, [0, 1, 2, 3, 4]);
+
+        assert_eq!(block_on((&mut lagging).collect::<Vec<_>>()), [0, 1]);
+        assert!(lagging.is_lagged());
+    }
+}
@@ -0,0 +1,50 @@
+use crate::Model;
+
+/// The features each model supports, for gating UI such as image attachments
+/// or tool toggles. Custom models are assumed to support everything, since
+/// they're usually models newer than this crate.
+impl Model {
+    pub fn supports_tools(&self) -> bool {
+        true
+    }
+
+    /// Whether the model accepts images in its messages.
+    pub fn supports_vision(&self) -> bool {
+        true
+    }
+
+    pub fn supports_prompt_caching(&self) -> bool {
+        !matches!(self, Self::Claude3Sonnet)
+    }
+
+    /// Whether the model can reason before answering when
+    /// [`crate::Request::thinking`] is set.
+    pub fn supports_extended_thinking(&self) -> bool {
+        match self {
+            Self::Claude3_5Sonnet
+            | Self::Claude3Opus
+            | Self::Claude3Sonnet
+            | Self::Claude3Haiku
+            | Self::Claude3_5Haiku => false,
+            Self::Claude3_7Sonnet
+            | Self::ClaudeSonnet4
+            | Self::ClaudeOpus4
+            | Self::ClaudeOpus4_1
+            | Self::Custom { .. } => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        assert!(!Model::Claude3Sonnet.supports_prompt_caching());
+        assert!(Model::Claude3Haiku.supports_prompt_caching());
+        assert!(!Model::Claude3_5Sonnet.supports_extended_thinking());
+        assert!(Model::Claude3_7Sonnet.supports_extended_thinking());
+        assert!(Model::ClaudeSonnet4.supports_vision() && Model::ClaudeSonnet4.supports_tools());
+    }
+}
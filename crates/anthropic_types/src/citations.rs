@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+/// Whether the model should cite a document it's given, see
+/// [`crate::RequestContent::with_citations`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CitationsConfig {
+    pub enabled: bool,
+}
+
+/// A reference from a text block of a response to the part of a document
+/// that supports it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Citation {
+    /// A range of characters of a plain text document.
+    CharLocation {
+        cited_text: String,
+        document_index: usize,
+        document_title: Option<String>,
+        start_char_index: usize,
+        /// Exclusive.
+        end_char_index: usize,
+    },
+    /// A range of pages of a PDF, numbered from 1.
+    PageLocation {
+        cited_text: String,
+        document_index: usize,
+        document_title: Option<String>,
+        start_page_number: usize,
+        /// Exclusive.
+        end_page_number: usize,
+    },
+    /// A range of the blocks of a document made of content blocks.
+    ContentBlockLocation {
+        cited_text: String,
+        document_index: usize,
+        document_title: Option<String>,
+        start_block_index: usize,
+        /// Exclusive.
+        end_block_index: usize,
+    },
+}
+
+impl Citation {
+    /// The text of the document being cited.
+    pub fn cited_text(&self) -> &str {
+        match self {
+            Self::CharLocation { cited_text, .. }
+            | Self::PageLocation { cited_text, .. }
+            | Self::ContentBlockLocation { cited_text, .. } => cited_text,
+        }
+    }
+
+    /// The index of the cited document among the request's documents.
+    pub fn document_index(&self) -> usize {
+        match self {
+            Self::CharLocation { document_index, .. }
+            | Self::PageLocation { document_index, .. }
+            | Self::ContentBlockLocation { document_index, .. } => *document_index,
+        }
+    }
+
+    pub fn document_title(&self) -> Option<&str> {
+        match self {
+            Self::CharLocation { document_title, .. }
+            | Self::PageLocation { document_title, .. }
+            | Self::ContentBlockLocation { document_title, .. } => document_title.as_deref(),
+        }
+    }
+}
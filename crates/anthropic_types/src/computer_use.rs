@@ -0,0 +1,270 @@
+use crate::ToolDefinition;
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+
+/// The beta that enables the Anthropic-defined tools of version `20241022`.
+pub const COMPUTER_USE_2024_10_22_BETA: &str = "computer-use-2024-10-22";
+
+/// The beta that enables the Anthropic-defined tools of version `20250124`.
+pub const COMPUTER_USE_2025_01_24_BETA: &str = "computer-use-2025-01-24";
+
+/// A tool in a request: either one defined by its JSON schema, or one
+/// defined by Anthropic, whose schema the model already knows.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Tool {
+    Anthropic(AnthropicTool),
+    Custom(ToolDefinition),
+}
+
+impl Tool {
+    /// The name the model calls the tool by.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Anthropic(tool) => tool.name(),
+            Self::Custom(tool) => &tool.name,
+        }
+    }
+
+    /// The beta that has to be enabled for requests offering the tool, if
+    /// any.
+    pub fn beta(&self) -> Option<&'static str> {
+        match self {
+            Self::Anthropic(tool) => Some(tool.version().beta()),
+            Self::Custom(_) => None,
+        }
+    }
+}
+
+impl From<ToolDefinition> for Tool {
+    fn from(tool: ToolDefinition) -> Self {
+        Self::Custom(tool)
+    }
+}
+
+impl From<AnthropicTool> for Tool {
+    fn from(tool: AnthropicTool) -> Self {
+        Self::Anthropic(tool)
+    }
+}
+
+/// The version of an [`AnthropicTool`], which determines the models it
+/// works with: `20241022` for Claude 3.5 Sonnet, `20250124` for later models.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ComputerUseVersion {
+    V20241022,
+    #[default]
+    V20250124,
+}
+
+impl ComputerUseVersion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::V20241022 => "20241022",
+            Self::V20250124 => "20250124",
+        }
+    }
+
+    pub fn beta(&self) -> &'static str {
+        match self {
+            Self::V20241022 => COMPUTER_USE_2024_10_22_BETA,
+            Self::V20250124 => COMPUTER_USE_2025_01_24_BETA,
+        }
+    }
+
+    fn parse(version: &str) -> Option<Self> {
+        match version {
+            "20241022" => Some(Self::V20241022),
+            "20250124" => Some(Self::V20250124),
+            _ => None,
+        }
+    }
+}
+
+/// A tool defined by Anthropic for computer use agents, which carry out the
+/// actions the model calls it with themselves.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "RawAnthropicTool", try_from = "RawAnthropicTool")]
+pub enum AnthropicTool {
+    /// Takes screenshots of a display and controls its mouse and keyboard.
+    Computer {
+        version: ComputerUseVersion,
+        display_width_px: u32,
+        display_height_px: u32,
+        /// The X11 display to control, if there are several.
+        display_number: Option<u32>,
+    },
+    /// Runs commands in a persistent shell session.
+    Bash { version: ComputerUseVersion },
+    /// Views, creates and edits text files.
+    TextEditor { version: ComputerUseVersion },
+}
+
+impl AnthropicTool {
+    pub fn computer(display_width_px: u32, display_height_px: u32) -> Self {
+        Self::Computer {
+            version: ComputerUseVersion::default(),
+            display_width_px,
+            display_height_px,
+            display_number: None,
+        }
+    }
+
+    pub fn bash() -> Self {
+        Self::Bash {
+            version: ComputerUseVersion::default(),
+        }
+    }
+
+    pub fn text_editor() -> Self {
+        Self::TextEditor {
+            version: ComputerUseVersion::default(),
+        }
+    }
+
+    pub fn with_version(mut self, new_version: ComputerUseVersion) -> Self {
+        match &mut self {
+            Self::Computer { version, .. }
+            | Self::Bash { version }
+            | Self::TextEditor { version } => *version = new_version,
+        }
+        self
+    }
+
+    pub fn version(&self) -> ComputerUseVersion {
+        match self {
+            Self::Computer { version, .. }
+            | Self::Bash { version }
+            | Self::TextEditor { version } => *version,
+        }
+    }
+
+    /// The name the model calls the tool by, which can't be changed.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Computer { .. } => "computer",
+            Self::Bash { .. } => "bash",
+            Self::TextEditor { .. } => "str_replace_editor",
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Computer { .. } => "computer",
+            Self::Bash { .. } => "bash",
+            Self::TextEditor { .. } => "text_editor",
+        }
+    }
+}
+
+/// The JSON representation of an [`AnthropicTool`], e.g.
+/// `{"type": "bash_20250124", "name": "bash"}`.
+#[derive(Serialize, Deserialize)]
+struct RawAnthropicTool {
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    display_width_px: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    display_height_px: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    display_number: Option<u32>,
+}
+
+impl From<AnthropicTool> for RawAnthropicTool {
+    fn from(tool: AnthropicTool) -> Self {
+        let mut raw = RawAnthropicTool {
+            kind: format!("{}_{}", tool.kind(), tool.version().as_str()),
+            name: tool.name().to_string(),
+            display_width_px: None,
+            display_height_px: None,
+            display_number: None,
+        };
+        if let AnthropicTool::Computer {
+            display_width_px,
+            display_height_px,
+            display_number,
+            ..
+        } = tool
+        {
+            raw.display_width_px = Some(display_width_px);
+            raw.display_height_px = Some(display_height_px);
+            raw.display_number = display_number;
+        }
+        raw
+    }
+}
+
+impl TryFrom<RawAnthropicTool> for AnthropicTool {
+    type Error = Error;
+
+    fn try_from(raw: RawAnthropicTool) -> Result<Self, Error> {
+        let (kind, version) = raw
+            .kind
+            .rsplit_once('_')
+            .ok_or_else(|| anyhow!("invalid tool type '{}'", raw.kind))?;
+        let version = ComputerUseVersion::parse(version)
+            .ok_or_else(|| anyhow!("unsupported tool version '{version}'"))?;
+        match kind {
+            "computer" => Ok(Self::Computer {
+                version,
+                display_width_px: raw
+                    .display_width_px
+                    .ok_or_else(|| anyhow!("display_width_px is missing"))?,
+                display_height_px: raw
+                    .display_height_px
+                    .ok_or_else(|| anyhow!("display_height_px is missing"))?,
+                display_number: raw.display_number,
+            }),
+            "bash" => Ok(Self::Bash { version }),
+            "text_editor" => Ok(Self::TextEditor { version }),
+            _ => Err(anyhow!("unknown tool type '{}'", raw.kind)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_anthropic_tools() {
+        let computer =
+            AnthropicTool::computer(1024, 768).with_version(ComputerUseVersion::V20241022);
+        assert_eq!(
+            serde_json::to_value(&computer).unwrap(),
+            json!({
+                "type": "computer_20241022",
+                "name": "computer",
+                "display_width_px": 1024,
+                "display_height_px": 768,
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(AnthropicTool::text_editor()).unwrap(),
+            json!({"type": "text_editor_20250124", "name": "str_replace_editor"})
+        );
+
+        let tools: Vec<Tool> = serde_json::from_value(json!([
+            {"type": "bash_20250124", "name": "bash"},
+            {"name": "read_file", "input_schema": {"type": "object"}},
+        ]))
+        .unwrap();
+        assert_eq!(tools[0], Tool::Anthropic(AnthropicTool::bash()));
+        assert_eq!(tools[0].beta(), Some(COMPUTER_USE_2025_01_24_BETA));
+        assert_eq!(tools[1].name(), "read_file");
+        assert_eq!(tools[1].beta(), None);
+
+        let request = crate::RequestBuilder::new(crate::Model::Claude3_7Sonnet)
+            .user("List the files")
+            .tools(tools)
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.betas,
+            vec![COMPUTER_USE_2025_01_24_BETA.to_string()]
+        );
+    }
+}
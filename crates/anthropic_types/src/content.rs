@@ -0,0 +1,760 @@
+use crate::{Base64Data, CitationsConfig};
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// The largest image the API accepts, before base64 encoding.
+pub const MAX_IMAGE_SIZE: usize = 5 * 1024 * 1024;
+
+/// A rough number of tokens an image takes up in a request. The actual count
+/// depends on the image's dimensions, and is at most about this much for the
+/// largest images the API doesn't downscale.
+pub const ESTIMATED_IMAGE_TOKENS: usize = 1600;
+
+/// The largest PDF the API accepts, before base64 encoding.
+pub const MAX_PDF_SIZE: usize = 32 * 1024 * 1024;
+
+/// The most pages a PDF can have.
+pub const MAX_PDF_PAGES: usize = 100;
+
+/// The beta that enables base64 PDF [`DocumentSource`]s.
+pub const PDFS_BETA: &str = "pdfs-2024-09-25";
+
+/// The beta that enables [`CacheControl`] breakpoints.
+pub const PROMPT_CACHING_BETA: &str = "prompt-caching-2024-07-31";
+
+/// The beta that enables the files endpoints and referring to uploaded files
+/// from content blocks.
+pub const FILES_API_BETA: &str = "files-api-2025-04-14";
+
+/// The content of a [`crate::RequestMessage`]: plain text, or blocks mixing
+/// text, images and documents.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Blocks(Vec<RequestContent>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RequestContent {
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+    Image {
+        source: ImageSource,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+    Document {
+        source: DocumentSource,
+        /// Identifies the document in [`crate::Citation`]s.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        citations: Option<CitationsConfig>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+    /// A tool call from an earlier response, which is sent back in the
+    /// assistant message it was made in.
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+    /// The output of a tool call, which is sent in the user message following
+    /// the call.
+    ToolResult {
+        tool_use_id: String,
+        /// Text, or blocks of text and images.
+        #[serde(default, skip_serializing_if = "MessageContent::is_empty")]
+        content: MessageContent,
+        /// Whether the tool failed, in which case the content describes the
+        /// error.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        is_error: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+}
+
+/// Marks the end of a prefix of the request that the API should cache, so
+/// that later requests starting with the same prefix are processed faster
+/// and billed at a lower rate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CacheControl {
+    /// Cached for a few minutes after its last use.
+    #[default]
+    Ephemeral,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    Base64 {
+        media_type: ImageMediaType,
+        data: Base64Data,
+    },
+    /// An image uploaded with the files API.
+    File { file_id: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DocumentSource {
+    Text {
+        media_type: DocumentMediaType,
+        data: String,
+    },
+    Base64 {
+        media_type: DocumentMediaType,
+        data: Base64Data,
+    },
+    /// A document uploaded with the files API.
+    File { file_id: String },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DocumentMediaType {
+    #[serde(rename = "text/plain")]
+    PlainText,
+    #[serde(rename = "application/pdf")]
+    Pdf,
+}
+
+/// The image formats supported by the API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ImageMediaType {
+    #[serde(rename = "image/jpeg")]
+    Jpeg,
+    #[serde(rename = "image/png")]
+    Png,
+    #[serde(rename = "image/gif")]
+    Gif,
+    #[serde(rename = "image/webp")]
+    Webp,
+}
+
+impl ImageMediaType {
+    pub fn from_mime_type(mime_type: &str) -> Result<Self> {
+        match mime_type.trim().to_ascii_lowercase().as_str() {
+            "image/jpeg" | "image/jpg" => Ok(Self::Jpeg),
+            "image/png" => Ok(Self::Png),
+            "image/gif" => Ok(Self::Gif),
+            "image/webp" => Ok(Self::Webp),
+            _ => Err(anyhow!("unsupported image type '{mime_type}'")),
+        }
+    }
+
+    /// Recognizes the format of an image from its first bytes.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some(Self::Webp)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Gif => "image/gif",
+            Self::Webp => "image/webp",
+        }
+    }
+}
+
+impl RequestContent {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text {
+            text: text.into(),
+            cache_control: None,
+        }
+    }
+
+    /// Creates an image block, checking that the data is in the format given
+    /// by `mime_type` and isn't larger than the API accepts.
+    pub fn image(mime_type: &str, data: impl Into<Base64Data>) -> Result<Self> {
+        let media_type = ImageMediaType::from_mime_type(mime_type)?;
+        let data = data.into();
+        let bytes = data.bytes()?;
+        if bytes.len() > MAX_IMAGE_SIZE {
+            bail!(
+                "image is {} bytes, but at most {MAX_IMAGE_SIZE} are supported",
+                bytes.len()
+            );
+        }
+        match ImageMediaType::detect(&bytes) {
+            Some(detected) if detected != media_type => bail!(
+                "image was declared as {} but is {}",
+                media_type.as_str(),
+                detected.as_str()
+            ),
+            _ => {}
+        }
+        drop(bytes);
+        Ok(Self::Image {
+            source: ImageSource::Base64 { media_type, data },
+            cache_control: None,
+        })
+    }
+
+    /// Creates an image block, detecting its format from its data.
+    pub fn image_from_bytes(bytes: impl Into<Vec<u8>>) -> Result<Self> {
+        let bytes = bytes.into();
+        let media_type =
+            ImageMediaType::detect(&bytes).ok_or_else(|| anyhow!("unrecognized image format"))?;
+        Self::image(media_type.as_str(), bytes)
+    }
+
+    /// Refers to an image uploaded with the files API.
+    pub fn image_file(file_id: impl Into<String>) -> Self {
+        Self::Image {
+            source: ImageSource::File {
+                file_id: file_id.into(),
+            },
+            cache_control: None,
+        }
+    }
+
+    /// Creates a PDF document block, checking that the data is a PDF that
+    /// isn't larger, and doesn't have more pages, than the API accepts.
+    pub fn pdf(data: impl Into<Base64Data>) -> Result<Self> {
+        let data = data.into();
+        let bytes = data.bytes()?;
+        if !bytes.starts_with(b"%PDF-") {
+            bail!("document isn't a PDF");
+        }
+        if bytes.len() > MAX_PDF_SIZE {
+            bail!(
+                "PDF is {} bytes, but at most {MAX_PDF_SIZE} are supported",
+                bytes.len()
+            );
+        }
+        if let Some(page_count) = pdf_page_count(&bytes) {
+            if page_count > MAX_PDF_PAGES {
+                bail!("PDF has {page_count} pages, but at most {MAX_PDF_PAGES} are supported");
+            }
+        }
+        drop(bytes);
+        Ok(Self::Document {
+            source: DocumentSource::Base64 {
+                media_type: DocumentMediaType::Pdf,
+                data,
+            },
+            title: None,
+            citations: None,
+            cache_control: None,
+        })
+    }
+
+    /// Creates a plain text document block, which unlike a text block can be
+    /// cited by the model.
+    pub fn text_document(text: impl Into<String>) -> Self {
+        Self::Document {
+            source: DocumentSource::Text {
+                media_type: DocumentMediaType::PlainText,
+                data: text.into(),
+            },
+            title: None,
+            citations: None,
+            cache_control: None,
+        }
+    }
+
+    /// Refers to a document uploaded with the files API.
+    pub fn document_file(file_id: impl Into<String>) -> Self {
+        Self::Document {
+            source: DocumentSource::File {
+                file_id: file_id.into(),
+            },
+            title: None,
+            citations: None,
+            cache_control: None,
+        }
+    }
+
+    pub fn tool_use(
+        id: impl Into<String>,
+        name: impl Into<String>,
+        input: serde_json::Value,
+    ) -> Self {
+        Self::ToolUse {
+            id: id.into(),
+            name: name.into(),
+            input,
+            cache_control: None,
+        }
+    }
+
+    /// Returns the output of the tool call with the given ID.
+    pub fn tool_result(tool_use_id: impl Into<String>, content: impl Into<MessageContent>) -> Self {
+        Self::ToolResult {
+            tool_use_id: tool_use_id.into(),
+            content: content.into(),
+            is_error: false,
+            cache_control: None,
+        }
+    }
+
+    /// Reports that the tool call with the given ID failed with `error`.
+    pub fn tool_error(tool_use_id: impl Into<String>, error: impl Into<String>) -> Self {
+        Self::ToolResult {
+            tool_use_id: tool_use_id.into(),
+            content: MessageContent::Text(error.into()),
+            is_error: true,
+            cache_control: None,
+        }
+    }
+
+    /// Sets the title of a document block. Other blocks are left unchanged.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        if let Self::Document { title: slot, .. } = &mut self {
+            *slot = Some(title.into());
+        }
+        self
+    }
+
+    /// Lets the model cite a document block, with [`crate::Citation`]s in the text
+    /// blocks of its response. Other blocks are left unchanged.
+    pub fn with_citations(mut self) -> Self {
+        if let Self::Document { citations, .. } = &mut self {
+            *citations = Some(CitationsConfig { enabled: true });
+        }
+        self
+    }
+
+    /// Returns the ID of the uploaded file the block refers to, if any.
+    pub fn file_id(&self) -> Option<&str> {
+        match self {
+            Self::Image {
+                source: ImageSource::File { file_id },
+                ..
+            }
+            | Self::Document {
+                source: DocumentSource::File { file_id },
+                ..
+            } => Some(file_id),
+            _ => None,
+        }
+    }
+
+    /// Returns the approximate size of the block once serialized.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Self::Text { text, .. } => text.len(),
+            Self::Image {
+                source: ImageSource::Base64 { data, .. },
+                ..
+            }
+            | Self::Document {
+                source: DocumentSource::Base64 { data, .. },
+                ..
+            } => data.encoded_len(),
+            Self::Image {
+                source: ImageSource::File { file_id },
+                ..
+            }
+            | Self::Document {
+                source: DocumentSource::File { file_id },
+                ..
+            } => file_id.len(),
+            Self::Document {
+                source: DocumentSource::Text { data, .. },
+                ..
+            } => data.len(),
+            Self::ToolUse {
+                id, name, input, ..
+            } => id.len() + name.len() + input.to_string().len(),
+            Self::ToolResult {
+                tool_use_id,
+                content,
+                ..
+            } => tool_use_id.len() + content.encoded_len(),
+        }
+    }
+
+    pub fn cache_control(&self) -> Option<CacheControl> {
+        match self {
+            Self::Text { cache_control, .. }
+            | Self::Image { cache_control, .. }
+            | Self::Document { cache_control, .. }
+            | Self::ToolUse { cache_control, .. }
+            | Self::ToolResult { cache_control, .. } => *cache_control,
+        }
+    }
+
+    pub fn set_cache_control(&mut self, cache_control: Option<CacheControl>) {
+        match self {
+            Self::Text {
+                cache_control: slot,
+                ..
+            }
+            | Self::Image {
+                cache_control: slot,
+                ..
+            }
+            | Self::Document {
+                cache_control: slot,
+                ..
+            }
+            | Self::ToolUse {
+                cache_control: slot,
+                ..
+            }
+            | Self::ToolResult {
+                cache_control: slot,
+                ..
+            } => *slot = cache_control,
+        }
+    }
+
+    pub fn with_cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.set_cache_control(Some(cache_control));
+        self
+    }
+}
+
+impl MessageContent {
+    /// Returns the text of the content, without its images and documents.
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            Self::Text(text) => Cow::Borrowed(text),
+            Self::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    RequestContent::Text { text, .. } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<String>()
+                .into(),
+        }
+    }
+
+    pub fn images(&self) -> impl Iterator<Item = &ImageSource> {
+        let blocks = match self {
+            Self::Text(_) => &[][..],
+            Self::Blocks(blocks) => blocks.as_slice(),
+        };
+        blocks.iter().filter_map(|block| match block {
+            RequestContent::Image { source, .. } => Some(source),
+            _ => None,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Text(text) => text.is_empty(),
+            Self::Blocks(blocks) => blocks.is_empty(),
+        }
+    }
+
+    /// Whether the content only has whitespace text.
+    pub fn is_blank(&self) -> bool {
+        self.is_text_only() && self.text().trim().is_empty()
+    }
+
+    fn is_text_only(&self) -> bool {
+        match self {
+            Self::Text(_) => true,
+            Self::Blocks(blocks) => blocks
+                .iter()
+                .all(|block| matches!(block, RequestContent::Text { .. })),
+        }
+    }
+
+    /// Whether the content includes a base64 PDF, which requires the
+    /// [`PDFS_BETA`].
+    pub fn has_pdfs(&self) -> bool {
+        match self {
+            Self::Text(_) => false,
+            Self::Blocks(blocks) => blocks.iter().any(|block| match block {
+                RequestContent::Document {
+                    source: DocumentSource::Base64 { .. },
+                    ..
+                } => true,
+                RequestContent::ToolResult { content, .. } => content.has_pdfs(),
+                _ => false,
+            }),
+        }
+    }
+
+    /// Whether any block of the content refers to an uploaded file, which
+    /// requires the [`FILES_API_BETA`].
+    pub fn uses_files(&self) -> bool {
+        match self {
+            Self::Text(_) => false,
+            Self::Blocks(blocks) => blocks.iter().any(|block| match block {
+                RequestContent::ToolResult { content, .. } => content.uses_files(),
+                block => block.file_id().is_some(),
+            }),
+        }
+    }
+
+    /// Returns the approximate size of the content once serialized.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Self::Text(text) => text.len(),
+            Self::Blocks(blocks) => blocks.iter().map(RequestContent::encoded_len).sum(),
+        }
+    }
+
+    /// Appends text to the last text block, or in a new one if the content
+    /// doesn't end with text.
+    pub fn push_str(&mut self, text: &str) {
+        match self {
+            Self::Text(content) => content.push_str(text),
+            Self::Blocks(blocks) => match blocks.last_mut() {
+                Some(RequestContent::Text { text: last, .. }) => last.push_str(text),
+                _ => blocks.push(RequestContent::text(text)),
+            },
+        }
+    }
+
+    /// Appends `other`, separating text from the existing text with a blank
+    /// line.
+    pub fn merge(&mut self, other: MessageContent) {
+        if other.is_empty() {
+            return;
+        }
+        if self.is_empty() {
+            *self = other;
+            return;
+        }
+        match (&mut *self, other) {
+            (Self::Text(text), Self::Text(other)) => {
+                text.push_str("\n\n");
+                text.push_str(&other);
+            }
+            (this, other) => {
+                let mut blocks = this.take_blocks();
+                blocks.extend(other.into_blocks());
+                *this = Self::Blocks(blocks);
+            }
+        }
+    }
+
+    /// Removes whitespace from the end of the content's last text block.
+    pub fn truncate_trailing_whitespace(&mut self) {
+        let text = match self {
+            Self::Text(text) => text,
+            Self::Blocks(blocks) => match blocks.last_mut() {
+                Some(RequestContent::Text { text, .. }) => text,
+                _ => return,
+            },
+        };
+        text.truncate(text.trim_end().len());
+    }
+
+    pub fn into_blocks(self) -> Vec<RequestContent> {
+        match self {
+            Self::Text(text) if text.is_empty() => Vec::new(),
+            Self::Text(text) => vec![RequestContent::text(text)],
+            Self::Blocks(blocks) => blocks,
+        }
+    }
+
+    /// Whether any block of the content is marked with a [`CacheControl`].
+    pub fn has_cache_control(&self) -> bool {
+        match self {
+            Self::Text(_) => false,
+            Self::Blocks(blocks) => blocks.iter().any(|block| block.cache_control().is_some()),
+        }
+    }
+
+    /// Marks the content's last block as the end of a cached prefix,
+    /// converting plain text content to a text block. Empty content is left
+    /// unchanged, since the API rejects empty text blocks.
+    pub fn set_cache_control(&mut self, cache_control: CacheControl) {
+        let mut blocks = self.take_blocks();
+        if let Some(block) = blocks.last_mut() {
+            block.set_cache_control(Some(cache_control));
+            *self = Self::Blocks(blocks);
+        }
+    }
+
+    fn take_blocks(&mut self) -> Vec<RequestContent> {
+        std::mem::take(self).into_blocks()
+    }
+}
+
+/// Counts the page objects of a PDF, or returns `None` if there seem to be
+/// none, e.g. because they're in compressed object streams.
+fn pdf_page_count(bytes: &[u8]) -> Option<usize> {
+    const TYPE: &[u8] = b"/Type";
+    const PAGE: &[u8] = b"/Page";
+    let mut count = 0;
+    let mut ix = 0;
+    while let Some(offset) = bytes[ix..]
+        .windows(TYPE.len())
+        .position(|window| window == TYPE)
+    {
+        ix += offset + TYPE.len();
+        let rest = &bytes[ix..];
+        let rest = &rest[rest
+            .iter()
+            .position(|byte| !byte.is_ascii_whitespace())
+            .unwrap_or(rest.len())..];
+        // Skip `/Pages`, the nodes of the page tree.
+        if rest.starts_with(PAGE) && !rest[PAGE.len()..].starts_with(b"s") {
+            count += 1;
+        }
+    }
+    (count > 0).then_some(count)
+}
+
+impl Default for MessageContent {
+    fn default() -> Self {
+        Self::Text(String::new())
+    }
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for MessageContent {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_string())
+    }
+}
+
+impl From<Vec<RequestContent>> for MessageContent {
+    fn from(blocks: Vec<RequestContent>) -> Self {
+        Self::Blocks(blocks)
+    }
+}
+
+impl PartialEq<str> for MessageContent {
+    fn eq(&self, other: &str) -> bool {
+        self.is_text_only() && self.text() == other
+    }
+}
+
+impl PartialEq<&str> for MessageContent {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_content() {
+        let png = b"\x89PNG\r\n\x1a\n....".to_vec();
+        let image = RequestContent::image_from_bytes(png.clone()).unwrap();
+        assert!(RequestContent::image("image/jpeg", png.clone()).is_err());
+        assert!(RequestContent::image("image/tiff", png).is_err());
+        assert!(RequestContent::image_from_bytes(b"hello".to_vec()).is_err());
+
+        let mut content = MessageContent::from("Describe this:");
+        content.merge(MessageContent::Blocks(vec![image.clone()]));
+        content.push_str("Briefly.");
+        assert_eq!(content.text(), "Describe this:Briefly.");
+        assert_eq!(content.images().count(), 1);
+        assert!(!content.is_blank());
+
+        let json = serde_json::to_value(&content).unwrap();
+        assert_eq!(json[1]["type"], "image");
+        assert_eq!(json[1]["source"]["type"], "base64");
+        assert_eq!(json[1]["source"]["media_type"], "image/png");
+        assert_eq!(json[1]["source"]["data"], "iVBORw0KGgouLi4u");
+        assert_eq!(
+            serde_json::to_value(MessageContent::from("Hi")).unwrap(),
+            "Hi"
+        );
+
+        let content = MessageContent::from(vec![RequestContent::document_file("file_01")]);
+        assert!(!content.is_blank());
+        assert!(content.uses_files());
+        assert_eq!(
+            serde_json::to_value(&content).unwrap()[0]["source"],
+            serde_json::json!({"type": "file", "file_id": "file_01"})
+        );
+
+        let pdf = b"%PDF-1.4\n1 0 obj << /Type /Pages /Count 2 >>\n\
+            2 0 obj << /Type /Page >>\n3 0 obj << /Type/Page >>"
+            .to_vec();
+        assert_eq!(pdf_page_count(&pdf), Some(2));
+        assert_eq!(pdf_page_count(b"%PDF-1.7\n"), None);
+        assert!(RequestContent::pdf(pdf).is_ok());
+        assert!(RequestContent::pdf(b"hello".to_vec()).is_err());
+        let many_pages = format!("%PDF-1.4\n{}", "<< /Type /Page >>".repeat(101));
+        assert!(RequestContent::pdf(many_pages.into_bytes()).is_err());
+
+        let mut content = MessageContent::from("Some long file");
+        assert!(!content.has_cache_control());
+        content.set_cache_control(CacheControl::Ephemeral);
+        assert!(content.has_cache_control());
+        assert_eq!(
+            serde_json::to_value(&content).unwrap(),
+            serde_json::json!([{
+                "type": "text",
+                "text": "Some long file",
+                "cache_control": {"type": "ephemeral"}
+            }])
+        );
+
+        let mut content = MessageContent::from("");
+        content.set_cache_control(CacheControl::Ephemeral);
+        assert!(content.is_empty());
+        assert!(!content.has_cache_control());
+    }
+
+    #[test]
+    fn test_tool_content() {
+        let tool_use = RequestContent::tool_use(
+            "toolu_01",
+            "get_weather",
+            serde_json::json!({"city": "Paris"}),
+        );
+        assert_eq!(
+            serde_json::to_value(&tool_use).unwrap(),
+            serde_json::json!({
+                "type": "tool_use",
+                "id": "toolu_01",
+                "name": "get_weather",
+                "input": {"city": "Paris"}
+            })
+        );
+
+        let result = MessageContent::Blocks(vec![
+            RequestContent::tool_result("toolu_01", "Sunny"),
+            RequestContent::tool_error("toolu_02", "City not found"),
+        ]);
+        assert!(!result.is_blank());
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            serde_json::json!([
+                {"type": "tool_result", "tool_use_id": "toolu_01", "content": "Sunny"},
+                {
+                    "type": "tool_result",
+                    "tool_use_id": "toolu_02",
+                    "content": "City not found",
+                    "is_error": true
+                }
+            ])
+        );
+        let parsed: MessageContent =
+            serde_json::from_value(serde_json::to_value(&result).unwrap()).unwrap();
+        assert_eq!(parsed, result);
+    }
+}
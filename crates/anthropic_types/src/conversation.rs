@@ -0,0 +1,645 @@
+use crate::{
+    estimate_message_tokens, estimate_request_tokens, estimate_tokens, AnnotatedMessages,
+    EncodedPrefix, MessageContent, Model, Request, RequestBuilder, RequestContent, RequestMessage,
+    Role, Tool, ToolChoice,
+};
+use anyhow::{anyhow, Result};
+use futures::lock::{Mutex, OwnedMutexGuard};
+use std::{fmt, mem, sync::Arc};
+
+/// The text that replaces the content of messages elided with
+/// [`Truncation::ElideOldest`].
+pub const ELIDED_MESSAGE: &str = "[earlier message omitted]";
+
+/// Parameters applied to every request made from a [`Conversation`].
+#[derive(Clone, Debug, Default)]
+pub struct RequestDefaults {
+    pub model: Option<Model>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    pub stop_sequences: Vec<String>,
+    pub max_tokens: Option<u32>,
+    /// The thinking budget, see [`RequestBuilder::thinking`].
+    pub thinking_budget: Option<u32>,
+    pub betas: Vec<String>,
+    pub tools: Vec<Tool>,
+    pub tool_choice: Option<ToolChoice>,
+}
+
+/// Parameters that take precedence over a conversation's [`RequestDefaults`]
+/// for a single request.
+#[derive(Clone, Debug, Default)]
+pub struct RequestOverrides {
+    pub model: Option<Model>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    /// Replaces the default stop sequences when set.
+    pub stop_sequences: Option<Vec<String>>,
+    pub max_tokens: Option<u32>,
+    pub thinking_budget: Option<u32>,
+    /// Replaces the default betas when set.
+    pub betas: Option<Vec<String>>,
+    /// Replaces the default tools when set.
+    pub tools: Option<Vec<Tool>>,
+    pub tool_choice: Option<ToolChoice>,
+}
+
+/// How a [`Conversation`] leaves its oldest turns out of requests that
+/// wouldn't fit the model's context window otherwise. The conversation itself
+/// keeps every turn.
+///
+/// Turns are left out in exchanges ending right before a user message that
+/// doesn't carry tool results, so roles keep alternating and every tool
+/// result follows its tool use. The last user message and anything after it
+/// are always sent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Truncation {
+    /// Send every turn, even if the request will be rejected as too long.
+    #[default]
+    Disabled,
+    /// Leave the oldest exchanges out of the request.
+    DropOldest,
+    /// Replace the content of the oldest exchanges with [`ELIDED_MESSAGE`],
+    /// so the model knows some context is missing, and only drop them if
+    /// that's not enough.
+    ElideOldest,
+}
+
+/// The history of a multi-turn exchange, along with the parameters used to
+/// continue it.
+#[derive(Clone, Debug, Default)]
+pub struct Conversation {
+    system: String,
+    messages: AnnotatedMessages,
+    defaults: RequestDefaults,
+    profile: Option<String>,
+    truncation: Truncation,
+    sending: bool,
+    /// Incremented whenever the system prompt or messages may have been
+    /// changed in place, which invalidates `encoded_prefix`.
+    generation: u64,
+    encoded_prefix: Option<Arc<EncodedPrefix>>,
+}
+
+/// The error returned when starting a send on a [`Conversation`] that is still
+/// waiting for the response to a previous one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConversationBusy;
+
+impl fmt::Display for ConversationBusy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the conversation is already waiting for a response")
+    }
+}
+
+impl std::error::Error for ConversationBusy {}
+
+/// A send started with [`Conversation::begin_send`], which must be completed
+/// or aborted before another one can start.
+#[derive(Debug)]
+#[must_use]
+pub struct PendingSend {
+    message_count: usize,
+}
+
+impl Conversation {
+    pub fn new(defaults: RequestDefaults) -> Self {
+        Self {
+            defaults,
+            ..Default::default()
+        }
+    }
+
+    pub fn defaults(&self) -> &RequestDefaults {
+        &self.defaults
+    }
+
+    pub fn defaults_mut(&mut self) -> &mut RequestDefaults {
+        &mut self.defaults
+    }
+
+    /// The name of the client profile requests from this conversation are
+    /// sent with, if it doesn't use the default one.
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    pub fn set_profile(&mut self, profile: Option<String>) {
+        self.profile = profile;
+    }
+
+    pub fn truncation(&self) -> Truncation {
+        self.truncation
+    }
+
+    pub fn set_truncation(&mut self, truncation: Truncation) {
+        self.truncation = truncation;
+    }
+
+    /// Estimates the number of tokens the system prompt and messages take up,
+    /// preferring the token counts set on [`Conversation::messages_mut`],
+    /// e.g. from the usage reported by the API.
+    pub fn token_usage(&self) -> usize {
+        estimate_tokens(&self.system)
+            + (0..self.messages.len())
+                .map(|ix| self.message_tokens(ix))
+                .sum::<usize>()
+    }
+
+    fn message_tokens(&self, ix: usize) -> usize {
+        self.messages
+            .cached_token_count(ix)
+            .unwrap_or_else(|| estimate_message_tokens(&self.messages.messages()[ix]))
+    }
+
+    pub fn system(&self) -> &str {
+        &self.system
+    }
+
+    pub fn set_system(&mut self, system: impl Into<String>) {
+        self.system = system.into();
+        self.generation += 1;
+    }
+
+    pub fn messages(&self) -> &AnnotatedMessages {
+        &self.messages
+    }
+
+    pub fn messages_mut(&mut self) -> &mut AnnotatedMessages {
+        self.generation += 1;
+        &mut self.messages
+    }
+
+    pub fn push(&mut self, message: RequestMessage) {
+        self.messages.push(message);
+    }
+
+    pub fn push_user(&mut self, content: impl Into<MessageContent>) {
+        self.push(RequestMessage::user(content));
+    }
+
+    pub fn push_assistant(&mut self, content: impl Into<MessageContent>) {
+        self.push(RequestMessage::assistant(content));
+    }
+
+    pub fn is_sending(&self) -> bool {
+        self.sending
+    }
+
+    /// Starts a send, returning the request to make. Fails with
+    /// [`ConversationBusy`] if a previous send hasn't been completed or
+    /// aborted yet, since interleaving turns would break the alternation of
+    /// roles.
+    ///
+    /// The request carries the [`EncodedPrefix`] of its system prompt and
+    /// all messages but the last, which later sends reuse until the system
+    /// prompt is set or the messages are changed with
+    /// [`Conversation::messages_mut`].
+    pub fn begin_send(&mut self, overrides: RequestOverrides) -> Result<(Request, PendingSend)> {
+        if self.sending {
+            return Err(ConversationBusy.into());
+        }
+        let (mut request, truncated) = self.build_request(overrides)?;
+        if !truncated {
+            self.encode_prefix(&mut request)?;
+        }
+        self.sending = true;
+        let pending = PendingSend {
+            message_count: self.messages.len(),
+        };
+        Ok((request, pending))
+    }
+
+    /// Records the response to a send, right after the messages it responds
+    /// to.
+    pub fn complete_send(&mut self, pending: PendingSend, response: RequestMessage) {
+        self.sending = false;
+        let ix = pending.message_count.min(self.messages.len());
+        self.messages.insert(ix, response);
+    }
+
+    /// Ends a send without a response, e.g. because the request failed.
+    pub fn abort_send(&mut self, _pending: PendingSend) {
+        self.sending = false;
+    }
+
+    /// Builds a request continuing the conversation with its defaults.
+    pub fn request(&self) -> Result<Request> {
+        self.request_with(RequestOverrides::default())
+    }
+
+    /// Builds a request continuing the conversation, with `overrides` taking
+    /// precedence over its defaults.
+    ///
+    /// Unless truncation is [`Truncation::Disabled`], the oldest turns are
+    /// left out so that the request and its `max_tokens` fit the model's
+    /// context window, failing if that's impossible.
+    pub fn request_with(&self, overrides: RequestOverrides) -> Result<Request> {
+        let (mut request, truncated) = self.build_request(overrides)?;
+        if !truncated {
+            request.encoded_prefix = self
+                .encoded_prefix
+                .clone()
+                .filter(|prefix| self.is_prefix_of(prefix, &request));
+        }
+        Ok(request)
+    }
+
+    /// Builds a request continuing the conversation, and returns whether
+    /// turns were left out of it.
+    fn build_request(&self, overrides: RequestOverrides) -> Result<(Request, bool)> {
+        let mut builder = RequestBuilder::default().system(self.system.clone());
+        if let Some(model) = overrides.model.or_else(|| self.defaults.model.clone()) {
+            builder = builder.model(model);
+        }
+        if let Some(temperature) = overrides.temperature.or(self.defaults.temperature) {
+            builder = builder.temperature(temperature);
+        }
+        if let Some(top_p) = overrides.top_p.or(self.defaults.top_p) {
+            builder = builder.top_p(top_p);
+        }
+        if let Some(top_k) = overrides.top_k.or(self.defaults.top_k) {
+            builder = builder.top_k(top_k);
+        }
+        builder = builder.stop_sequences(
+            overrides
+                .stop_sequences
+                .unwrap_or_else(|| self.defaults.stop_sequences.clone()),
+        );
+        if let Some(max_tokens) = overrides.max_tokens.or(self.defaults.max_tokens) {
+            builder = builder.max_tokens(max_tokens);
+        }
+        if let Some(budget) = overrides.thinking_budget.or(self.defaults.thinking_budget) {
+            builder = builder.thinking(budget);
+        }
+        for beta in overrides
+            .betas
+            .unwrap_or_else(|| self.defaults.betas.clone())
+        {
+            builder = builder.beta(beta);
+        }
+        builder = builder.tools(
+            overrides
+                .tools
+                .unwrap_or_else(|| self.defaults.tools.clone()),
+        );
+        if let Some(tool_choice) = overrides
+            .tool_choice
+            .or_else(|| self.defaults.tool_choice.clone())
+        {
+            builder = builder.tool_choice(tool_choice);
+        }
+        let mut request = builder
+            .clone()
+            .messages(self.messages.messages().iter().cloned())
+            .build()?;
+        if self.truncation == Truncation::Disabled {
+            return Ok((request, false));
+        }
+        // Truncation works on the conversation's messages rather than the
+        // request's, since building may merge or insert messages, and the
+        // result is normalized again by building it.
+        let messages = mem::take(&mut request.messages);
+        match self.truncated_messages(&request)? {
+            Some(truncated) => Ok((builder.messages(truncated).build()?, true)),
+            None => {
+                request.messages = messages;
+                Ok((request, false))
+            }
+        }
+    }
+
+    /// Whether `prefix` was encoded from the first messages of `request`,
+    /// excluding the last one, which later messages can still be merged into.
+    fn is_prefix_of(&self, prefix: &EncodedPrefix, request: &Request) -> bool {
+        prefix.generation == self.generation && prefix.message_count() < request.messages.len()
+    }
+
+    /// Sets the encoded prefix of `request`, which was built without
+    /// truncation, encoding only the messages the previous one lacks.
+    fn encode_prefix(&mut self, request: &mut Request) -> Result<()> {
+        let mut prefix = match self.encoded_prefix.take() {
+            Some(prefix) if self.is_prefix_of(&prefix, request) => prefix,
+            _ => Arc::new(EncodedPrefix::new(&request.system, self.generation)?),
+        };
+        let stable_count = request.messages.len() - 1;
+        if prefix.message_count() < stable_count {
+            let prefix = Arc::make_mut(&mut prefix);
+            for message in &request.messages[prefix.message_count()..stable_count] {
+                prefix.push_message(message)?;
+            }
+        }
+        request.encoded_prefix = Some(prefix.clone());
+        self.encoded_prefix = Some(prefix);
+        Ok(())
+    }
+
+    /// Picks the messages to send in `request`, whose own messages have been
+    /// cleared so only the tokens of its other parts are counted, or returns
+    /// `None` if all of them fit.
+    fn truncated_messages(&self, request: &Request) -> Result<Option<Vec<RequestMessage>>> {
+        let messages = self.messages.messages();
+        let fixed_tokens = estimate_request_tokens(request);
+        let budget = request
+            .model
+            .max_token_count()
+            .saturating_sub(request.max_tokens as usize);
+        let mut message_tokens: Vec<usize> = (0..messages.len())
+            .map(|ix| self.message_tokens(ix))
+            .collect();
+        let mut total = fixed_tokens + message_tokens.iter().sum::<usize>();
+        if total <= budget {
+            return Ok(None);
+        }
+
+        let protected = messages
+            .iter()
+            .rposition(|message| message.role == Role::User)
+            .unwrap_or(0);
+        let boundaries: Vec<usize> = (1..=protected)
+            .filter(|&ix| {
+                let message = &messages[ix];
+                message.role == Role::User && !has_tool_results(&message.content)
+            })
+            .collect();
+
+        let mut elided = 0;
+        if self.truncation == Truncation::ElideOldest {
+            let elided_tokens = estimate_message_tokens(&RequestMessage::user(ELIDED_MESSAGE));
+            for &end in &boundaries {
+                if total <= budget {
+                    break;
+                }
+                for ix in elided..end {
+                    total = total - message_tokens[ix] + elided_tokens;
+                    message_tokens[ix] = elided_tokens;
+                }
+                elided = end;
+            }
+        }
+
+        let mut dropped = 0;
+        for end in boundaries {
+            if total <= budget {
+                break;
+            }
+            total -= message_tokens[dropped..end].iter().sum::<usize>();
+            dropped = end;
+        }
+        if total > budget {
+            return Err(anyhow!(
+                "the conversation doesn't fit in the context window of {}",
+                request.model.display_name()
+            ));
+        }
+        Ok(Some(
+            messages
+                .iter()
+                .enumerate()
+                .skip(dropped)
+                .map(|(ix, message)| {
+                    let mut message = message.clone();
+                    if ix < elided {
+                        message.content = ELIDED_MESSAGE.into();
+                    }
+                    message
+                })
+                .collect(),
+        ))
+    }
+}
+
+fn has_tool_results(content: &MessageContent) -> bool {
+    match content {
+        MessageContent::Text(_) => false,
+        MessageContent::Blocks(blocks) => blocks
+            .iter()
+            .any(|block| matches!(block, RequestContent::ToolResult { .. })),
+    }
+}
+
+/// Queues the sends of a conversation shared between tasks, so that each one
+/// waits for the previous one to complete instead of failing with
+/// [`ConversationBusy`].
+#[derive(Clone, Debug, Default)]
+pub struct SendQueue(Arc<Mutex<()>>);
+
+pub type SendPermit = OwnedMutexGuard<()>;
+
+impl SendQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits for the sends queued before this one. The returned permit should
+    /// be held until the send has completed.
+    pub async fn acquire(&self) -> SendPermit {
+        self.0.clone().lock_owned().await
+    }
+
+    pub fn try_acquire(&self) -> Result<SendPermit, ConversationBusy> {
+        self.0.clone().try_lock_owned().ok_or(ConversationBusy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_defaults_and_overrides() {
+        let mut conversation = Conversation::new(RequestDefaults {
+            model: Some(Model::Claude3Haiku),
+            temperature: Some(0.5),
+            max_tokens: None,
+            betas: vec!["beta-a".into()],
+            ..Default::default()
+        });
+        conversation.set_system("Be brief.");
+        conversation.push_user("Hello");
+
+        let request = conversation.request().unwrap();
+        assert_eq!(request.model, Model::Claude3Haiku);
+        assert_eq!(request.temperature, Some(0.5));
+        assert_eq!(request.system, "Be brief.");
+        assert_eq!(request.betas, vec!["beta-a".to_string()]);
+
+        let request = conversation
+            .request_with(RequestOverrides {
+                model: Some(Model::Claude3Opus),
+                max_tokens: Some(100),
+                betas: Some(Vec::new()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(request.model, Model::Claude3Opus);
+        assert_eq!(request.temperature, Some(0.5));
+        assert_eq!(request.max_tokens, 100);
+        assert!(request.betas.is_empty());
+
+        assert!(Conversation::default().request().is_err());
+    }
+
+    #[test]
+    fn test_overlapping_sends() {
+        let mut conversation = Conversation::new(RequestDefaults {
+            model: Some(Model::Claude3Haiku),
+            ..Default::default()
+        });
+        conversation.push_user("Hello");
+
+        let (_, pending) = conversation.begin_send(Default::default()).unwrap();
+        let error = conversation.begin_send(Default::default()).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ConversationBusy>(),
+            Some(&ConversationBusy)
+        );
+
+        conversation.complete_send(pending, RequestMessage::assistant("Hi"));
+        assert!(!conversation.is_sending());
+        conversation.push_user("Again");
+        let (request, pending) = conversation.begin_send(Default::default()).unwrap();
+        assert_eq!(request.messages.len(), 3);
+        conversation.abort_send(pending);
+        assert!(!conversation.is_sending());
+    }
+
+    #[test]
+    fn test_encoded_prefix() {
+        let mut conversation = Conversation::new(RequestDefaults {
+            model: Some(Model::Claude3Haiku),
+            ..Default::default()
+        });
+        conversation.set_system("Be brief.");
+        conversation.push_user("u1");
+        let encode = |request: &Request| serde_json::to_string(request).unwrap();
+        let encode_plain = |request: &Request| {
+            encode(&Request {
+                encoded_prefix: None,
+                ..request.clone()
+            })
+        };
+
+        let (request, pending) = conversation.begin_send(Default::default()).unwrap();
+        assert_eq!(request.encoded_prefix.as_ref().unwrap().message_count(), 0);
+        assert_eq!(encode(&request), encode_plain(&request));
+        conversation.complete_send(pending, RequestMessage::assistant("a1"));
+        conversation.push_user("u2");
+
+        // Only the messages added since the previous send are encoded, and
+        // the prefix is reused as is while nothing is added.
+        let (request, pending) = conversation.begin_send(Default::default()).unwrap();
+        let prefix = request.encoded_prefix.clone().unwrap();
+        assert_eq!(prefix.message_count(), 2);
+        assert_eq!(encode(&request), encode_plain(&request));
+        conversation.abort_send(pending);
+        let request = conversation.request().unwrap();
+        assert!(Arc::ptr_eq(
+            request.encoded_prefix.as_ref().unwrap(),
+            &prefix
+        ));
+
+        // A prefix no longer matching the messages is ignored.
+        let mut stale = request.clone();
+        stale.messages.pop();
+        assert_eq!(encode(&stale), encode_plain(&stale));
+
+        conversation.set_system("Be thorough.");
+        assert!(conversation.request().unwrap().encoded_prefix.is_none());
+        let (request, _) = conversation.begin_send(Default::default()).unwrap();
+        assert!(!Arc::ptr_eq(
+            request.encoded_prefix.as_ref().unwrap(),
+            &prefix
+        ));
+        assert!(encode(&request).contains("Be thorough."));
+        assert_eq!(encode(&request), encode_plain(&request));
+    }
+
+    #[test]
+    fn test_truncation() {
+        let mut conversation = Conversation::new(RequestDefaults {
+            model: Some(Model::Custom {
+                name: "claude-small".into(),
+                max_tokens: Some(100),
+                max_output_tokens: None,
+            }),
+            max_tokens: Some(10),
+            ..Default::default()
+        });
+        conversation.push_user("u1");
+        conversation.push_assistant("a1");
+        conversation.push_user("u2");
+        conversation.push_assistant("a2");
+        conversation.push_user("u3");
+        for ix in 0..5 {
+            conversation.messages_mut().set_token_count(ix, 30);
+        }
+        assert_eq!(conversation.token_usage(), 150);
+        assert_eq!(conversation.request().unwrap().messages.len(), 5);
+
+        conversation.set_truncation(Truncation::DropOldest);
+        let request = conversation.request().unwrap();
+        assert_eq!(request.messages.len(), 3);
+        assert_eq!(request.messages[0].content, "u2");
+
+        conversation.set_truncation(Truncation::ElideOldest);
+        let request = conversation.request().unwrap();
+        assert_eq!(request.messages.len(), 5);
+        assert_eq!(request.messages[0].content, ELIDED_MESSAGE);
+        assert_eq!(request.messages[3].content, ELIDED_MESSAGE);
+        assert_eq!(request.messages[4].content, "u3");
+
+        // Tool results are never separated from their tool use.
+        conversation.set_truncation(Truncation::DropOldest);
+        conversation.messages_mut().update(2, |message| {
+            message.content = vec![RequestContent::tool_result("toolu_1", "42")].into();
+        });
+        conversation.messages_mut().set_token_count(2, 30);
+        let request = conversation.request().unwrap();
+        assert_eq!(request.messages.len(), 1);
+        assert_eq!(request.messages[0].content, "u3");
+
+        conversation.messages_mut().set_token_count(4, 200);
+        assert!(conversation.request().is_err());
+    }
+
+    #[test]
+    fn test_truncation_before_normalization() {
+        let mut conversation = Conversation::new(RequestDefaults {
+            model: Some(Model::Custom {
+                name: "claude-small".into(),
+                max_tokens: Some(100),
+                max_output_tokens: None,
+            }),
+            max_tokens: Some(10),
+            ..Default::default()
+        });
+        conversation.set_truncation(Truncation::DropOldest);
+
+        // Building prepends a user message to conversations starting with the
+        // assistant, which mustn't shift the token counts used.
+        conversation.push_assistant("a0");
+        conversation.push_user("u1");
+        conversation.push_assistant("a1");
+        conversation.push_user("u2");
+        for ix in 0..4 {
+            conversation.messages_mut().set_token_count(ix, 30);
+        }
+        let request = conversation.request().unwrap();
+        assert_eq!(request.messages.len(), 3);
+        assert_eq!(request.messages[0].content, "u1");
+
+        // Consecutive user turns are merged when building, but are dropped
+        // separately.
+        conversation
+            .messages_mut()
+            .update(0, |message| message.role = Role::User);
+        conversation.messages_mut().set_token_count(0, 30);
+        let request = conversation.request().unwrap();
+        assert_eq!(request.messages.len(), 3);
+        assert_eq!(request.messages[0].content, "u1");
+        assert_eq!(request.messages[2].content, "u2");
+    }
+}
@@ -0,0 +1,159 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{borrow::Cow, fmt, ops::Deref};
+
+const INLINE_CAPACITY: usize = 22;
+
+/// The text carried by a streamed delta.
+///
+/// Deltas are typically only a few characters long, so short ones are stored
+/// inline instead of in their own heap allocation.
+#[derive(Clone)]
+pub struct DeltaText(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Inline {
+        len: u8,
+        bytes: [u8; INLINE_CAPACITY],
+    },
+    Heap(String),
+}
+
+impl DeltaText {
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            // Inline bytes are always copied from a `&str`, so they're valid UTF-8.
+            Repr::Inline { len, bytes } => {
+                std::str::from_utf8(&bytes[..*len as usize]).unwrap_or_default()
+            }
+            Repr::Heap(text) => text,
+        }
+    }
+
+    pub fn into_string(self) -> String {
+        match self.0 {
+            Repr::Inline { len, bytes } => {
+                String::from_utf8_lossy(&bytes[..len as usize]).into_owned()
+            }
+            Repr::Heap(text) => text,
+        }
+    }
+}
+
+impl Default for DeltaText {
+    fn default() -> Self {
+        Self::from("")
+    }
+}
+
+impl From<&str> for DeltaText {
+    fn from(text: &str) -> Self {
+        if text.len() <= INLINE_CAPACITY {
+            let mut bytes = [0; INLINE_CAPACITY];
+            bytes[..text.len()].copy_from_slice(text.as_bytes());
+            Self(Repr::Inline {
+                len: text.len() as u8,
+                bytes,
+            })
+        } else {
+            Self(Repr::Heap(text.to_owned()))
+        }
+    }
+}
+
+impl From<String> for DeltaText {
+    fn from(text: String) -> Self {
+        Self(Repr::Heap(text))
+    }
+}
+
+impl From<Cow<'_, str>> for DeltaText {
+    fn from(text: Cow<'_, str>) -> Self {
+        match text {
+            Cow::Borrowed(text) => Self::from(text),
+            Cow::Owned(text) => Self::from(text),
+        }
+    }
+}
+
+impl From<DeltaText> for String {
+    fn from(text: DeltaText) -> Self {
+        text.into_string()
+    }
+}
+
+impl Deref for DeltaText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for DeltaText {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for DeltaText {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for DeltaText {}
+
+impl PartialEq<str> for DeltaText {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for DeltaText {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for DeltaText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for DeltaText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl Serialize for DeltaText {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for DeltaText {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = DeltaText;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(DeltaText::from(value))
+            }
+
+            fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+                Ok(DeltaText::from(value))
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
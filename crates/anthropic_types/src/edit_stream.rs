@@ -0,0 +1,313 @@
+use crate::ResponseEvent;
+
+/// A hunk of a unified diff.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiffHunk {
+    /// The file named by the preceding `+++` header, if any.
+    pub path: Option<String>,
+    /// The line the hunk starts at in the original file, if the hunk header
+    /// specified it.
+    pub old_start: Option<u32>,
+    pub new_start: Option<u32>,
+    pub old_text: String,
+    pub new_text: String,
+}
+
+/// A search/replace block:
+///
+/// ```text
+/// path/to/file.rs
+/// <<<<<<< SEARCH
+/// old text
+/// =======
+/// new text
+/// >>>>>>> REPLACE
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchReplace {
+    /// The file named on the line preceding the block, if any.
+    pub path: Option<String>,
+    pub search: String,
+    pub replace: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EditEvent {
+    /// A unified diff for the given file started.
+    File {
+        path: String,
+    },
+    Hunk(DiffHunk),
+    SearchReplace(SearchReplace),
+}
+
+const SEARCH_MARKER: &str = "<<<<<<< SEARCH";
+const DIVIDER_MARKER: &str = "=======";
+const REPLACE_MARKER: &str = ">>>>>>> REPLACE";
+
+enum State {
+    Text,
+    Hunk {
+        hunk: DiffHunk,
+        /// The number of old and new lines still expected, if the header
+        /// specified them.
+        remaining: Option<(u32, u32)>,
+    },
+    Search(SearchReplace),
+    Replace(SearchReplace),
+}
+
+/// Recognizes unified diff hunks and search/replace blocks in streamed text,
+/// emitting each edit as soon as it's complete so that it can be applied
+/// while the rest of the response streams in.
+pub struct EditParser {
+    state: State,
+    /// The incomplete last line of the text pushed so far.
+    line: String,
+    /// The file named by the last `+++` header.
+    diff_path: Option<String>,
+    /// The last non-empty line of text outside of an edit, which names the
+    /// file of a following search/replace block.
+    last_text_line: Option<String>,
+}
+
+impl Default for EditParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EditParser {
+    pub fn new() -> Self {
+        Self {
+            state: State::Text,
+            line: String::new(),
+            diff_path: None,
+            last_text_line: None,
+        }
+    }
+
+    pub fn push(&mut self, text: &str) -> Vec<EditEvent> {
+        let mut events = Vec::new();
+        self.line.push_str(text);
+        while let Some(newline_ix) = self.line.find('\n') {
+            let line = self.line[..newline_ix].trim_end_matches('\r').to_string();
+            self.line.drain(..=newline_ix);
+            self.push_line(&line, &mut events);
+        }
+        events
+    }
+
+    /// Feeds the text carried by `event`, if any.
+    pub fn push_event(&mut self, event: &ResponseEvent) -> Vec<EditEvent> {
+        match event.text() {
+            Some(text) => self.push(text),
+            None => Vec::new(),
+        }
+    }
+
+    /// Processes the last line, and emits a hunk that was still open. An
+    /// unterminated search/replace block is discarded.
+    pub fn finish(mut self) -> Vec<EditEvent> {
+        let mut events = Vec::new();
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.push_line(&line, &mut events);
+        }
+        if let State::Hunk { hunk, .. } = self.state {
+            events.push(EditEvent::Hunk(hunk));
+        }
+        events
+    }
+
+    fn push_line(&mut self, line: &str, events: &mut Vec<EditEvent>) {
+        match std::mem::replace(&mut self.state, State::Text) {
+            State::Text => self.push_text_line(line, events),
+            State::Hunk {
+                mut hunk,
+                mut remaining,
+            } => {
+                let (old, new) = match line.chars().next() {
+                    Some(' ') => (true, true),
+                    Some('-') => (true, false),
+                    Some('+') => (false, true),
+                    // Models sometimes drop the space of empty context lines.
+                    None if remaining.is_some() => (true, true),
+                    Some('\\') => {
+                        // "\ No newline at end of file"
+                        self.state = State::Hunk { hunk, remaining };
+                        return;
+                    }
+                    _ => (false, false),
+                };
+                if !old && !new {
+                    events.push(EditEvent::Hunk(hunk));
+                    self.push_text_line(line, events);
+                    return;
+                }
+
+                let content = line.get(1..).unwrap_or("");
+                if old {
+                    hunk.old_text.push_str(content);
+                    hunk.old_text.push('\n');
+                }
+                if new {
+                    hunk.new_text.push_str(content);
+                    hunk.new_text.push('\n');
+                }
+                if let Some((old_remaining, new_remaining)) = &mut remaining {
+                    *old_remaining = old_remaining.saturating_sub(old as u32);
+                    *new_remaining = new_remaining.saturating_sub(new as u32);
+                    if *old_remaining == 0 && *new_remaining == 0 {
+                        events.push(EditEvent::Hunk(hunk));
+                        return;
+                    }
+                }
+                self.state = State::Hunk { hunk, remaining };
+            }
+            State::Search(mut block) => {
+                if line.trim_end() == DIVIDER_MARKER {
+                    self.state = State::Replace(block);
+                } else {
+                    block.search.push_str(line);
+                    block.search.push('\n');
+                    self.state = State::Search(block);
+                }
+            }
+            State::Replace(mut block) => {
+                if line.trim_end() == REPLACE_MARKER {
+                    events.push(EditEvent::SearchReplace(block));
+                } else {
+                    block.replace.push_str(line);
+                    block.replace.push('\n');
+                    self.state = State::Replace(block);
+                }
+            }
+        }
+    }
+
+    fn push_text_line(&mut self, line: &str, events: &mut Vec<EditEvent>) {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            let path = path.trim();
+            let path = path.strip_prefix("b/").unwrap_or(path).to_string();
+            self.diff_path = Some(path.clone());
+            events.push(EditEvent::File { path });
+        } else if line.starts_with("--- ") {
+            // The original path is ignored in favor of the new one.
+        } else if line.starts_with("@@") {
+            let (old_start, new_start, remaining) = parse_hunk_header(line);
+            self.state = State::Hunk {
+                hunk: DiffHunk {
+                    path: self.diff_path.clone(),
+                    old_start,
+                    new_start,
+                    ..Default::default()
+                },
+                remaining,
+            };
+        } else if line.trim_end() == SEARCH_MARKER {
+            self.state = State::Search(SearchReplace {
+                path: self.last_text_line.take(),
+                ..Default::default()
+            });
+        } else {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with("```") {
+                self.last_text_line = Some(line.to_string());
+            }
+        }
+    }
+}
+
+/// Parses a header like `@@ -12,3 +12,4 @@`, returning the start lines and the
+/// number of lines in the hunk. Models often omit the ranges.
+fn parse_hunk_header(line: &str) -> (Option<u32>, Option<u32>, Option<(u32, u32)>) {
+    let mut parts = line.split_whitespace().skip(1);
+    let old = parts
+        .next()
+        .and_then(|range| parse_range(range.strip_prefix('-')?));
+    let new = parts
+        .next()
+        .and_then(|range| parse_range(range.strip_prefix('+')?));
+    match (old, new) {
+        (Some((old_start, old_len)), Some((new_start, new_len))) => {
+            (Some(old_start), Some(new_start), Some((old_len, new_len)))
+        }
+        _ => (None, None, None),
+    }
+}
+
+fn parse_range(range: &str) -> Option<(u32, u32)> {
+    match range.split_once(',') {
+        Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(chunks: &[&str]) -> Vec<EditEvent> {
+        let mut parser = EditParser::new();
+        let mut events = Vec::new();
+        for chunk in chunks {
+            events.extend(parser.push(chunk));
+        }
+        events.extend(parser.finish());
+        events
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let events = parse(&[
+            "Here's the fix:\n--- a/src/main.rs\n+++ b/src/",
+            "main.rs\n@@ -1,3 +1,3 @@\n fn main() {\n-    println!(\"hi\");\n+    println!",
+            "(\"hello\");\n }\nDone.\n",
+        ]);
+        assert_eq!(
+            events,
+            vec![
+                EditEvent::File {
+                    path: "src/main.rs".into()
+                },
+                EditEvent::Hunk(DiffHunk {
+                    path: Some("src/main.rs".into()),
+                    old_start: Some(1),
+                    new_start: Some(1),
+                    old_text: "fn main() {\n    println!(\"hi\");\n}\n".into(),
+                    new_text: "fn main() {\n    println!(\"hello\");\n}\n".into(),
+                }),
+            ]
+        );
+
+        // Without line counts, a hunk ends at the first line that isn't part
+        // of the diff, or at the end of the stream.
+        let events = parse(&["@@ @@\n-a\n+b\nok\n@@ @@\n-c\n+d"]);
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[1],
+            EditEvent::Hunk(DiffHunk {
+                old_text: "c\n".into(),
+                new_text: "d\n".into(),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_search_replace() {
+        let events = parse(&[
+            "```\nsrc/lib.rs\n<<<<<<< SEA",
+            "RCH\nlet a = 1;\n=======\nlet a = 2;\n>>>>>>> REPLACE\n```\n",
+        ]);
+        assert_eq!(
+            events,
+            vec![EditEvent::SearchReplace(SearchReplace {
+                path: Some("src/lib.rs".into()),
+                search: "let a = 1;\n".into(),
+                replace: "let a = 2;\n".into(),
+            })]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{Metadata, Request, RequestMessage, SystemPrompt, Thinking, Tool, ToolChoice};
+use serde::{ser::SerializeSeq, Serialize, Serializer};
+use serde_json::value::{to_raw_value, RawValue};
+
+/// The JSON encoding of the system prompt and first messages of a request,
+/// which a [`Conversation`](crate::Conversation) keeps across turns so that
+/// each request only encodes the messages added since the previous one.
+///
+/// It's spliced into the request when serializing it, so requests carrying
+/// one must be serialized as JSON.
+#[derive(Clone, Debug)]
+pub struct EncodedPrefix {
+    /// The generation of the conversation the prefix was encoded from.
+    pub(crate) generation: u64,
+    system: Box<RawValue>,
+    messages: Vec<Box<RawValue>>,
+}
+
+impl EncodedPrefix {
+    pub(crate) fn new(system: &SystemPrompt, generation: u64) -> serde_json::Result<Self> {
+        Ok(Self {
+            generation,
+            system: to_raw_value(system)?,
+            messages: Vec::new(),
+        })
+    }
+
+    /// The number of messages encoded, which are the first messages of the
+    /// request.
+    pub fn message_count(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub(crate) fn push_message(&mut self, message: &RequestMessage) -> serde_json::Result<()> {
+        self.messages.push(to_raw_value(message)?);
+        Ok(())
+    }
+}
+
+/// The body of a [`Request`], with the encoded prefix in place of the system
+/// prompt and first messages if it has one.
+#[derive(Serialize)]
+struct RequestBody<'a> {
+    model: &'a str,
+    messages: Messages<'a>,
+    stream: bool,
+    system: Encoded<'a, SystemPrompt>,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    stop_sequences: &'a [String],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tools: &'a [Tool],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<&'a ToolChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<&'a Metadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking: Option<&'a Thinking>,
+    #[serde(flatten)]
+    extra: Option<&'a serde_json::Map<String, serde_json::Value>>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Encoded<'a, T> {
+    Raw(&'a RawValue),
+    Value(&'a T),
+}
+
+struct Messages<'a> {
+    encoded: &'a [Box<RawValue>],
+    rest: &'a [RequestMessage],
+}
+
+impl Serialize for Messages<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.encoded.len() + self.rest.len()))?;
+        for message in self.encoded {
+            seq.serialize_element(message)?;
+        }
+        for message in self.rest {
+            seq.serialize_element(message)?;
+        }
+        seq.end()
+    }
+}
+
+impl Serialize for Request {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Request {
+            model,
+            messages,
+            stream,
+            system,
+            max_tokens,
+            temperature,
+            top_p,
+            top_k,
+            stop_sequences,
+            tools,
+            tool_choice,
+            metadata,
+            thinking,
+            extra,
+            betas: _,
+            api_version: _,
+            timeout: _,
+            encoded_prefix,
+        } = self;
+        // A prefix covering every message means the last one was removed
+        // after it was set, so it no longer matches the request.
+        let prefix = encoded_prefix
+            .as_deref()
+            .filter(|prefix| prefix.messages.len() < messages.len());
+        let (system, messages) = match prefix {
+            Some(prefix) => (
+                Encoded::Raw(&prefix.system),
+                Messages {
+                    encoded: &prefix.messages,
+                    rest: &messages[prefix.messages.len()..],
+                },
+            ),
+            None => (
+                Encoded::Value(system),
+                Messages {
+                    encoded: &[],
+                    rest: messages,
+                },
+            ),
+        };
+        RequestBody {
+            model: model.id(),
+            messages,
+            stream: *stream,
+            system,
+            max_tokens: *max_tokens,
+            temperature: *temperature,
+            top_p: *top_p,
+            top_k: *top_k,
+            stop_sequences,
+            tools,
+            tool_choice: tool_choice.as_ref(),
+            metadata: metadata.as_ref(),
+            thinking: thinking.as_ref(),
+            extra: extra.as_ref(),
+        }
+        .serialize(serializer)
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{
+    MessageTokenCounter, Request, RequestMessage, SystemPrompt, Tool, ESTIMATED_IMAGE_TOKENS,
+};
+
+/// Tokens taken up by the framing of each message (role markers, separators).
+pub(crate) const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// The number of characters per token of long words and identifiers. Shorter
+/// ones are usually a single token.
+const WORD_TOKEN_LEN: usize = 6;
+
+/// Quickly estimates the number of tokens in `text`, without a tokenizer.
+///
+/// Words are counted as a token per six characters, and punctuation and
+/// characters of other scripts, such as CJK, as a token each. This
+/// is typically within 20% of the actual count for prose and code, which is
+/// enough to show how full the context window is while typing. Enable the
+/// `bpe-tokenizer` feature for closer estimates.
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut tokens = 0;
+    let mut word_len = 0;
+    for char in text.chars() {
+        if char.is_ascii_alphanumeric() || char == '_' {
+            word_len += 1;
+            continue;
+        }
+        tokens += word_tokens(word_len);
+        word_len = 0;
+        if char.is_ascii_punctuation() || !char.is_ascii() && !char.is_whitespace() {
+            tokens += 1;
+        }
+    }
+    tokens + word_tokens(word_len)
+}
+
+fn word_tokens(len: usize) -> usize {
+    len.div_ceil(WORD_TOKEN_LEN)
+}
+
+/// Estimates the number of tokens `message` takes up in a request, see
+/// [`estimate_tokens`].
+pub fn estimate_message_tokens(message: &RequestMessage) -> usize {
+    estimate_tokens(&message.content.text())
+        + message.content.images().count() * ESTIMATED_IMAGE_TOKENS
+        + MESSAGE_OVERHEAD_TOKENS
+}
+
+/// Estimates the number of input tokens of `request`, including its system
+/// prompt and tools, see [`estimate_tokens`].
+pub fn estimate_request_tokens(request: &Request) -> usize {
+    estimate_input_tokens(&request.system, &request.messages, &request.tools)
+}
+
+pub(crate) fn estimate_input_tokens(
+    system: &SystemPrompt,
+    messages: &[RequestMessage],
+    tools: &[Tool],
+) -> usize {
+    let tool_tokens = tools
+        .iter()
+        .map(|tool| match tool {
+            Tool::Custom(tool) => {
+                estimate_tokens(&tool.name)
+                    + estimate_tokens(&tool.description)
+                    + estimate_tokens(&tool.input_schema.to_string())
+            }
+            // The schemas of Anthropic-defined tools are added by the API.
+            Tool::Anthropic(tool) => estimate_tokens(tool.name()),
+        })
+        .sum::<usize>();
+    estimate_tokens(&system.text())
+        + messages.iter().map(estimate_message_tokens).sum::<usize>()
+        + tool_tokens
+}
+
+/// Counts tokens with [`estimate_message_tokens`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokenEstimator;
+
+impl MessageTokenCounter for TokenEstimator {
+    fn count_message_tokens(&self, message: &RequestMessage) -> usize {
+        estimate_message_tokens(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("Hello, world!"), 4);
+        assert_eq!(estimate_tokens("fn main() {}"), 6);
+        assert_eq!(estimate_tokens("internationalization"), 4);
+        assert_eq!(estimate_tokens("你好"), 2);
+        assert_eq!(
+            estimate_message_tokens(&RequestMessage::user("Hi there")),
+            2 + MESSAGE_OVERHEAD_TOKENS
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{Citation, ContentBlock, Model, ResponseMessage, Role, Usage};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Why the model stopped generating.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StopReason {
+    EndTurn,
+    /// The response reached `max_tokens` and is truncated.
+    MaxTokens,
+    StopSequence,
+    ToolUse,
+    /// A reason not known to this crate.
+    Other(String),
+}
+
+impl StopReason {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::EndTurn => "end_turn",
+            Self::MaxTokens => "max_tokens",
+            Self::StopSequence => "stop_sequence",
+            Self::ToolUse => "tool_use",
+            Self::Other(reason) => reason,
+        }
+    }
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for StopReason {
+    fn from(reason: &str) -> Self {
+        match reason {
+            "end_turn" => Self::EndTurn,
+            "max_tokens" => Self::MaxTokens,
+            "stop_sequence" => Self::StopSequence,
+            "tool_use" => Self::ToolUse,
+            _ => Self::Other(reason.to_string()),
+        }
+    }
+}
+
+impl Serialize for StopReason {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for StopReason {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let reason = String::deserialize(deserializer)?;
+        Ok(Self::from(reason.as_str()))
+    }
+}
+
+/// A complete response message, as opposed to the lenient
+/// [`ResponseMessage`] sent at the start of a streamed response.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Message {
+    pub id: String,
+    pub role: Role,
+    #[serde(
+        serialize_with = "crate::serialize_request_model",
+        deserialize_with = "deserialize_model_id"
+    )]
+    pub model: Model,
+    #[serde(default)]
+    pub content: Vec<ContentBlock>,
+    pub stop_reason: Option<StopReason>,
+    pub stop_sequence: Option<String>,
+    #[serde(default)]
+    pub usage: Usage,
+    /// The `request-id` header of the response the message was sent in, if
+    /// known.
+    #[serde(skip)]
+    pub request_id: Option<String>,
+}
+
+impl Message {
+    /// Returns the text of all the message's text blocks.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the citations of all the message's text blocks.
+    pub fn citations(&self) -> impl Iterator<Item = &Citation> {
+        self.content.iter().flat_map(|block| match block {
+            ContentBlock::Text { citations, .. } => citations.as_slice(),
+            _ => &[],
+        })
+    }
+
+    /// Returns the reasoning in the message's thinking blocks.
+    pub fn thinking(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Thinking { thinking, .. } => Some(thinking.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl TryFrom<ResponseMessage> for Message {
+    type Error = anyhow::Error;
+
+    fn try_from(message: ResponseMessage) -> Result<Self> {
+        let id = message
+            .id
+            .ok_or_else(|| anyhow!("response message has no id"))?;
+        let role = Role::try_from(
+            message
+                .role
+                .ok_or_else(|| anyhow!("response message has no role"))?,
+        )?;
+        let model = Model::from_id(
+            message
+                .model
+                .as_deref()
+                .ok_or_else(|| anyhow!("response message has no model"))?,
+        )?;
+        let content = message
+            .content
+            .unwrap_or_default()
+            .into_iter()
+            .map(|text| ContentBlock::Text {
+                text,
+                citations: Vec::new(),
+                extra: Default::default(),
+            })
+            .collect();
+        Ok(Self {
+            id,
+            role,
+            model,
+            content,
+            stop_reason: message.stop_reason,
+            stop_sequence: message.stop_sequence,
+            usage: message.usage.unwrap_or_default(),
+            request_id: None,
+        })
+    }
+}
+
+pub(crate) fn deserialize_model_id<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Model, D::Error> {
+    let id = String::deserialize(deserializer)?;
+    Model::from_id(&id).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_message() {
+        let message: Message = serde_json::from_str(
+            r#"{
+                "id": "msg_01",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-3-haiku-20240307",
+                "content": [
+                    {"type": "thinking", "thinking": "A greeting.", "signature": "sig"},
+                    {"type": "redacted_thinking", "data": "..."},
+                    {"type": "text", "text": "Hi", "citations": [{
+                        "type": "page_location",
+                        "cited_text": "Hello",
+                        "document_index": 0,
+                        "document_title": null,
+                        "start_page_number": 1,
+                        "end_page_number": 2
+                    }]}
+                ],
+                "stop_reason": "refusal",
+                "stop_sequence": null,
+                "usage": {"input_tokens": 10, "output_tokens": 2}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(message.role, Role::Assistant);
+        assert_eq!(message.model, Model::Claude3Haiku);
+        assert_eq!(message.text(), "Hi");
+        assert_eq!(message.thinking(), "A greeting.");
+        let citations = message.citations().collect::<Vec<_>>();
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].cited_text(), "Hello");
+        assert_eq!(
+            message.stop_reason,
+            Some(StopReason::Other("refusal".into()))
+        );
+        assert_eq!(message.usage.output_tokens, Some(2));
+
+        let error = Message::try_from(ResponseMessage::default()).unwrap_err();
+        assert_eq!(error.to_string(), "response message has no id");
+    }
+}
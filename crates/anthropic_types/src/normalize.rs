@@ -0,0 +1,103 @@
+use crate::{Request, RequestMessage, Role};
+
+/// The content of user turns inserted by [`Request::normalize_with`].
+pub const USER_BRIDGE_TEXT: &str = "Continue.";
+/// The content of assistant turns inserted by [`Request::normalize_with`].
+pub const ASSISTANT_BRIDGE_TEXT: &str = "OK.";
+
+/// How [`Request::normalize_with`] resolves consecutive messages with the
+/// same role, which the API rejects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SameRolePolicy {
+    /// Join the messages into one, separated by a blank line.
+    #[default]
+    Merge,
+    /// Keep the messages separate and insert a short turn from the other role
+    /// between them.
+    Bridge,
+}
+
+impl Request {
+    /// Makes the roles of the messages alternate, starting with the user, by
+    /// merging consecutive messages with the same role.
+    pub fn normalize(&mut self) {
+        self.normalize_with(SameRolePolicy::Merge);
+    }
+
+    pub fn normalize_with(&mut self, policy: SameRolePolicy) {
+        self.messages = normalize_messages(std::mem::take(&mut self.messages), policy);
+    }
+}
+
+pub(crate) fn normalize_messages(
+    messages: Vec<RequestMessage>,
+    policy: SameRolePolicy,
+) -> Vec<RequestMessage> {
+    let mut normalized: Vec<RequestMessage> = Vec::with_capacity(messages.len());
+    for message in messages {
+        match normalized.last_mut() {
+            None if message.role == Role::Assistant => {
+                // The conversation has to start with a user turn, which can't
+                // be achieved by merging.
+                normalized.push(bridge_message(Role::User));
+                normalized.push(message);
+            }
+            Some(last) if last.role == message.role => match policy {
+                SameRolePolicy::Merge => last.content.merge(message.content),
+                SameRolePolicy::Bridge => {
+                    let bridge_role = match message.role {
+                        Role::User => Role::Assistant,
+                        Role::Assistant => Role::User,
+                    };
+                    normalized.push(bridge_message(bridge_role));
+                    normalized.push(message);
+                }
+            },
+            _ => normalized.push(message),
+        }
+    }
+    normalized
+}
+
+fn bridge_message(role: Role) -> RequestMessage {
+    match role {
+        Role::User => RequestMessage::user(USER_BRIDGE_TEXT),
+        Role::Assistant => RequestMessage::assistant(ASSISTANT_BRIDGE_TEXT),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_messages() {
+        let messages = vec![
+            RequestMessage::assistant("a"),
+            RequestMessage::user("b"),
+            RequestMessage::user("c"),
+            RequestMessage::assistant("d"),
+        ];
+
+        assert_eq!(
+            normalize_messages(messages.clone(), SameRolePolicy::Merge),
+            vec![
+                RequestMessage::user(USER_BRIDGE_TEXT),
+                RequestMessage::assistant("a"),
+                RequestMessage::user("b\n\nc"),
+                RequestMessage::assistant("d"),
+            ]
+        );
+        assert_eq!(
+            normalize_messages(messages, SameRolePolicy::Bridge),
+            vec![
+                RequestMessage::user(USER_BRIDGE_TEXT),
+                RequestMessage::assistant("a"),
+                RequestMessage::user("b"),
+                RequestMessage::assistant(ASSISTANT_BRIDGE_TEXT),
+                RequestMessage::user("c"),
+                RequestMessage::assistant("d"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{MessageDelta, Request, RequestMessage, ResponseEvent, Role, StopReason, Usage};
+use anyhow::Result;
+use futures::{
+    ready,
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use std::{collections::VecDeque, task::Poll};
+
+/// The `stop_reason` of a message cut short by [`cap_output_tokens`].
+pub const LOCAL_MAX_TOKENS_STOP_REASON: &str = "local_max_tokens";
+
+/// Ends `events` once the text it carries reaches `max_output_tokens`, as
+/// counted by `count_tokens`, e.g. [`crate::bpe_token_count`].
+///
+/// Unlike the request's `max_tokens`, the cap can be adjusted without
+/// changing the request, which is useful for background features that only
+/// need the beginning of a response. Once reached, `events` is dropped, which
+/// cancels the request, and the stream ends with a `message_delta` whose stop
+/// reason is [`LOCAL_MAX_TOKENS_STOP_REASON`] followed by a `message_stop`.
+/// The delta that crosses the cap is passed through whole. Use
+/// [`continuation_request`] to resume generation later.
+pub fn cap_output_tokens(
+    events: BoxStream<'static, Result<ResponseEvent>>,
+    max_output_tokens: usize,
+    count_tokens: impl Fn(&str) -> usize + Send + 'static,
+) -> BoxStream<'static, Result<ResponseEvent>> {
+    let mut events = Some(events);
+    let mut output_tokens = 0;
+    let mut pending = VecDeque::new();
+    stream::poll_fn(move |cx| {
+        if let Some(event) = pending.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+        let Some(upstream) = events.as_mut() else {
+            return Poll::Ready(None);
+        };
+        let Some(event) = ready!(upstream.poll_next_unpin(cx)) else {
+            events = None;
+            return Poll::Ready(None);
+        };
+        if let Ok(event) = &event {
+            if let Some(text) = event.text() {
+                output_tokens += count_tokens(text);
+                if output_tokens >= max_output_tokens {
+                    events = None;
+                    pending.push_back(Ok(ResponseEvent::MessageDelta {
+                        delta: MessageDelta {
+                            stop_reason: Some(StopReason::Other(
+                                LOCAL_MAX_TOKENS_STOP_REASON.to_string(),
+                            )),
+                            stop_sequence: None,
+                        },
+                        usage: Usage {
+                            output_tokens: Some(output_tokens as u32),
+                            ..Default::default()
+                        },
+                    }));
+                    pending.push_back(Ok(ResponseEvent::MessageStop {}));
+                }
+            }
+        }
+        Poll::Ready(Some(event))
+    })
+    .boxed()
+}
+
+/// Returns a request that continues a response cut short after producing
+/// `partial_text`, by prefilling the assistant's turn with it.
+pub fn continuation_request(request: &Request, partial_text: &str) -> Request {
+    let mut request = request.clone();
+    match request.messages.last_mut() {
+        Some(message) if message.role == Role::Assistant => {
+            message.content.push_str(partial_text);
+        }
+        _ => request
+            .messages
+            .push(RequestMessage::assistant(partial_text)),
+    }
+    // A prefill can't end with whitespace.
+    if let Some(message) = request.messages.last_mut() {
+        message.content.truncate_trailing_whitespace();
+    }
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TextDelta;
+    use futures::executor::block_on;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc,
+    };
+
+    fn delta(text: &str) -> Result<ResponseEvent> {
+        Ok(ResponseEvent::ContentBlockDelta {
+            index: 0,
+            delta: TextDelta::TextDelta { text: text.into() },
+        })
+    }
+
+    #[test]
+    fn test_cap_output_tokens() {
+        let events = stream::iter([delta("one two"), delta("three"), delta("four")]).boxed();
+        let events = block_on(
+            cap_output_tokens(events, 3, |text| text.split_whitespace().count())
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(events.len(), 4);
+        let stop = events.iter().find_map(|event| match event {
+            Ok(ResponseEvent::MessageDelta { delta, usage }) => {
+                Some((delta.stop_reason.clone(), usage.output_tokens))
+            }
+            _ => None,
+        });
+        assert_eq!(
+            stop,
+            Some((
+                Some(StopReason::Other(LOCAL_MAX_TOKENS_STOP_REASON.to_string())),
+                Some(3)
+            ))
+        );
+        assert!(matches!(events[3], Ok(ResponseEvent::MessageStop {})));
+    }
+
+    #[test]
+    fn test_cap_output_tokens_drops_upstream() {
+        struct DropFlag(Arc<AtomicBool>);
+
+        impl Drop for DropFlag {
+            fn drop(&mut self) {
+                self.0.store(true, SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = DropFlag(dropped.clone());
+        // An upstream that never yields again after the capping delta.
+        let events = stream::iter([delta("one two three")])
+            .chain(stream::pending())
+            .map(move |event| {
+                let _flag = &flag;
+                event
+            })
+            .boxed();
+        let mut events = cap_output_tokens(events, 3, |text| text.split_whitespace().count());
+        let capped = block_on(events.by_ref().take(3).collect::<Vec<_>>());
+        assert!(matches!(capped[1], Ok(ResponseEvent::MessageDelta { .. })));
+        assert!(matches!(capped[2], Ok(ResponseEvent::MessageStop {})));
+        assert!(dropped.load(SeqCst));
+        assert!(block_on(events.next()).is_none());
+    }
+
+    #[test]
+    fn test_continuation_request() {
+        let request = Request::new(Default::default(), ["Count to ten"]);
+        let request = continuation_request(&request, "1, 2, ");
+        assert_eq!(request.messages[1], RequestMessage::assistant("1, 2,"));
+        let request = continuation_request(&request, " 3, 4");
+        assert_eq!(request.messages[1], RequestMessage::assistant("1, 2, 3, 4"));
+    }
+}
@@ -0,0 +1,98 @@
+/// Parses JSON that's been cut off, such as the input of a tool call that's
+/// still being streamed, by closing its open strings, arrays and objects.
+///
+/// Incomplete keys and literals at the end are left out, e.g.
+/// `{"path": "src/ma` parses as `{"path": "src/ma"}` and `{"path": "a", "li`
+/// as `{"path": "a"}`. Returns `None` if nothing could be parsed yet.
+pub fn parse_partial_json(json: &str) -> Option<serde_json::Value> {
+    let mut closers = Vec::new();
+    // The last prefix that can be completed by closing the containers that
+    // were open at its end.
+    let mut safe_point = None;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (ix, byte) in json.bytes().enumerate() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                closers.push(if byte == b'{' { b'}' } else { b']' });
+                safe_point = Some((ix + 1, closers.clone()));
+            }
+            b'}' | b']' => {
+                closers.pop();
+            }
+            b',' => safe_point = Some((ix, closers.clone())),
+            _ => {}
+        }
+    }
+
+    complete(json, in_string, &closers).or_else(|| {
+        let (len, closers) = safe_point?;
+        complete(&json[..len], false, &closers)
+    })
+}
+
+fn complete(prefix: &str, in_string: bool, closers: &[u8]) -> Option<serde_json::Value> {
+    let mut json = prefix.to_string();
+    if in_string {
+        // Drop an escape sequence that was cut off, like `\` or `\u00`.
+        if let Some(escape_ix) = json.rfind('\\') {
+            let escape = &json[escape_ix + 1..];
+            let backslashes = json[..escape_ix]
+                .bytes()
+                .rev()
+                .take_while(|byte| *byte == b'\\')
+                .count();
+            let is_cut_off = escape.is_empty() || (escape.starts_with('u') && escape.len() < 5);
+            if backslashes % 2 == 0 && is_cut_off {
+                json.truncate(escape_ix);
+            }
+        }
+        json.push('"');
+    }
+    json.extend(closers.iter().rev().map(|byte| *byte as char));
+    serde_json::from_str(&json).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_partial_json() {
+        assert_eq!(parse_partial_json(""), None);
+        assert_eq!(parse_partial_json("{"), Some(json!({})));
+        assert_eq!(
+            parse_partial_json(r#"{"path": "src/ma"#),
+            Some(json!({"path": "src/ma"}))
+        );
+        assert_eq!(
+            parse_partial_json(r#"{"path": "a", "li"#),
+            Some(json!({"path": "a"}))
+        );
+        assert_eq!(
+            parse_partial_json(r#"{"path": "a", "lines": [1, 2"#),
+            Some(json!({"path": "a", "lines": [1, 2]}))
+        );
+        assert_eq!(
+            parse_partial_json(r#"{"edits": [{"old": "x\"y\u00"#),
+            Some(json!({"edits": [{"old": "x\"y"}]}))
+        );
+        assert_eq!(parse_partial_json(r#"{"ok": tr"#), Some(json!({})));
+        assert_eq!(
+            parse_partial_json(r#"{"ok": true}"#),
+            Some(json!({"ok": true}))
+        );
+    }
+}
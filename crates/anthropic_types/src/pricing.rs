@@ -0,0 +1,78 @@
+use crate::{Model, Usage};
+
+/// The price of writing input tokens to the prompt cache, relative to the
+/// model's input price.
+pub const CACHE_WRITE_PRICE_MULTIPLIER: f64 = 1.25;
+
+/// The price of reading input tokens from the prompt cache, relative to the
+/// model's input price.
+pub const CACHE_READ_PRICE_MULTIPLIER: f64 = 0.1;
+
+/// The list price of a model, in US dollars per million tokens.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModelPricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPricing {
+    /// Returns the cost, in US dollars, of a request with the given token
+    /// counts.
+    pub fn cost(&self, input_tokens: usize, output_tokens: usize) -> f64 {
+        (input_tokens as f64 * self.input_per_million
+            + output_tokens as f64 * self.output_per_million)
+            / 1_000_000.
+    }
+
+    /// Returns the cost, in US dollars, of a response with `usage`, pricing
+    /// the input tokens written to and read from the prompt cache at their
+    /// own rates.
+    pub fn usage_cost(&self, usage: &Usage) -> f64 {
+        let input_cost = usage.input_tokens.unwrap_or(0) as f64
+            + usage.cache_creation_input_tokens.unwrap_or(0) as f64 * CACHE_WRITE_PRICE_MULTIPLIER
+            + usage.cache_read_input_tokens.unwrap_or(0) as f64 * CACHE_READ_PRICE_MULTIPLIER;
+        (input_cost * self.input_per_million
+            + usage.output_tokens.unwrap_or(0) as f64 * self.output_per_million)
+            / 1_000_000.
+    }
+}
+
+impl Model {
+    /// Returns the list price of the model, or `None` for custom models.
+    pub fn pricing(&self) -> Option<ModelPricing> {
+        let (input_per_million, output_per_million) = match self {
+            Self::Claude3_5Sonnet
+            | Self::Claude3Sonnet
+            | Self::Claude3_7Sonnet
+            | Self::ClaudeSonnet4 => (3., 15.),
+            Self::Claude3Opus | Self::ClaudeOpus4 | Self::ClaudeOpus4_1 => (15., 75.),
+            Self::Claude3Haiku => (0.25, 1.25),
+            Self::Claude3_5Haiku => (0.8, 4.),
+            Self::Custom { .. } => return None,
+        };
+        Some(ModelPricing {
+            input_per_million,
+            output_per_million,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_cost() {
+        let pricing = Model::Claude3_5Sonnet.pricing().unwrap();
+        let usage = Usage {
+            input_tokens: Some(1_000_000),
+            output_tokens: Some(100_000),
+            cache_creation_input_tokens: Some(1_000_000),
+            cache_read_input_tokens: Some(2_000_000),
+            ..Default::default()
+        };
+        assert!((pricing.usage_cost(&usage) - (3. + 3.75 + 0.6 + 1.5)).abs() < 1e-9);
+        assert_eq!(usage.cache_hit_ratio(), Some(0.5));
+        assert_eq!(Usage::default().cache_hit_ratio(), None);
+    }
+}
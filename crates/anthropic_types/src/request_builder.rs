@@ -0,0 +1,567 @@
+use crate::{
+    estimate::estimate_input_tokens, is_valid_tool_name, normalize::normalize_messages,
+    MessageContent, Metadata, Model, Request, RequestMessage, Role, SameRolePolicy, SystemPrompt,
+    Thinking, Tool, ToolChoice, FILES_API_BETA, MIN_THINKING_BUDGET, PDFS_BETA,
+    PROMPT_CACHING_BETA,
+};
+use anyhow::{anyhow, bail, Result};
+use std::time::Duration;
+
+pub const DEFAULT_MAX_TOKENS: u32 = 4096;
+const MAX_USER_ID_LEN: usize = 256;
+const MAX_CACHE_BREAKPOINTS: usize = 4;
+/// The percentage added to the estimated input tokens by
+/// [`RequestBuilder::auto_max_tokens`], since estimates can be about 20% off.
+const AUTO_MAX_TOKENS_MARGIN_PERCENT: usize = 20;
+
+impl Request {
+    /// Starts building a request to `model`, e.g.
+    /// `Request::builder(model).system("Be brief.").user("Hi").build()`.
+    pub fn builder(model: Model) -> RequestBuilder {
+        RequestBuilder::new(model)
+    }
+}
+
+/// Builds a [`Request`], checking at [`RequestBuilder::build`] time the
+/// constraints the API would otherwise reject the request for.
+#[derive(Clone, Debug)]
+pub struct RequestBuilder {
+    model: Option<Model>,
+    system: SystemPrompt,
+    messages: Vec<RequestMessage>,
+    max_tokens: Option<u32>,
+    auto_max_tokens: bool,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    top_k: Option<u32>,
+    stop_sequences: Vec<String>,
+    tools: Vec<Tool>,
+    tool_choice: Option<ToolChoice>,
+    metadata: Option<Metadata>,
+    thinking: Option<Thinking>,
+    stream: bool,
+    normalization: Option<SameRolePolicy>,
+    extra: Option<serde_json::Map<String, serde_json::Value>>,
+    betas: Vec<String>,
+    api_version: Option<String>,
+    timeout: Option<Duration>,
+}
+
+impl Default for RequestBuilder {
+    fn default() -> Self {
+        Self {
+            model: None,
+            system: SystemPrompt::default(),
+            messages: Vec::new(),
+            max_tokens: None,
+            auto_max_tokens: false,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            stop_sequences: Vec::new(),
+            tools: Vec::new(),
+            tool_choice: None,
+            metadata: None,
+            thinking: None,
+            stream: true,
+            normalization: Some(SameRolePolicy::Merge),
+            extra: None,
+            betas: Vec::new(),
+            api_version: None,
+            timeout: None,
+        }
+    }
+}
+
+impl RequestBuilder {
+    pub fn new(model: Model) -> Self {
+        Self::default().model(model)
+    }
+
+    /// Sets the model, which [`RequestBuilder::build`] fails without.
+    pub fn model(mut self, model: Model) -> Self {
+        self.model = Some(model);
+        self
+    }
+
+    pub fn system(mut self, system: impl Into<SystemPrompt>) -> Self {
+        self.system = system.into();
+        self
+    }
+
+    pub fn user(self, content: impl Into<MessageContent>) -> Self {
+        self.message(Role::User, content)
+    }
+
+    pub fn assistant(self, content: impl Into<MessageContent>) -> Self {
+        self.message(Role::Assistant, content)
+    }
+
+    pub fn message(mut self, role: Role, content: impl Into<MessageContent>) -> Self {
+        self.messages.push(RequestMessage::new(role, content));
+        self
+    }
+
+    pub fn messages(
+        mut self,
+        messages: impl IntoIterator<Item = impl Into<RequestMessage>>,
+    ) -> Self {
+        self.messages.extend(messages.into_iter().map(Into::into));
+        self
+    }
+
+    /// Defaults to the model's [`Model::default_max_tokens`].
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Unless `max_tokens` is set, sets it to the model's whole output limit,
+    /// or to what's left of its context window after the input if that's
+    /// less. The input is measured with [`crate::estimate_request_tokens`],
+    /// plus a margin for its inaccuracy.
+    pub fn auto_max_tokens(mut self) -> Self {
+        self.auto_max_tokens = true;
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Samples only from the most likely tokens whose probabilities add up
+    /// to `top_p`.
+    pub fn top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// Samples only from the `top_k` most likely tokens.
+    pub fn top_k(mut self, top_k: u32) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+
+    /// Adds a sequence that ends generation when the model produces it.
+    pub fn stop_sequence(mut self, stop_sequence: impl Into<String>) -> Self {
+        self.stop_sequences.push(stop_sequence.into());
+        self
+    }
+
+    pub fn stop_sequences(
+        mut self,
+        stop_sequences: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.stop_sequences
+            .extend(stop_sequences.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds a tool, enabling the beta it requires if it's an
+    /// [`crate::AnthropicTool`].
+    pub fn tool(mut self, tool: impl Into<Tool>) -> Self {
+        self.tools.push(tool.into());
+        self
+    }
+
+    pub fn tools(mut self, tools: impl IntoIterator<Item = impl Into<Tool>>) -> Self {
+        self.tools.extend(tools.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Attributes the request to a user, see [`Metadata::user_id`].
+    pub fn user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.metadata = Some(Metadata {
+            user_id: Some(user_id.into()),
+        });
+        self
+    }
+
+    /// Lets the model reason for up to `budget_tokens` before answering,
+    /// which must be less than `max_tokens`.
+    pub fn thinking(mut self, budget_tokens: u32) -> Self {
+        self.thinking = Some(Thinking::Enabled { budget_tokens });
+        self
+    }
+
+    /// Defaults to `true`.
+    pub fn stream(mut self, stream: bool) -> Self {
+        self.stream = stream;
+        self
+    }
+
+    /// Adds a parameter to the request body that has no dedicated setter,
+    /// such as one recently introduced by the API.
+    pub fn extra(mut self, key: impl Into<String>, value: impl Into<serde_json::Value>) -> Self {
+        self.extra
+            .get_or_insert_with(Default::default)
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// Enables a beta feature, such as `prompt-caching-2024-07-31`, for this
+    /// request.
+    pub fn beta(mut self, beta: impl Into<String>) -> Self {
+        self.betas.push(beta.into());
+        self
+    }
+
+    /// Sends the request with a different API version than the client's.
+    pub fn api_version(mut self, api_version: impl Into<String>) -> Self {
+        self.api_version = Some(api_version.into());
+        self
+    }
+
+    /// Aborts the request if it hasn't completed, including streaming the
+    /// response, within `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// How messages are normalized before being validated, which defaults to
+    /// [`SameRolePolicy::Merge`]. With `None`, messages whose roles don't
+    /// alternate are reported as an error instead.
+    pub fn normalization(mut self, policy: Option<SameRolePolicy>) -> Self {
+        self.normalization = policy;
+        self
+    }
+
+    pub fn build(mut self) -> Result<Request> {
+        let model = self
+            .model
+            .ok_or_else(|| anyhow!("no model was specified"))?;
+        if let Some(policy) = self.normalization {
+            self.messages = normalize_messages(self.messages, policy);
+        }
+
+        let Some(first) = self.messages.first() else {
+            bail!("a request needs at least one message");
+        };
+        if first.role != Role::User {
+            bail!("the first message must be from the user");
+        }
+        for (ix, pair) in self.messages.windows(2).enumerate() {
+            if pair[0].role == pair[1].role {
+                bail!(
+                    "messages {} and {} are both from the {}, but roles must alternate",
+                    ix,
+                    ix + 1,
+                    String::from(pair[0].role)
+                );
+            }
+        }
+        let last_ix = self.messages.len() - 1;
+        for (ix, message) in self.messages.iter().enumerate() {
+            // A trailing assistant message is a prefill for the response and
+            // may be empty, but every other message needs content.
+            let is_prefill = ix == last_ix && message.role == Role::Assistant;
+            if !is_prefill && message.content.is_blank() {
+                bail!("message {ix} is empty");
+            }
+        }
+
+        let max_tokens = match self.max_tokens {
+            Some(max_tokens) => max_tokens,
+            None if self.auto_max_tokens => {
+                let input_tokens = estimate_input_tokens(&self.system, &self.messages, &self.tools);
+                let input_tokens = input_tokens * (100 + AUTO_MAX_TOKENS_MARGIN_PERCENT) / 100;
+                let remaining = model.max_token_count().saturating_sub(input_tokens);
+                if remaining == 0 {
+                    bail!(
+                        "the request leaves no room for a response in the context window of {}",
+                        model.display_name()
+                    );
+                }
+                remaining.min(model.max_output_tokens() as usize) as u32
+            }
+            None => model.default_max_tokens(),
+        };
+        if max_tokens == 0 {
+            bail!("max_tokens must be greater than zero");
+        }
+        if max_tokens as usize > model.max_token_count() {
+            bail!(
+                "max_tokens is {}, but {} supports at most {} tokens",
+                max_tokens,
+                model.display_name(),
+                model.max_token_count()
+            );
+        }
+        if max_tokens > model.max_output_tokens() {
+            bail!(
+                "max_tokens is {}, but {} generates at most {} tokens",
+                max_tokens,
+                model.display_name(),
+                model.max_output_tokens()
+            );
+        }
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=1.0).contains(&temperature) {
+                bail!("temperature must be between 0 and 1, got {temperature}");
+            }
+        }
+        if let Some(top_p) = self.top_p {
+            if !(0.0..=1.0).contains(&top_p) {
+                bail!("top_p must be between 0 and 1, got {top_p}");
+            }
+        }
+        if self.top_k == Some(0) {
+            bail!("top_k must be greater than zero");
+        }
+        if self
+            .stop_sequences
+            .iter()
+            .any(|stop_sequence| stop_sequence.trim().is_empty())
+        {
+            bail!("stop sequences must contain non-whitespace characters");
+        }
+
+        for (ix, tool) in self.tools.iter().enumerate() {
+            if !is_valid_tool_name(tool.name()) {
+                bail!("invalid tool name '{}'", tool.name());
+            }
+            if self.tools[..ix]
+                .iter()
+                .any(|other| other.name() == tool.name())
+            {
+                bail!("tool '{}' is defined more than once", tool.name());
+            }
+        }
+        if let Some(Thinking::Enabled { budget_tokens }) = self.thinking {
+            if budget_tokens < MIN_THINKING_BUDGET {
+                bail!("the thinking budget must be at least {MIN_THINKING_BUDGET} tokens");
+            }
+            if budget_tokens >= max_tokens {
+                bail!("the thinking budget of {budget_tokens} tokens must be less than max_tokens");
+            }
+            if self.temperature.is_some() || self.top_k.is_some() {
+                bail!("temperature and top_k can't be set when thinking is enabled");
+            }
+        }
+        if let Some(user_id) = self
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.user_id.as_ref())
+        {
+            if user_id.is_empty() || user_id.len() > MAX_USER_ID_LEN {
+                bail!("user_id must be between 1 and {MAX_USER_ID_LEN} bytes long");
+            }
+        }
+        match &self.tool_choice {
+            Some(ToolChoice::Tool { name, .. }) => {
+                if !self.tools.iter().any(|tool| tool.name() == name) {
+                    bail!("tool_choice names the undefined tool '{name}'");
+                }
+            }
+            Some(_) if self.tools.is_empty() => bail!("tool_choice requires tools"),
+            _ => {}
+        }
+
+        let cache_breakpoints = self.system.cache_breakpoints()
+            + self
+                .messages
+                .iter()
+                .filter_map(|message| match &message.content {
+                    MessageContent::Blocks(blocks) => Some(blocks),
+                    MessageContent::Text(_) => None,
+                })
+                .flatten()
+                .filter(|block| block.cache_control().is_some())
+                .count();
+        if cache_breakpoints > MAX_CACHE_BREAKPOINTS {
+            bail!(
+                "{cache_breakpoints} blocks are marked with cache_control, but at most \
+                {MAX_CACHE_BREAKPOINTS} are allowed"
+            );
+        }
+        if cache_breakpoints > 0 {
+            self.enable_beta(PROMPT_CACHING_BETA);
+        }
+        let tool_betas: Vec<&str> = self.tools.iter().filter_map(Tool::beta).collect();
+        for beta in tool_betas {
+            self.enable_beta(beta);
+        }
+        if self
+            .messages
+            .iter()
+            .any(|message| message.content.uses_files())
+        {
+            self.enable_beta(FILES_API_BETA);
+        }
+        if self
+            .messages
+            .iter()
+            .any(|message| message.content.has_pdfs())
+        {
+            self.enable_beta(PDFS_BETA);
+        }
+
+        Ok(Request {
+            model,
+            messages: self.messages,
+            stream: self.stream,
+            system: self.system,
+            max_tokens,
+            temperature: self.temperature,
+            top_p: self.top_p,
+            top_k: self.top_k,
+            stop_sequences: self.stop_sequences,
+            tools: self.tools,
+            tool_choice: self.tool_choice,
+            metadata: self.metadata,
+            thinking: self.thinking,
+            extra: self.extra,
+            betas: self.betas,
+            api_version: self.api_version,
+            timeout: self.timeout,
+            encoded_prefix: None,
+        })
+    }
+
+    fn enable_beta(&mut self, beta: &str) {
+        if !self.betas.iter().any(|enabled| enabled == beta) {
+            self.betas.push(beta.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CacheControl, RequestContent, ToolDefinition};
+
+    #[test]
+    fn test_build_request() {
+        let request = Request::builder(Model::Claude3Haiku)
+            .system("Be brief.")
+            .user("Hello")
+            .assistant("Hi")
+            .user("How are you?")
+            .temperature(0.2)
+            .top_k(40)
+            .stop_sequence("</answer>")
+            .build()
+            .unwrap();
+        assert_eq!(request.messages.len(), 3);
+        assert_eq!(request.system, "Be brief.");
+        assert_eq!(request.temperature, Some(0.2));
+        assert_eq!(request.top_k, Some(40));
+        assert_eq!(request.stop_sequences, vec!["</answer>".to_string()]);
+        assert_eq!(request.max_tokens, DEFAULT_MAX_TOKENS);
+        assert!(request.stream);
+
+        let request = Request::builder(Model::Claude3Haiku)
+            .user("Hello")
+            .user("Again")
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.messages,
+            vec![RequestMessage::user("Hello\n\nAgain")]
+        );
+
+        let request = Request::builder(Model::Claude3Haiku)
+            .messages([
+                RequestMessage::user("A long file").with_cache_control(CacheControl::Ephemeral)
+            ])
+            .build()
+            .unwrap();
+        assert!(request.uses_prompt_caching());
+        assert_eq!(request.betas, vec![PROMPT_CACHING_BETA.to_string()]);
+
+        // An empty trailing assistant message is allowed as a prefill.
+        assert!(Request::builder(Model::Claude3Haiku)
+            .user("Hello")
+            .assistant("")
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_auto_max_tokens() {
+        let request = Request::builder(Model::Claude3_7Sonnet)
+            .user("Hello")
+            .auto_max_tokens()
+            .build()
+            .unwrap();
+        assert_eq!(request.max_tokens, 64_000);
+
+        let model = Model::Custom {
+            name: "claude-small".into(),
+            max_tokens: Some(1_000),
+            max_output_tokens: Some(900),
+        };
+        let request = Request::builder(model.clone())
+            .user("word ".repeat(500))
+            .auto_max_tokens()
+            .build()
+            .unwrap();
+        assert_eq!(request.max_tokens, 1_000 - (500 + 4) * 6 / 5);
+
+        let request = Request::builder(model.clone())
+            .user("Hello")
+            .max_tokens(10)
+            .auto_max_tokens()
+            .build()
+            .unwrap();
+        assert_eq!(request.max_tokens, 10);
+
+        assert!(Request::builder(model)
+            .user("word ".repeat(1_000))
+            .auto_max_tokens()
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let valid = || Request::builder(Model::Claude3Haiku).user("Hello");
+
+        assert!(RequestBuilder::default().user("Hello").build().is_err());
+        assert!(Request::builder(Model::Claude3Haiku).build().is_err());
+        assert!(Request::builder(Model::Claude3Haiku)
+            .normalization(None)
+            .assistant("Hi")
+            .build()
+            .is_err());
+        assert!(valid().normalization(None).user("Again").build().is_err());
+        assert!(valid().assistant("").user("Hello").build().is_err());
+        assert!(valid().max_tokens(0).build().is_err());
+        assert!(valid().max_tokens(1_000_000).build().is_err());
+        assert!(valid().temperature(1.5).build().is_err());
+        assert!(valid().top_p(-0.1).build().is_err());
+        assert!(valid().top_k(0).build().is_err());
+        assert!(valid().user_id("").build().is_err());
+        assert!(valid().stop_sequence(" \n").build().is_err());
+        assert!(valid().thinking(2048).build().is_ok());
+        assert!(valid().thinking(512).build().is_err());
+        assert!(valid().thinking(8192).build().is_err());
+        assert!(valid().thinking(2048).temperature(0.5).build().is_err());
+        let cached = RequestContent::text("Hi").with_cache_control(CacheControl::Ephemeral);
+        assert!(Request::builder(Model::Claude3Haiku)
+            .user(vec![cached; 5])
+            .build()
+            .is_err());
+
+        let tool = ToolDefinition::new("read_file", "", serde_json::json!({"type": "object"}));
+        assert!(valid().tool(tool.clone()).build().is_ok());
+        assert!(valid().tool_choice(ToolChoice::any()).build().is_err());
+        assert!(valid()
+            .tool(tool.clone())
+            .tool_choice(ToolChoice::tool("write_file"))
+            .build()
+            .is_err());
+        assert!(valid().tools([tool.clone(), tool]).build().is_err());
+        assert!(valid()
+            .tool(ToolDefinition::new("read file", "", serde_json::json!({})))
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{Request, RequestMessage, Role};
+
+/// How [`Request::shrink`] makes a request smaller after the API rejected it
+/// as too large.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShrinkPolicy {
+    /// Never drop the first message, which often carries the context the
+    /// rest of the conversation relies on.
+    pub keep_first_message: bool,
+    /// The fraction of the droppable exchanges removed at once. At least one
+    /// exchange is removed.
+    pub drop_fraction: f32,
+}
+
+impl Default for ShrinkPolicy {
+    fn default() -> Self {
+        Self {
+            keep_first_message: false,
+            drop_fraction: 0.25,
+        }
+    }
+}
+
+impl Request {
+    /// Drops the oldest exchanges of the conversation according to `policy`,
+    /// returning the messages that were removed. The last user message and
+    /// anything after it are always kept, so nothing is dropped when there's
+    /// no earlier exchange.
+    ///
+    /// Messages are dropped in pairs, so roles keep alternating.
+    pub fn shrink(&mut self, policy: &ShrinkPolicy) -> Vec<RequestMessage> {
+        let start = usize::from(policy.keep_first_message);
+        let Some(last_user_ix) = self
+            .messages
+            .iter()
+            .rposition(|message| message.role == Role::User)
+        else {
+            return Vec::new();
+        };
+        let droppable_pairs = last_user_ix.saturating_sub(start) / 2;
+        if droppable_pairs == 0 {
+            return Vec::new();
+        }
+        let dropped_pairs = ((droppable_pairs as f32 * policy.drop_fraction).ceil() as usize)
+            .clamp(1, droppable_pairs);
+        self.encoded_prefix = None;
+        self.messages
+            .drain(start..start + dropped_pairs * 2)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Model;
+
+    #[test]
+    fn test_shrink() {
+        let mut request = Request::new(
+            Model::Claude3Haiku,
+            ["u1", "a1", "u2", "a2", "u3", "a3", "u4"]
+                .into_iter()
+                .enumerate()
+                .map(|(ix, content)| {
+                    let role = if ix % 2 == 0 {
+                        Role::User
+                    } else {
+                        Role::Assistant
+                    };
+                    RequestMessage::new(role, content)
+                }),
+        );
+        let policy = ShrinkPolicy {
+            keep_first_message: true,
+            ..Default::default()
+        };
+
+        let dropped = request.shrink(&policy);
+        assert_eq!(
+            dropped,
+            vec![RequestMessage::assistant("a1"), RequestMessage::user("u2")]
+        );
+        request.shrink(&policy);
+        let contents = request
+            .messages
+            .iter()
+            .map(|message| message.content.text())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, ["u1", "a3", "u4"]);
+        assert!(request.shrink(&policy).is_empty());
+
+        assert_eq!(request.shrink(&ShrinkPolicy::default()).len(), 2);
+        assert_eq!(request.messages, vec![RequestMessage::user("u4")]);
+    }
+}
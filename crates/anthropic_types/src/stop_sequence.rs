@@ -0,0 +1,55 @@
+use crate::{ResponseEvent, StopReason};
+
+/// The `stop_reason` reported when generation ended on a stop sequence.
+pub const STOP_SEQUENCE_STOP_REASON: &str = "stop_sequence";
+
+impl ResponseEvent {
+    /// Returns the stop sequence that ended generation, if this event reports
+    /// one.
+    pub fn stop_sequence(&self) -> Option<&str> {
+        match self {
+            Self::MessageDelta { delta, .. }
+                if delta.stop_reason == Some(StopReason::StopSequence) =>
+            {
+                delta.stop_sequence.as_deref()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Removes `stop_sequence` from the end of `text`, or, if it isn't there, the
+/// longest partial match of it, i.e. a suffix of `text` that is a prefix of
+/// `stop_sequence`.
+///
+/// Only call this for text whose generation is known to have ended on
+/// `stop_sequence`, since a partial match may also be legitimate text.
+pub fn trim_stop_sequence<'a>(text: &'a str, stop_sequence: &str) -> &'a str {
+    if stop_sequence.is_empty() {
+        return text;
+    }
+    if let Some(trimmed) = text.strip_suffix(stop_sequence) {
+        return trimmed;
+    }
+    (1..stop_sequence.len())
+        .rev()
+        .filter(|len| stop_sequence.is_char_boundary(*len))
+        .find_map(|len| text.strip_suffix(&stop_sequence[..len]))
+        .unwrap_or(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim_stop_sequence() {
+        assert_eq!(trim_stop_sequence("answer</done>", "</done>"), "answer");
+        assert_eq!(trim_stop_sequence("answer</do", "</done>"), "answer");
+        assert_eq!(trim_stop_sequence("answer<", "</done>"), "answer");
+        assert_eq!(trim_stop_sequence("answer", "</done>"), "answer");
+        assert_eq!(trim_stop_sequence("a</done>b", "</done>"), "a</done>b");
+        assert_eq!(trim_stop_sequence("é", "éé"), "");
+        assert_eq!(trim_stop_sequence("text", ""), "text");
+    }
+}
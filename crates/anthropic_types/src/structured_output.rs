@@ -0,0 +1,97 @@
+use crate::{ContentBlock, Message, Request, RequestBuilder, ToolChoice, ToolDefinition};
+use anyhow::{anyhow, Result};
+
+/// The name of the tool the model is forced to call to return structured
+/// output.
+pub const STRUCTURED_OUTPUT_TOOL: &str = "respond";
+
+const STRUCTURED_OUTPUT_DESCRIPTION: &str =
+    "Respond to the user with arguments matching the input schema.";
+
+fn structured_output_tool(schema: serde_json::Value) -> ToolDefinition {
+    ToolDefinition::new(
+        STRUCTURED_OUTPUT_TOOL,
+        STRUCTURED_OUTPUT_DESCRIPTION,
+        schema,
+    )
+}
+
+impl Request {
+    /// Makes the model respond with JSON matching `schema`, by calling a tool
+    /// that takes it as its input. This replaces the request's tools.
+    pub fn set_structured_output(&mut self, schema: serde_json::Value) {
+        self.tools = vec![structured_output_tool(schema).into()];
+        self.tool_choice = Some(ToolChoice::tool(STRUCTURED_OUTPUT_TOOL));
+    }
+}
+
+impl RequestBuilder {
+    /// Makes the model respond with JSON matching `schema`, see
+    /// [`Request::set_structured_output`]. No other tools should be added.
+    pub fn structured_output(self, schema: serde_json::Value) -> Self {
+        self.tools([structured_output_tool(schema)])
+            .tool_choice(ToolChoice::tool(STRUCTURED_OUTPUT_TOOL))
+    }
+}
+
+impl Message {
+    /// Returns the output of a request made with
+    /// [`Request::set_structured_output`].
+    pub fn structured_output(&self) -> Result<&serde_json::Value> {
+        self.content
+            .iter()
+            .find_map(|block| match block {
+                ContentBlock::ToolUse { name, input, .. } if name == STRUCTURED_OUTPUT_TOOL => {
+                    Some(input)
+                }
+                _ => None,
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "the response has no structured output (stop reason: {})",
+                    self.stop_reason
+                        .as_ref()
+                        .map_or("none", |stop_reason| stop_reason.as_str())
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Model;
+
+    #[test]
+    fn test_structured_output() {
+        let request = Request::builder(Model::Claude3Haiku)
+            .user("Name a color")
+            .structured_output(serde_json::json!({
+                "type": "object",
+                "properties": {"color": {"type": "string"}},
+            }))
+            .build()
+            .unwrap();
+        assert_eq!(request.tools.len(), 1);
+        assert_eq!(
+            request.tool_choice,
+            Some(ToolChoice::tool(STRUCTURED_OUTPUT_TOOL))
+        );
+
+        let message: Message = serde_json::from_value(serde_json::json!({
+            "id": "msg_01",
+            "role": "assistant",
+            "model": "claude-3-haiku-20240307",
+            "content": [
+                {"type": "tool_use", "id": "toolu_01", "name": "respond", "input": {"color": "red"}}
+            ],
+            "stop_reason": "tool_use",
+            "stop_sequence": null,
+        }))
+        .unwrap();
+        assert_eq!(
+            message.structured_output().unwrap(),
+            &serde_json::json!({"color": "red"})
+        );
+    }
+}
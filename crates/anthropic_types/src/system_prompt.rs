@@ -0,0 +1,182 @@
+use crate::CacheControl;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// The system prompt of a request, either plain text or a list of text
+/// blocks, which can be marked as the end of a cached prefix.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SystemPrompt {
+    Text(String),
+    Blocks(Vec<SystemBlock>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SystemBlock {
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+}
+
+impl SystemBlock {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text {
+            text: text.into(),
+            cache_control: None,
+        }
+    }
+
+    pub fn cache_control(&self) -> Option<CacheControl> {
+        match self {
+            Self::Text { cache_control, .. } => *cache_control,
+        }
+    }
+
+    pub fn with_cache_control(mut self, cache_control: CacheControl) -> Self {
+        match &mut self {
+            Self::Text {
+                cache_control: block_cache_control,
+                ..
+            } => *block_cache_control = Some(cache_control),
+        }
+        self
+    }
+}
+
+impl SystemPrompt {
+    /// Returns the text of all the prompt's blocks.
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            Self::Text(text) => Cow::Borrowed(text),
+            Self::Blocks(blocks) => blocks
+                .iter()
+                .map(|block| match block {
+                    SystemBlock::Text { text, .. } => text.as_str(),
+                })
+                .collect::<String>()
+                .into(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Text(text) => text.is_empty(),
+            Self::Blocks(blocks) => blocks.is_empty(),
+        }
+    }
+
+    /// Returns the length of the prompt's text, to size request buffers.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Self::Text(text) => text.len(),
+            Self::Blocks(blocks) => blocks
+                .iter()
+                .map(|block| match block {
+                    SystemBlock::Text { text, .. } => text.len(),
+                })
+                .sum(),
+        }
+    }
+
+    /// Whether any block of the prompt is marked with a [`CacheControl`].
+    pub fn has_cache_control(&self) -> bool {
+        self.cache_breakpoints() > 0
+    }
+
+    pub(crate) fn cache_breakpoints(&self) -> usize {
+        match self {
+            Self::Text(_) => 0,
+            Self::Blocks(blocks) => blocks
+                .iter()
+                .filter(|block| block.cache_control().is_some())
+                .count(),
+        }
+    }
+
+    /// Marks the prompt's last block as the end of a cached prefix,
+    /// converting a plain text prompt to a text block. An empty prompt is left
+    /// unchanged, since the API rejects empty text blocks.
+    pub fn set_cache_control(&mut self, cache_control: CacheControl) {
+        if self.is_empty() {
+            return;
+        }
+        let mut blocks = match std::mem::take(self) {
+            Self::Text(text) => vec![SystemBlock::text(text)],
+            Self::Blocks(blocks) => blocks,
+        };
+        if let Some(block) = blocks.pop() {
+            blocks.push(block.with_cache_control(cache_control));
+        }
+        *self = Self::Blocks(blocks);
+    }
+
+    pub fn with_cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.set_cache_control(cache_control);
+        self
+    }
+}
+
+impl Default for SystemPrompt {
+    fn default() -> Self {
+        Self::Text(String::new())
+    }
+}
+
+impl From<String> for SystemPrompt {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for SystemPrompt {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_string())
+    }
+}
+
+impl From<Vec<SystemBlock>> for SystemPrompt {
+    fn from(blocks: Vec<SystemBlock>) -> Self {
+        Self::Blocks(blocks)
+    }
+}
+
+impl PartialEq<str> for SystemPrompt {
+    fn eq(&self, other: &str) -> bool {
+        self.text() == other
+    }
+}
+
+impl PartialEq<&str> for SystemPrompt {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_system_prompt() {
+        let prompt = SystemPrompt::from("Be brief.");
+        assert_eq!(
+            serde_json::to_value(&prompt).unwrap(),
+            serde_json::json!("Be brief.")
+        );
+
+        let prompt = prompt.with_cache_control(CacheControl::Ephemeral);
+        assert!(prompt.has_cache_control());
+        assert_eq!(prompt, "Be brief.");
+        assert_eq!(
+            serde_json::to_value(&prompt).unwrap(),
+            serde_json::json!([{
+                "type": "text",
+                "text": "Be brief.",
+                "cache_control": {"type": "ephemeral"}
+            }])
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{trim_stop_sequence, ResponseEvent};
+
+/// What a [`TextAccumulator`] does once its text reaches its size limit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Keep the most recent text, dropping the oldest.
+    DropOldest,
+    /// Keep the beginning of the text, followed by `marker`, and ignore
+    /// everything after it.
+    Truncate { marker: String },
+}
+
+/// Accumulates the text streamed for a message, optionally capping the amount
+/// that is retained so that a runaway generation can't grow without bound.
+#[derive(Debug, Default)]
+pub struct TextAccumulator {
+    text: String,
+    /// Offset of the first retained byte in `text`. Text before it has been
+    /// dropped, and is only removed from `text` once it makes up half of it so
+    /// that dropping remains cheap.
+    start: usize,
+    limit: Option<(usize, OverflowPolicy)>,
+    dropped_len: usize,
+    truncated: bool,
+}
+
+impl TextAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an accumulator that retains at most `max_len` bytes of text,
+    /// not counting a truncation marker.
+    pub fn with_limit(max_len: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            limit: Some((max_len, policy)),
+            ..Self::default()
+        }
+    }
+
+    pub fn push(&mut self, delta: &str) {
+        if self.truncated {
+            self.dropped_len += delta.len();
+            return;
+        }
+
+        self.text.push_str(delta);
+        let Some((max_len, policy)) = &self.limit else {
+            return;
+        };
+        let max_len = *max_len;
+        if self.text.len() - self.start <= max_len {
+            return;
+        }
+
+        match policy {
+            OverflowPolicy::DropOldest => {
+                let new_start = ceil_char_boundary(&self.text, self.text.len() - max_len);
+                self.dropped_len += new_start - self.start;
+                self.start = new_start;
+                if self.start > self.text.len() / 2 {
+                    self.text.drain(..self.start);
+                    self.start = 0;
+                }
+            }
+            OverflowPolicy::Truncate { marker } => {
+                let end = floor_char_boundary(&self.text, self.start + max_len);
+                self.dropped_len += self.text.len() - end;
+                self.text.truncate(end);
+                self.text.push_str(marker);
+                self.truncated = true;
+            }
+        }
+    }
+
+    /// Removes a trailing `stop_sequence`, or a partial match of it, from the
+    /// retained text. See [`trim_stop_sequence`].
+    pub fn trim_stop_sequence(&mut self, stop_sequence: &str) {
+        if self.truncated {
+            return;
+        }
+        let trimmed_len = trim_stop_sequence(self.text(), stop_sequence).len();
+        self.text.truncate(self.start + trimmed_len);
+    }
+
+    /// Appends the text carried by `event`, if any.
+    pub fn push_event(&mut self, event: &ResponseEvent) {
+        if let Some(text) = event.text() {
+            self.push(text);
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text[self.start..]
+    }
+
+    pub fn into_text(mut self) -> String {
+        self.text.drain(..self.start);
+        self.text
+    }
+
+    /// Returns how many bytes of text were discarded because of the limit.
+    pub fn dropped_len(&self) -> usize {
+        self.dropped_len
+    }
+
+    /// Returns whether the text was cut off because of the limit.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_oldest() {
+        let mut accumulator = TextAccumulator::with_limit(4, OverflowPolicy::DropOldest);
+        accumulator.push("ab");
+        accumulator.push("cd");
+        assert_eq!(accumulator.text(), "abcd");
+        accumulator.push("éf");
+        assert_eq!(accumulator.text(), "déf");
+        assert_eq!(accumulator.dropped_len(), 3);
+        accumulator.push("ghijk");
+        assert_eq!(accumulator.text(), "hijk");
+        assert_eq!(accumulator.into_text(), "hijk");
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut accumulator = TextAccumulator::with_limit(
+            4,
+            OverflowPolicy::Truncate {
+                marker: "…".into()
+            },
+        );
+        accumulator.push("abcdef");
+        accumulator.push("gh");
+        assert_eq!(accumulator.text(), "abcd…");
+        assert!(accumulator.is_truncated());
+        assert_eq!(accumulator.dropped_len(), 4);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{ResponseEvent, TextAccumulator};
+use anyhow::Result;
+use futures::{AsyncWrite, AsyncWriteExt, Stream, StreamExt};
+
+/// A destination for streamed text.
+///
+/// Implementing this for a rope or a buffer lets streamed text be appended
+/// directly where it's displayed, instead of first being collected into one
+/// ever-growing `String`.
+pub trait TextSink {
+    fn push_str(&mut self, text: &str);
+}
+
+impl TextSink for String {
+    fn push_str(&mut self, text: &str) {
+        String::push_str(self, text);
+    }
+}
+
+impl TextSink for TextAccumulator {
+    fn push_str(&mut self, text: &str) {
+        self.push(text);
+    }
+}
+
+impl<T: TextSink + ?Sized> TextSink for &mut T {
+    fn push_str(&mut self, text: &str) {
+        (**self).push_str(text);
+    }
+}
+
+/// Adapts a closure into a [`TextSink`], for destinations that can't
+/// implement the trait directly.
+pub struct FnTextSink<F>(pub F);
+
+impl<F: FnMut(&str)> TextSink for FnTextSink<F> {
+    fn push_str(&mut self, text: &str) {
+        (self.0)(text);
+    }
+}
+
+/// Appends the text of every event in `events` to `sink`, stopping at the
+/// first error.
+pub async fn stream_text_into(
+    mut events: impl Stream<Item = Result<ResponseEvent>> + Unpin,
+    sink: &mut impl TextSink,
+) -> Result<()> {
+    while let Some(event) = events.next().await {
+        if let Some(text) = event?.text() {
+            sink.push_str(text);
+        }
+    }
+    Ok(())
+}
+
+/// When [`write_text_to`] flushes its writer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Flush after every text delta, so output appears as soon as it arrives.
+    #[default]
+    EveryDelta,
+    /// Flush after deltas containing a newline, which suits line-buffered
+    /// consumers such as terminals and pipes.
+    Newline,
+    /// Only flush once the stream has ended.
+    AtEnd,
+}
+
+/// Writes the text of every event in `events` to `writer`, for CLI and
+/// scripting uses where the output goes to a file, pipe or socket.
+///
+/// The writer is always flushed before returning successfully.
+pub async fn write_text_to(
+    mut events: impl Stream<Item = Result<ResponseEvent>> + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+    flush_policy: FlushPolicy,
+) -> Result<()> {
+    while let Some(event) = events.next().await {
+        if let Some(text) = event?.text() {
+            writer.write_all(text.as_bytes()).await?;
+            let flush = match flush_policy {
+                FlushPolicy::EveryDelta => true,
+                FlushPolicy::Newline => text.contains('\n'),
+                FlushPolicy::AtEnd => false,
+            };
+            if flush {
+                writer.flush().await?;
+            }
+        }
+    }
+    writer.flush().await?;
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use crate::ResponseEvent;
+use anyhow::Result;
+use futures::{future, stream::BoxStream, StreamExt};
+
+/// Turns `events` into a stream of the text they carry, for callers that
+/// don't need tool calls or metadata. Errors are passed through.
+pub fn text_stream(
+    events: BoxStream<'static, Result<ResponseEvent>>,
+) -> BoxStream<'static, Result<String>> {
+    events
+        .filter_map(|event| {
+            future::ready(match event {
+                Ok(event) => event
+                    .text()
+                    .filter(|text| !text.is_empty())
+                    .map(|text| Ok(text.to_string())),
+                Err(error) => Some(Err(error)),
+            })
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, stream};
+
+    #[test]
+    fn test_text_stream() {
+        let events = [
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"ping"}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello"}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":" world"}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+            r#"{"type":"message_stop"}"#,
+        ]
+        .map(|event| Ok(serde_json::from_str(event).unwrap()));
+        let text = block_on(text_stream(stream::iter(events).boxed()).collect::<Vec<_>>());
+        let text = text.into_iter().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(text, ["Hello", " world"]);
+    }
+}
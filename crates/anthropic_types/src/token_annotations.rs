@@ -0,0 +1,200 @@
+use crate::RequestMessage;
+
+/// Counts the tokens a message takes up in a request.
+pub trait MessageTokenCounter {
+    fn count_message_tokens(&self, message: &RequestMessage) -> usize;
+}
+
+impl<F: Fn(&RequestMessage) -> usize> MessageTokenCounter for F {
+    fn count_message_tokens(&self, message: &RequestMessage) -> usize {
+        self(message)
+    }
+}
+
+/// Counts tokens locally with [`crate::bpe_message_token_count`].
+#[cfg(feature = "bpe-tokenizer")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BpeTokenCounter;
+
+#[cfg(feature = "bpe-tokenizer")]
+impl MessageTokenCounter for BpeTokenCounter {
+    fn count_message_tokens(&self, message: &RequestMessage) -> usize {
+        crate::bpe_message_token_count(message)
+    }
+}
+
+/// A list of messages that remembers the token count of each message, along
+/// with a running total, so that only new or edited messages are counted
+/// again.
+///
+/// Counts are computed lazily with a [`MessageTokenCounter`], or can be
+/// provided with [`AnnotatedMessages::set_token_count`], e.g. once they have
+/// been returned by the API.
+#[derive(Clone, Debug, Default)]
+pub struct AnnotatedMessages {
+    messages: Vec<RequestMessage>,
+    token_counts: Vec<Option<usize>>,
+    /// The sum of the counts in `token_counts` that are known.
+    counted_total: usize,
+}
+
+impl AnnotatedMessages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn messages(&self) -> &[RequestMessage] {
+        &self.messages
+    }
+
+    pub fn into_messages(self) -> Vec<RequestMessage> {
+        self.messages
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn push(&mut self, message: RequestMessage) {
+        self.messages.push(message);
+        self.token_counts.push(None);
+    }
+
+    pub fn insert(&mut self, ix: usize, message: RequestMessage) {
+        self.messages.insert(ix, message);
+        self.token_counts.insert(ix, None);
+    }
+
+    pub fn pop(&mut self) -> Option<RequestMessage> {
+        let count = self.token_counts.pop()?;
+        self.counted_total -= count.unwrap_or(0);
+        self.messages.pop()
+    }
+
+    pub fn remove(&mut self, ix: usize) -> RequestMessage {
+        self.counted_total -= self.token_counts.remove(ix).unwrap_or(0);
+        self.messages.remove(ix)
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        while self.messages.len() > len {
+            self.pop();
+        }
+    }
+
+    /// Edits the message at `ix`, whose token count will be recomputed the
+    /// next time it's needed.
+    pub fn update(&mut self, ix: usize, update: impl FnOnce(&mut RequestMessage)) {
+        update(&mut self.messages[ix]);
+        self.invalidate(ix);
+    }
+
+    pub fn invalidate(&mut self, ix: usize) {
+        self.counted_total -= self.token_counts[ix].take().unwrap_or(0);
+    }
+
+    pub fn set_token_count(&mut self, ix: usize, token_count: usize) {
+        self.invalidate(ix);
+        self.token_counts[ix] = Some(token_count);
+        self.counted_total += token_count;
+    }
+
+    /// Returns the token count of the message at `ix` if it's already known.
+    pub fn cached_token_count(&self, ix: usize) -> Option<usize> {
+        self.token_counts[ix]
+    }
+
+    pub fn token_count(&mut self, ix: usize, counter: &impl MessageTokenCounter) -> usize {
+        if let Some(token_count) = self.token_counts[ix] {
+            return token_count;
+        }
+        let token_count = counter.count_message_tokens(&self.messages[ix]);
+        self.token_counts[ix] = Some(token_count);
+        self.counted_total += token_count;
+        token_count
+    }
+
+    /// Returns the total token count of all messages, only counting the
+    /// messages whose count isn't known yet.
+    pub fn total_tokens(&mut self, counter: &impl MessageTokenCounter) -> usize {
+        for ix in 0..self.messages.len() {
+            self.token_count(ix, counter);
+        }
+        self.counted_total
+    }
+
+    /// Returns the total token count of the messages whose count is known,
+    /// without counting the others.
+    pub fn counted_tokens(&self) -> usize {
+        self.counted_total
+    }
+
+    /// Whether the token count of every message is known, making
+    /// [`AnnotatedMessages::counted_tokens`] the exact total.
+    pub fn is_fully_counted(&self) -> bool {
+        self.token_counts.iter().all(Option::is_some)
+    }
+}
+
+impl From<Vec<RequestMessage>> for AnnotatedMessages {
+    fn from(messages: Vec<RequestMessage>) -> Self {
+        let token_counts = vec![None; messages.len()];
+        Self {
+            messages,
+            token_counts,
+            counted_total: 0,
+        }
+    }
+}
+
+impl FromIterator<RequestMessage> for AnnotatedMessages {
+    fn from_iter<T: IntoIterator<Item = RequestMessage>>(iter: T) -> Self {
+        Self::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_annotated_messages() {
+        let counted = Cell::new(0);
+        let counter = |message: &RequestMessage| {
+            counted.set(counted.get() + 1);
+            message.content.text().len()
+        };
+
+        let mut messages: AnnotatedMessages = [
+            RequestMessage::user("one"),
+            RequestMessage::assistant("three"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(messages.total_tokens(&counter), 8);
+        assert_eq!(counted.get(), 2);
+
+        messages.push(RequestMessage::user("seven"));
+        assert_eq!(messages.counted_tokens(), 8);
+        assert!(!messages.is_fully_counted());
+        assert_eq!(messages.total_tokens(&counter), 13);
+        assert_eq!(counted.get(), 3);
+
+        messages.update(0, |message| message.content.push_str("!!"));
+        assert_eq!(messages.total_tokens(&counter), 15);
+        assert_eq!(counted.get(), 4);
+
+        messages.set_token_count(1, 100);
+        assert_eq!(messages.total_tokens(&counter), 110);
+        messages.pop();
+        assert_eq!(messages.counted_tokens(), 105);
+        messages.remove(0);
+        assert_eq!(messages.counted_tokens(), 100);
+        assert_eq!(counted.get(), 4);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::{parse_partial_json, ContentBlock, ResponseEvent, TextDelta};
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A function the model may call, described by the JSON schema of its input.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ToolDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    pub input_schema: serde_json::Value,
+}
+
+impl ToolDefinition {
+    pub fn new(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        input_schema: serde_json::Value,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            input_schema,
+        }
+    }
+}
+
+/// Whether and which tools the model has to use.
+///
+/// The model may call several tools at once unless
+/// `disable_parallel_tool_use` is set, in which case it calls at most one,
+/// or exactly one with [`ToolChoice::Any`] and [`ToolChoice::Tool`].
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolChoice {
+    /// Let the model decide whether to use a tool.
+    Auto {
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        disable_parallel_tool_use: bool,
+    },
+    /// Use one of the tools.
+    Any {
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        disable_parallel_tool_use: bool,
+    },
+    /// Use the named tool.
+    Tool {
+        name: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        disable_parallel_tool_use: bool,
+    },
+}
+
+impl Default for ToolChoice {
+    fn default() -> Self {
+        Self::auto()
+    }
+}
+
+impl ToolChoice {
+    pub fn auto() -> Self {
+        Self::Auto {
+            disable_parallel_tool_use: false,
+        }
+    }
+
+    pub fn any() -> Self {
+        Self::Any {
+            disable_parallel_tool_use: false,
+        }
+    }
+
+    pub fn tool(name: impl Into<String>) -> Self {
+        Self::Tool {
+            name: name.into(),
+            disable_parallel_tool_use: false,
+        }
+    }
+
+    /// Makes the model call tools one at a time, e.g. for integrations that
+    /// can't run them concurrently.
+    pub fn sequential(mut self) -> Self {
+        *self.disable_parallel_tool_use_mut() = true;
+        self
+    }
+
+    pub fn is_parallel_tool_use_disabled(&self) -> bool {
+        match self {
+            Self::Auto {
+                disable_parallel_tool_use,
+            }
+            | Self::Any {
+                disable_parallel_tool_use,
+            }
+            | Self::Tool {
+                disable_parallel_tool_use,
+                ..
+            } => *disable_parallel_tool_use,
+        }
+    }
+
+    fn disable_parallel_tool_use_mut(&mut self) -> &mut bool {
+        match self {
+            Self::Auto {
+                disable_parallel_tool_use,
+            }
+            | Self::Any {
+                disable_parallel_tool_use,
+            }
+            | Self::Tool {
+                disable_parallel_tool_use,
+                ..
+            } => disable_parallel_tool_use,
+        }
+    }
+}
+
+/// Returns whether `name` is accepted by the API as a tool name.
+pub fn is_valid_tool_name(name: &str) -> bool {
+    (1..=64).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// A call to a tool made by the model, with its complete input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToolUse {
+    pub id: String,
+    pub name: String,
+    pub input: serde_json::Value,
+}
+
+/// Assembles the tool calls of a streamed response from the
+/// [`TextDelta::InputJsonDelta`]s of their content blocks.
+#[derive(Debug, Default)]
+pub struct ToolUseCollector {
+    pending: BTreeMap<u32, PendingToolUse>,
+}
+
+#[derive(Debug)]
+struct PendingToolUse {
+    id: String,
+    name: String,
+    input: serde_json::Value,
+    partial_json: String,
+}
+
+impl ToolUseCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds an event, returning the tool call it completed, if any.
+    pub fn push_event(&mut self, event: &ResponseEvent) -> Result<Option<ToolUse>> {
+        match event {
+            ResponseEvent::ContentBlockStart {
+                index,
+                content_block: ContentBlock::ToolUse { id, name, input },
+            } => {
+                self.pending.insert(
+                    *index,
+                    PendingToolUse {
+                        id: id.clone(),
+                        name: name.clone(),
+                        input: input.clone(),
+                        partial_json: String::new(),
+                    },
+                );
+            }
+            ResponseEvent::ContentBlockDelta {
+                index,
+                delta: TextDelta::InputJsonDelta { partial_json },
+            } => {
+                if let Some(tool_use) = self.pending.get_mut(index) {
+                    tool_use.partial_json.push_str(partial_json);
+                }
+            }
+            ResponseEvent::ContentBlockStop { index } => {
+                if let Some(tool_use) = self.pending.remove(index) {
+                    // Tools without parameters stream no input at all.
+                    let input = if tool_use.partial_json.trim().is_empty() {
+                        tool_use.input
+                    } else {
+                        serde_json::from_str(&tool_use.partial_json).with_context(|| {
+                            format!("invalid input for tool '{}'", tool_use.name)
+                        })?
+                    };
+                    return Ok(Some(ToolUse {
+                        id: tool_use.id,
+                        name: tool_use.name,
+                        input,
+                    }));
+                }
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    /// Returns the name and the input streamed so far of the tool call in
+    /// the content block at `index`, e.g. to show its progress.
+    pub fn partial_input(&self, index: u32) -> Option<(&str, &str)> {
+        let tool_use = self.pending.get(&index)?;
+        Some((&tool_use.name, &tool_use.partial_json))
+    }
+
+    /// Parses the input streamed so far of the tool call in the content
+    /// block at `index` as well as possible, see [`parse_partial_json`].
+    pub fn partial_value(&self, index: u32) -> Option<serde_json::Value> {
+        let tool_use = self.pending.get(&index)?;
+        parse_partial_json(&tool_use.partial_json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_tool_use() {
+        let events = [
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_01","name":"read_file","input":{}}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"path\": "}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"\"a.rs\"}"}}"#,
+            r#"{"type":"content_block_stop","index":1}"#,
+        ];
+        let mut collector = ToolUseCollector::new();
+        let mut tool_uses = Vec::new();
+        let mut partial_values = Vec::new();
+        for event in events {
+            let event: ResponseEvent = serde_json::from_str(event).unwrap();
+            tool_uses.extend(collector.push_event(&event).unwrap());
+            if tool_uses.is_empty() {
+                assert_eq!(collector.partial_input(1).unwrap().0, "read_file");
+                partial_values.push(collector.partial_value(1));
+            }
+        }
+        assert_eq!(
+            partial_values,
+            [
+                None,
+                Some(serde_json::json!({})),
+                Some(serde_json::json!({"path": "a.rs"}))
+            ]
+        );
+        assert_eq!(
+            tool_uses,
+            vec![ToolUse {
+                id: "toolu_01".into(),
+                name: "read_file".into(),
+                input: serde_json::json!({"path": "a.rs"}),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_tool_choice() {
+        assert_eq!(
+            serde_json::to_value(ToolChoice::tool("read_file")).unwrap(),
+            serde_json::json!({"type": "tool", "name": "read_file"})
+        );
+        let choice = ToolChoice::any().sequential();
+        assert!(choice.is_parallel_tool_use_disabled());
+        assert_eq!(
+            serde_json::to_value(&choice).unwrap(),
+            serde_json::json!({"type": "any", "disable_parallel_tool_use": true})
+        );
+        let choice: ToolChoice = serde_json::from_str(r#"{"type": "auto"}"#).unwrap();
+        assert_eq!(choice, ToolChoice::auto());
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{Model, ResponseEvent, Usage};
+use anyhow::Result;
+use futures::{
+    channel::oneshot,
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use std::{
+    collections::BTreeMap,
+    iter::Sum,
+    ops::{Add, AddAssign},
+};
+
+impl Usage {
+    /// The total number of tokens billed, including those written to and read
+    /// from the prompt cache.
+    pub fn total_tokens(&self) -> u32 {
+        self.total_input_tokens()
+            .saturating_add(self.output_tokens.unwrap_or(0))
+    }
+
+    /// The number of input tokens, including those written to and read from
+    /// the prompt cache.
+    pub fn total_input_tokens(&self) -> u32 {
+        self.input_tokens
+            .unwrap_or(0)
+            .saturating_add(self.cache_creation_input_tokens.unwrap_or(0))
+            .saturating_add(self.cache_read_input_tokens.unwrap_or(0))
+    }
+
+    /// The fraction of the input tokens that were read from the prompt
+    /// cache, or `None` if there were no input tokens.
+    pub fn cache_hit_ratio(&self) -> Option<f64> {
+        let total = self.total_input_tokens();
+        (total > 0).then(|| self.cache_read_input_tokens.unwrap_or(0) as f64 / total as f64)
+    }
+
+    /// Replaces the counts with those of `reported`, the cumulative usage
+    /// reported by a later event of the same response. Counts it doesn't
+    /// report are kept.
+    pub fn update(&mut self, reported: &Usage) {
+        self.input_tokens = reported.input_tokens.or(self.input_tokens);
+        self.output_tokens = reported.output_tokens.or(self.output_tokens);
+        self.cache_creation_input_tokens = reported
+            .cache_creation_input_tokens
+            .or(self.cache_creation_input_tokens);
+        self.cache_read_input_tokens = reported
+            .cache_read_input_tokens
+            .or(self.cache_read_input_tokens);
+    }
+}
+
+impl ResponseEvent {
+    /// Returns the usage reported by this event, if any.
+    pub fn usage(&self) -> Option<&Usage> {
+        match self {
+            Self::MessageStart { message } => message.usage.as_ref(),
+            Self::MessageDelta { usage, .. } => Some(usage),
+            _ => None,
+        }
+    }
+}
+
+/// Passes `events` through, and resolves the returned receiver with the
+/// response's final usage once the stream ends. If the stream is dropped
+/// early, the receiver gets the usage reported up to then.
+pub fn with_final_usage(
+    events: BoxStream<'static, Result<ResponseEvent>>,
+) -> (
+    BoxStream<'static, Result<ResponseEvent>>,
+    oneshot::Receiver<Usage>,
+) {
+    let (tx, rx) = oneshot::channel();
+    let tracker = UsageTracker {
+        usage: Usage::default(),
+        tx: Some(tx),
+    };
+    let events = stream::unfold((events, tracker), |(mut events, mut tracker)| async move {
+        match events.next().await {
+            Some(event) => {
+                if let Some(usage) = event.as_ref().ok().and_then(ResponseEvent::usage) {
+                    tracker.usage.update(usage);
+                }
+                Some((event, (events, tracker)))
+            }
+            None => {
+                tracker.send();
+                None
+            }
+        }
+    });
+    (events.boxed(), rx)
+}
+
+struct UsageTracker {
+    usage: Usage,
+    tx: Option<oneshot::Sender<Usage>>,
+}
+
+impl UsageTracker {
+    fn send(&mut self) {
+        if let Some(tx) = self.tx.take() {
+            tx.send(self.usage.clone()).ok();
+        }
+    }
+}
+
+impl Drop for UsageTracker {
+    fn drop(&mut self) {
+        self.send();
+    }
+}
+
+fn add_counts(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.saturating_add(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Adds up the token counts. Unknown fields are taken from the left-hand side.
+impl AddAssign<&Usage> for Usage {
+    fn add_assign(&mut self, other: &Usage) {
+        self.input_tokens = add_counts(self.input_tokens, other.input_tokens);
+        self.output_tokens = add_counts(self.output_tokens, other.output_tokens);
+        self.cache_creation_input_tokens = add_counts(
+            self.cache_creation_input_tokens,
+            other.cache_creation_input_tokens,
+        );
+        self.cache_read_input_tokens =
+            add_counts(self.cache_read_input_tokens, other.cache_read_input_tokens);
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        *self += &other;
+    }
+}
+
+impl Add for Usage {
+    type Output = Usage;
+
+    fn add(mut self, other: Usage) -> Usage {
+        self += &other;
+        self
+    }
+}
+
+impl Add<&Usage> for Usage {
+    type Output = Usage;
+
+    fn add(mut self, other: &Usage) -> Usage {
+        self += other;
+        self
+    }
+}
+
+impl Sum for Usage {
+    fn sum<I: Iterator<Item = Usage>>(iter: I) -> Self {
+        iter.fold(Usage::default(), Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Usage> for Usage {
+    fn sum<I: Iterator<Item = &'a Usage>>(iter: I) -> Self {
+        iter.fold(Usage::default(), Add::add)
+    }
+}
+
+/// Usage aggregated per model, e.g. over a session that used several models.
+#[derive(Clone, Debug, Default)]
+pub struct UsageBreakdown {
+    by_model: BTreeMap<String, Usage>,
+}
+
+impl UsageBreakdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, model: &Model, usage: &Usage) {
+        *self.by_model.entry(model.id().to_string()).or_default() += usage;
+    }
+
+    /// Returns the usage recorded for the model with the given id.
+    pub fn get(&self, model_id: &str) -> Option<&Usage> {
+        self.by_model.get(model_id)
+    }
+
+    /// Iterates over the usage of each model, keyed by model id.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Usage)> {
+        self.by_model
+            .iter()
+            .map(|(model_id, usage)| (model_id.as_str(), usage))
+    }
+
+    pub fn total(&self) -> Usage {
+        self.by_model.values().sum()
+    }
+}
+
+impl AddAssign<&UsageBreakdown> for UsageBreakdown {
+    fn add_assign(&mut self, other: &UsageBreakdown) {
+        for (model_id, usage) in &other.by_model {
+            *self.by_model.entry(model_id.clone()).or_default() += usage;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(input_tokens: u32, output_tokens: Option<u32>) -> Usage {
+        Usage {
+            input_tokens: Some(input_tokens),
+            output_tokens,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_usage_arithmetic() {
+        let total: Usage = [usage(10, Some(5)), usage(3, None)].iter().sum();
+        assert_eq!(total.input_tokens, Some(13));
+        assert_eq!(total.output_tokens, Some(5));
+        assert_eq!(total.cache_read_input_tokens, None);
+        assert_eq!(total.total_tokens(), 18);
+
+        let mut breakdown = UsageBreakdown::new();
+        breakdown.record(&Model::Claude3Opus, &usage(1, Some(2)));
+        breakdown.record(&Model::Claude3_5Sonnet, &usage(3, Some(4)));
+        breakdown.record(&Model::Claude3Opus, &usage(5, Some(6)));
+        assert_eq!(
+            breakdown
+                .get(Model::Claude3Opus.id())
+                .unwrap()
+                .total_tokens(),
+            14
+        );
+        assert_eq!(breakdown.total().total_tokens(), 21);
+
+        let huge = Usage {
+            cache_read_input_tokens: Some(u32::MAX),
+            ..usage(1, Some(1))
+        };
+        assert_eq!(huge.total_input_tokens(), u32::MAX);
+        assert_eq!(huge.total_tokens(), u32::MAX);
+    }
+
+    #[test]
+    fn test_final_usage() {
+        let events = [
+            r#"{"type":"message_start","message":{"id":"msg_01","role":"assistant","content":[],"model":"claude-3-haiku-20240307","usage":{"input_tokens":25,"output_tokens":1}}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":15}}"#,
+            r#"{"type":"message_stop"}"#,
+        ]
+        .map(|event| Ok(serde_json::from_str(event).unwrap()));
+        let (events, usage) = with_final_usage(stream::iter(events).boxed());
+        let events = futures::executor::block_on(events.collect::<Vec<_>>());
+        assert_eq!(events.len(), 4);
+        let usage = futures::executor::block_on(usage).unwrap();
+        assert_eq!(usage.input_tokens, Some(25));
+        assert_eq!(usage.output_tokens, Some(15));
+    }
+}
@@ -0,0 +1,230 @@
+use crate::ResponseEvent;
+
+/// Returns the content of the first `<tag>...</tag>` section in `text`, such
+/// as the `<answer>` the model was asked to wrap its answer in.
+pub fn extract_tag<'a>(text: &'a str, tag: &str) -> Option<&'a str> {
+    tag_sections(text, tag).next()
+}
+
+/// Returns the content of every `<tag>...</tag>` section in `text`, in order.
+pub fn extract_tags<'a>(text: &'a str, tag: &str) -> Vec<&'a str> {
+    tag_sections(text, tag).collect()
+}
+
+fn tag_sections<'a>(mut text: &'a str, tag: &str) -> impl Iterator<Item = &'a str> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    std::iter::from_fn(move || {
+        let start = text.find(&open)? + open.len();
+        let len = text[start..].find(&close)?;
+        let content = &text[start..start + len];
+        text = &text[start + len + close.len()..];
+        Some(content)
+    })
+}
+
+/// A section of a response wrapped in one of the tags a [`TagParser`] looks
+/// for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaggedSection {
+    pub tag: String,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TagEvent {
+    /// The opening tag of a section was streamed.
+    Start { tag: String },
+    /// Text was streamed, either inside the section with the given tag or
+    /// outside of any section.
+    Text { tag: Option<String>, text: String },
+    /// The closing tag of a section was streamed.
+    End(TaggedSection),
+}
+
+/// Incrementally recognizes tagged sections in streamed text.
+///
+/// Tags split across deltas are held back until they're complete. Sections
+/// don't nest: inside a section, only its own closing tag is recognized.
+#[derive(Clone, Debug)]
+pub struct TagParser {
+    tags: Vec<String>,
+    /// Streamed text that hasn't been emitted yet because it may be the start
+    /// of a tag.
+    pending: String,
+    current: Option<TaggedSection>,
+}
+
+impl TagParser {
+    pub fn new(tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            tags: tags.into_iter().map(Into::into).collect(),
+            pending: String::new(),
+            current: None,
+        }
+    }
+
+    pub fn push(&mut self, delta: &str) -> Vec<TagEvent> {
+        self.pending.push_str(delta);
+        let mut events = Vec::new();
+        let mut text_end = 0;
+        let mut ix = 0;
+        while let Some(offset) = self.pending[ix..].find('<') {
+            let lt = ix + offset;
+            let candidate = &self.pending[lt..];
+            match self.match_tag(candidate) {
+                TagMatch::Partial => {
+                    self.emit_text(&mut events, text_end, lt);
+                    self.pending.drain(..lt);
+                    return events;
+                }
+                TagMatch::None => ix = lt + 1,
+                TagMatch::Open(tag, len) => {
+                    self.emit_text(&mut events, text_end, lt);
+                    events.push(TagEvent::Start { tag: tag.clone() });
+                    self.current = Some(TaggedSection {
+                        tag,
+                        content: String::new(),
+                    });
+                    ix = lt + len;
+                    text_end = ix;
+                }
+                TagMatch::Close(len) => {
+                    self.emit_text(&mut events, text_end, lt);
+                    if let Some(section) = self.current.take() {
+                        events.push(TagEvent::End(section));
+                    }
+                    ix = lt + len;
+                    text_end = ix;
+                }
+            }
+        }
+        let len = self.pending.len();
+        self.emit_text(&mut events, text_end, len);
+        self.pending.clear();
+        events
+    }
+
+    /// Feeds the text carried by `event`, if any.
+    pub fn push_event(&mut self, event: &ResponseEvent) -> Vec<TagEvent> {
+        match event.text() {
+            Some(text) => self.push(text),
+            None => Vec::new(),
+        }
+    }
+
+    /// Emits any text held back at the end of the stream. A section that
+    /// wasn't closed isn't reported as ended.
+    pub fn finish(mut self) -> Vec<TagEvent> {
+        let mut events = Vec::new();
+        let len = self.pending.len();
+        self.emit_text(&mut events, 0, len);
+        events
+    }
+
+    fn emit_text(&mut self, events: &mut Vec<TagEvent>, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+        let text = self.pending[start..end].to_string();
+        if let Some(section) = &mut self.current {
+            section.content.push_str(&text);
+        }
+        events.push(TagEvent::Text {
+            tag: self.current.as_ref().map(|section| section.tag.clone()),
+            text,
+        });
+    }
+
+    fn match_tag(&self, candidate: &str) -> TagMatch {
+        let mut partial = false;
+        let mut check = |tag_text: String| -> Option<usize> {
+            if candidate.starts_with(&tag_text) {
+                Some(tag_text.len())
+            } else {
+                partial |= tag_text.starts_with(candidate);
+                None
+            }
+        };
+        match &self.current {
+            Some(section) => {
+                if let Some(len) = check(format!("</{}>", section.tag)) {
+                    return TagMatch::Close(len);
+                }
+            }
+            None => {
+                for tag in &self.tags {
+                    if let Some(len) = check(format!("<{tag}>")) {
+                        return TagMatch::Open(tag.clone(), len);
+                    }
+                }
+            }
+        }
+        if partial {
+            TagMatch::Partial
+        } else {
+            TagMatch::None
+        }
+    }
+}
+
+enum TagMatch {
+    None,
+    Partial,
+    Open(String, usize),
+    Close(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_tags() {
+        let text = "<thinking>hmm</thinking><answer>42</answer> and <answer>43</answer>";
+        assert_eq!(extract_tag(text, "answer"), Some("42"));
+        assert_eq!(extract_tags(text, "answer"), vec!["42", "43"]);
+        assert_eq!(extract_tag(text, "code"), None);
+        assert_eq!(extract_tag("<answer>unclosed", "answer"), None);
+    }
+
+    #[test]
+    fn test_tag_parser() {
+        let mut parser = TagParser::new(["answer"]);
+        let mut events = Vec::new();
+        for delta in ["Sure. <ans", "wer>4", "2 < 43</an", "swer> done <b"] {
+            events.extend(parser.push(delta));
+        }
+        events.extend(parser.finish());
+
+        let sections = events
+            .iter()
+            .filter_map(|event| match event {
+                TagEvent::End(section) => Some(section.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sections,
+            vec![TaggedSection {
+                tag: "answer".into(),
+                content: "42 < 43".into(),
+            }]
+        );
+
+        let outside = events
+            .iter()
+            .filter_map(|event| match event {
+                TagEvent::Text { tag: None, text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<String>();
+        assert_eq!(outside, "Sure.  done <b");
+        assert_eq!(
+            events[1],
+            TagEvent::Start {
+                tag: "answer".into()
+            }
+        );
+    }
+}
@@ -4707,11 +4707,11 @@ async fn complete_with_anthropic(
             match message.role() {
                 LanguageModelRole::LanguageModelUser => Some(anthropic::RequestMessage {
                     role: anthropic::Role::User,
-                    content: message.content,
+                    content: message.content.into(),
                 }),
                 LanguageModelRole::LanguageModelAssistant => Some(anthropic::RequestMessage {
                     role: anthropic::Role::Assistant,
-                    content: message.content,
+                    content: message.content.into(),
                 }),
                 // Anthropic's API breaks system instructions out as a separate field rather
                 // than having a system message role.
@@ -4737,8 +4737,9 @@ async fn complete_with_anthropic(
             model,
             messages,
             stream: true,
-            system: system_message,
+            system: system_message.into(),
             max_tokens: 4092,
+            ..Default::default()
         },
         None,
     )
@@ -4761,7 +4762,7 @@ async fn complete_with_anthropic(
             }
             anthropic::ResponseEvent::ContentBlockStart { content_block, .. } => {
                 match content_block {
-                    anthropic::ContentBlock::Text { text } => {
+                    anthropic::ContentBlock::Text { text, .. } => {
                         if !text.is_empty() {
                             response.send(proto::LanguageModelResponse {
                                 choices: vec![proto::LanguageModelChoiceDelta {
@@ -4776,6 +4777,10 @@ async fn complete_with_anthropic(
                             })?;
                         }
                     }
+                    // Tool calls and reasoning aren't forwarded to clients yet.
+                    anthropic::ContentBlock::ToolUse { .. }
+                    | anthropic::ContentBlock::Thinking { .. }
+                    | anthropic::ContentBlock::RedactedThinking { .. } => {}
                 }
             }
             anthropic::ResponseEvent::ContentBlockDelta { delta, .. } => match delta {
@@ -4785,13 +4790,17 @@ async fn complete_with_anthropic(
                             index: 0,
                             delta: Some(proto::LanguageModelResponseMessage {
                                 role: Some(current_role as i32),
-                                content: Some(text),
+                                content: Some(text.into()),
                                 tool_calls: Vec::new(),
                             }),
                             finish_reason: None,
                         }],
                     })?;
                 }
+                anthropic::TextDelta::InputJsonDelta { .. }
+                | anthropic::TextDelta::CitationsDelta { .. }
+                | anthropic::TextDelta::ThinkingDelta { .. }
+                | anthropic::TextDelta::SignatureDelta { .. } => {}
             },
             anthropic::ResponseEvent::MessageDelta { delta, .. } => {
                 if let Some(stop_reason) = delta.stop_reason {
@@ -4799,7 +4808,7 @@ async fn complete_with_anthropic(
                         choices: vec![proto::LanguageModelChoiceDelta {
                             index: 0,
                             delta: None,
-                            finish_reason: Some(stop_reason),
+                            finish_reason: Some(stop_reason.to_string()),
                         }],
                     })?;
                 }
@@ -4807,6 +4816,7 @@ async fn complete_with_anthropic(
             anthropic::ResponseEvent::ContentBlockStop { .. } => {}
             anthropic::ResponseEvent::MessageStop {} => {}
             anthropic::ResponseEvent::Ping {} => {}
+            anthropic::ResponseEvent::Error { error } => Err(anyhow!(error))?,
         }
     }
 
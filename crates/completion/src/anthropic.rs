@@ -1,12 +1,11 @@
 use crate::{count_open_ai_tokens, LanguageModelCompletionProvider};
 use crate::{CompletionProvider, LanguageModel, LanguageModelRequest};
-use anthropic::{stream_completion, Model as AnthropicModel, Request, RequestMessage};
+use anthropic::Model as AnthropicModel;
 use anyhow::{anyhow, Result};
 use editor::{Editor, EditorElement, EditorStyle};
-use futures::{future::BoxFuture, stream::BoxStream, FutureExt, StreamExt};
+use futures::{future::BoxFuture, stream::BoxStream, FutureExt};
 use gpui::{AnyView, AppContext, Task, TextStyle, View};
 use http::HttpClient;
-use language_model::Role;
 use settings::Settings;
 use std::time::Duration;
 use std::{env, sync::Arc};
@@ -96,43 +95,22 @@ impl LanguageModelCompletionProvider for AnthropicCompletionProvider {
         &self,
         request: LanguageModelRequest,
     ) -> BoxFuture<'static, Result<BoxStream<'static, Result<String>>>> {
-        let request = self.to_anthropic_request(request);
-
         let http_client = self.http_client.clone();
         let api_key = self.api_key.clone();
-        let api_url = self.api_url.clone();
-        let low_speed_timeout = self.low_speed_timeout;
+        let settings = anthropic::ProviderSettings {
+            model: self.model.clone(),
+            api_url: self.api_url.clone(),
+            low_speed_timeout: self.low_speed_timeout,
+        };
         async move {
             let api_key = api_key.ok_or_else(|| anyhow!("missing api key"))?;
-            let request = stream_completion(
+            anthropic::stream_language_model_completion(
                 http_client.as_ref(),
-                &api_url,
                 &api_key,
+                &settings,
                 request,
-                low_speed_timeout,
-            );
-            let response = request.await?;
-            let stream = response
-                .filter_map(|response| async move {
-                    match response {
-                        Ok(response) => match response {
-                            anthropic::ResponseEvent::ContentBlockStart {
-                                content_block, ..
-                            } => match content_block {
-                                anthropic::ContentBlock::Text { text } => Some(Ok(text)),
-                            },
-                            anthropic::ResponseEvent::ContentBlockDelta { delta, .. } => {
-                                match delta {
-                                    anthropic::TextDelta::TextDelta { text } => Some(Ok(text)),
-                                }
-                            }
-                            _ => None,
-                        },
-                        Err(error) => Some(Err(error)),
-                    }
-                })
-                .boxed();
-            Ok(stream)
+            )
+            .await
         }
         .boxed()
     }
@@ -172,43 +150,6 @@ impl AnthropicCompletionProvider {
         self.low_speed_timeout = low_speed_timeout;
         self.settings_version = settings_version;
     }
-
-    fn to_anthropic_request(&self, mut request: LanguageModelRequest) -> Request {
-        request.preprocess_anthropic();
-
-        let model = match request.model {
-            LanguageModel::Anthropic(model) => model,
-            _ => self.model.clone(),
-        };
-
-        let mut system_message = String::new();
-        if request
-            .messages
-            .first()
-            .map_or(false, |message| message.role == Role::System)
-        {
-            system_message = request.messages.remove(0).content;
-        }
-
-        Request {
-            model,
-            messages: request
-                .messages
-                .iter()
-                .map(|msg| RequestMessage {
-                    role: match msg.role {
-                        Role::User => anthropic::Role::User,
-                        Role::Assistant => anthropic::Role::Assistant,
-                        Role::System => unreachable!("filtered out by preprocess_request"),
-                    },
-                    content: msg.content.clone(),
-                })
-                .collect(),
-            stream: true,
-            system: system_message,
-            max_tokens: 4092,
-        }
-    }
 }
 
 struct AuthenticationPrompt {
@@ -213,18 +213,32 @@ pub fn client(proxy: Option<Uri>) -> Arc<dyn HttpClient> {
     })
 }
 
-fn read_proxy_from_env() -> Option<Uri> {
+/// Returns the proxy configured by the `ALL_PROXY`, `HTTPS_PROXY` and
+/// `http_proxy` environment variables, if any.
+///
+/// As with curl, the uppercase `HTTP_PROXY` is ignored, since CGI programs
+/// receive it from the `Proxy` request header.
+pub fn read_proxy_from_env() -> Option<Uri> {
+    proxy_from_vars(|name| std::env::var(name).ok())
+}
+
+/// Returns the proxy that requests to `url` should go through according to
+/// the environment, skipping it for the hosts listed in `NO_PROXY`.
+pub fn read_proxy_from_env_for(url: &Uri) -> Option<Uri> {
+    proxy_from_vars_for(url, |name| std::env::var(name).ok())
+}
+
+fn proxy_from_vars(var: impl Fn(&str) -> Option<String>) -> Option<Uri> {
     const ENV_VARS: &[&str] = &[
         "ALL_PROXY",
         "all_proxy",
         "HTTPS_PROXY",
         "https_proxy",
-        "HTTP_PROXY",
         "http_proxy",
     ];
 
-    for var in ENV_VARS {
-        if let Ok(env) = std::env::var(var) {
+    for name in ENV_VARS {
+        if let Some(env) = var(name) {
             return env.parse::<Uri>().ok();
         }
     }
@@ -232,6 +246,67 @@ fn read_proxy_from_env() -> Option<Uri> {
     None
 }
 
+fn proxy_from_vars_for(url: &Uri, var: impl Fn(&str) -> Option<String>) -> Option<Uri> {
+    let no_proxy = var("NO_PROXY").or_else(|| var("no_proxy"));
+    if let Some(no_proxy) = no_proxy {
+        if bypasses_proxy(no_proxy.split(','), url) {
+            return None;
+        }
+    }
+    proxy_from_vars(var)
+}
+
+/// Returns whether requests to `url` skip the proxy because of one of the
+/// given entries, which are in the format of `NO_PROXY`.
+pub fn bypasses_proxy<'a>(no_proxy: impl IntoIterator<Item = &'a str>, url: &Uri) -> bool {
+    let Some(host) = url.host() else {
+        return false;
+    };
+    let host = host.to_lowercase();
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = url.port_u16().or_else(|| match url.scheme_str() {
+        Some("https") => Some(443),
+        Some("http") => Some(80),
+        _ => None,
+    });
+    no_proxy.into_iter().any(|entry| {
+        let entry = entry.trim().to_lowercase();
+        if entry.is_empty() {
+            return false;
+        }
+        if entry == "*" {
+            return true;
+        }
+        let (entry_host, entry_port) = split_port(&entry);
+        if entry_port.is_some() && entry_port != port {
+            return false;
+        }
+        let entry_host = entry_host
+            .trim_start_matches("*.")
+            .trim_start_matches('.')
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        host == entry_host
+            || host
+                .strip_suffix(entry_host)
+                .map_or(false, |prefix| prefix.ends_with('.'))
+    })
+}
+
+/// Splits a `NO_PROXY` entry into its host and port, leaving bare IPv6
+/// addresses intact.
+fn split_port(entry: &str) -> (&str, Option<u16>) {
+    if let Some((host, port)) = entry.rsplit_once(':') {
+        let is_bare_ipv6 = host.contains(':') && !host.ends_with(']');
+        if !is_bare_ipv6 {
+            if let Ok(port) = port.parse() {
+                return (host, Some(port));
+            }
+        }
+    }
+    (entry, None)
+}
+
 impl HttpClient for isahc::HttpClient {
     fn send(
         &self,
@@ -317,3 +392,70 @@ impl HttpClient for FakeHttpClient {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn proxy_for(vars: &[(&str, &str)], url: &str) -> Option<String> {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        proxy_from_vars_for(&url.parse().unwrap(), |name| {
+            vars.get(name).map(|value| value.to_string())
+        })
+        .map(|proxy| proxy.to_string())
+    }
+
+    #[test]
+    fn test_proxy_precedence() {
+        let vars = [
+            ("HTTPS_PROXY", "http://upper:1"),
+            ("https_proxy", "http://lower:2"),
+        ];
+        assert_eq!(
+            proxy_for(&vars, "https://api.anthropic.com").as_deref(),
+            Some("http://upper:1/")
+        );
+
+        let vars = [
+            ("ALL_PROXY", "socks5://all:4"),
+            ("HTTPS_PROXY", "http://upper:1"),
+        ];
+        assert_eq!(
+            proxy_for(&vars, "https://api.anthropic.com").as_deref(),
+            Some("socks5://all:4/")
+        );
+
+        let vars = [("HTTP_PROXY", "http://plain:3")];
+        assert_eq!(proxy_for(&vars, "http://localhost:8080"), None);
+        let vars = [("http_proxy", "http://plain:3")];
+        assert_eq!(
+            proxy_for(&vars, "http://localhost:8080").as_deref(),
+            Some("http://plain:3/")
+        );
+
+        assert_eq!(proxy_for(&[], "https://api.anthropic.com"), None);
+    }
+
+    #[test]
+    fn test_no_proxy() {
+        let vars = [
+            ("HTTPS_PROXY", "http://proxy:1"),
+            (
+                "NO_PROXY",
+                "localhost, .internal.example.com,gateway:8443,[::1]",
+            ),
+        ];
+        assert_eq!(proxy_for(&vars, "https://localhost"), None);
+        assert_eq!(proxy_for(&vars, "https://llm.internal.example.com"), None);
+        assert_eq!(proxy_for(&vars, "https://internal.example.com"), None);
+        assert_eq!(proxy_for(&vars, "https://gateway:8443"), None);
+        assert_eq!(proxy_for(&vars, "https://[::1]:9000"), None);
+        assert!(proxy_for(&vars, "https://gateway").is_some());
+        assert!(proxy_for(&vars, "https://notlocalhost").is_some());
+        assert!(proxy_for(&vars, "https://api.anthropic.com").is_some());
+
+        let vars = [("HTTPS_PROXY", "http://proxy:1"), ("no_proxy", "*")];
+        assert_eq!(proxy_for(&vars, "https://api.anthropic.com"), None);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::LanguageModelRequest;
-pub use anthropic::Model as AnthropicModel;
+pub use anthropic_types::Model as AnthropicModel;
 pub use ollama::Model as OllamaModel;
 pub use open_ai::Model as OpenAiModel;
 use schemars::JsonSchema;
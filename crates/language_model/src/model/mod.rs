@@ -1,6 +1,6 @@
 pub mod cloud_model;
 
-pub use anthropic::Model as AnthropicModel;
+pub use anthropic_types::Model as AnthropicModel;
 pub use cloud_model::*;
 pub use ollama::Model as OllamaModel;
 pub use open_ai::Model as OpenAiModel;